@@ -1,4 +1,8 @@
-use lancedb::{arrow::IntoArrow, ipc::ipc_file_to_batches, table::merge::MergeInsertBuilder};
+use lancedb::{
+    arrow::IntoArrow,
+    ipc::ipc_file_to_batches,
+    table::{merge::MergeInsertBuilder, WriteResult},
+};
 
 use crate::error::convert_error;
 
@@ -27,7 +31,7 @@ impl NativeMergeInsertBuilder {
         this
     }
 
-    pub async fn execute(&self, buf: &[u8]) -> Result<(), String> {
+    pub async fn execute(&self, buf: &[u8]) -> Result<WriteResult, String> {
         let data = ipc_file_to_batches(buf.to_vec())
             .and_then(IntoArrow::into_arrow)
             .map_err(|e| format!("Failed to read IPC file: {}", convert_error(&e)))?;
@@ -1,7 +1,7 @@
 use arrow_ipc::writer::FileWriter;
 use lancedb::ipc::ipc_file_to_batches;
 use lancedb::table::{
-    AddDataMode, Table as LanceDbTable
+    AddDataMode, Table as LanceDbTable, WriteResult
 };
 use std::ffi::{CString, CStr};
 use std::os::raw::{c_char};
@@ -58,7 +58,7 @@ impl Table {
         writer.into_inner().map_err(|e| format!("Failed to get IPC file: {}", e))
     }
 
-    pub async fn add(&self, buf: Vec<u8>, mode: String) -> Result<(), String> {
+    pub async fn add(&self, buf: Vec<u8>, mode: String) -> Result<WriteResult, String> {
         let batches = ipc_file_to_batches(buf)
             .map_err(|e| format!("Failed to read IPC file: {}", e))?;
         let mut op = self.inner_ref()?.add(batches);
@@ -80,7 +80,7 @@ impl Table {
             .map_err(|e| e.to_string())
     }
 
-    pub async fn delete(&self, predicate: String) -> Result<(), String> {
+    pub async fn delete(&self, predicate: String) -> Result<WriteResult, String> {
         self.inner_ref()?.delete(&predicate).await.map_err(|e| e.to_string())
     }
 
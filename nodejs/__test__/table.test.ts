@@ -523,6 +523,15 @@ describe("When creating an index", () => {
     expect(stats).toBeUndefined();
   });
 
+  test("should be able to drop an index", async () => {
+    await tbl.createIndex("id");
+    expect(await tbl.listIndices()).toHaveLength(1);
+
+    await tbl.dropIndex("id_idx");
+    expect(await tbl.listIndices()).toHaveLength(0);
+    expect(await tbl.indexStats("id_idx")).toBeUndefined();
+  });
+
   // TODO: Move this test to the query API test (making sure we can reject queries
   // when the dimension is incorrect)
   test("two columns with different dimensions", async () => {
@@ -34,6 +34,7 @@ export {
   ClientConfig,
   TimeoutConfig,
   RetryConfig,
+  WriteResult,
 } from "./native.js";
 
 export {
@@ -1,5 +1,5 @@
 import { Data, fromDataToBuffer } from "./arrow";
-import { NativeMergeInsertBuilder } from "./native";
+import { NativeMergeInsertBuilder, WriteResult } from "./native";
 
 /** A builder used to create and run a merge insert operation */
 export class MergeInsertBuilder {
@@ -61,10 +61,11 @@ export class MergeInsertBuilder {
   /**
    * Executes the merge insert operation
    *
-   * Nothing is returned but the `Table` is updated
+   * The `Table` is updated and the number of inserted, updated and deleted
+   * rows is returned
    */
-  async execute(data: Data): Promise<void> {
+  async execute(data: Data): Promise<WriteResult> {
     const buffer = await fromDataToBuffer(data);
-    await this.#native.execute(buffer);
+    return await this.#native.execute(buffer);
   }
 }
@@ -37,6 +37,7 @@ import {
   IndexStatistics,
   OptimizeStats,
   Table as _NativeTable,
+  WriteResult,
 } from "./native";
 import { Query, VectorQuery } from "./query";
 import { sanitizeTable } from "./sanitize";
@@ -123,8 +124,12 @@ export abstract class Table {
   /**
    * Insert records into this Table.
    * @param {Data} data Records to be inserted into the Table
+   * @returns The new version of the table and the number of rows written
    */
-  abstract add(data: Data, options?: Partial<AddDataOptions>): Promise<void>;
+  abstract add(
+    data: Data,
+    options?: Partial<AddDataOptions>,
+  ): Promise<WriteResult>;
   /**
    * Update existing records in the Table
    * @param opts.values The values to update. The keys are the column names and the values
@@ -185,8 +190,11 @@ export abstract class Table {
 
   /** Count the total number of rows in the dataset. */
   abstract countRows(filter?: string): Promise<number>;
-  /** Delete the rows that satisfy the predicate. */
-  abstract delete(predicate: string): Promise<void>;
+  /**
+   * Delete the rows that satisfy the predicate.
+   * @returns The new version of the table and the number of rows deleted
+   */
+  abstract delete(predicate: string): Promise<WriteResult>;
   /**
    * Create an index to speed up queries.
    *
@@ -406,6 +414,15 @@ export abstract class Table {
   abstract optimize(options?: Partial<OptimizeOptions>): Promise<OptimizeStats>;
   /** List all indices that have been created with {@link Table.createIndex} */
   abstract listIndices(): Promise<IndexConfig[]>;
+  /**
+   * Drop an index from the table.
+   *
+   * @param {string} name The name of the index, as returned by {@link Table.listIndices}
+   * @example
+   * // Remove the full text search index on the "text" column
+   * await table.dropIndex("text_idx");
+   */
+  abstract dropIndex(name: string): Promise<void>;
   /** Return the table as an arrow table */
   abstract toArrow(): Promise<ArrowTable>;
 
@@ -491,7 +508,10 @@ export class LocalTable extends Table {
     return tbl.schema;
   }
 
-  async add(data: Data, options?: Partial<AddDataOptions>): Promise<void> {
+  async add(
+    data: Data,
+    options?: Partial<AddDataOptions>,
+  ): Promise<WriteResult> {
     const mode = options?.mode ?? "append";
     const schema = await this.schema();
     const registry = getRegistry();
@@ -502,7 +522,7 @@ export class LocalTable extends Table {
       functions.values().next().value,
       schema,
     );
-    await this.inner.add(buffer, mode);
+    return await this.inner.add(buffer, mode);
   }
 
   async update(
@@ -569,8 +589,8 @@ export class LocalTable extends Table {
     return await this.inner.countRows(filter);
   }
 
-  async delete(predicate: string): Promise<void> {
-    await this.inner.delete(predicate);
+  async delete(predicate: string): Promise<WriteResult> {
+    return await this.inner.delete(predicate);
   }
 
   async createIndex(column: string, options?: Partial<IndexOptions>) {
@@ -682,6 +702,10 @@ export class LocalTable extends Table {
     return await this.inner.listIndices();
   }
 
+  async dropIndex(name: string): Promise<void> {
+    await this.inner.dropIndex(name);
+  }
+
   async toArrow(): Promise<ArrowTable> {
     return await this.query().toArrow();
   }
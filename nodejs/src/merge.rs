@@ -3,6 +3,7 @@ use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
 use crate::error::convert_error;
+use crate::table::WriteResult;
 
 #[napi]
 #[derive(Clone)]
@@ -34,7 +35,7 @@ impl NativeMergeInsertBuilder {
     }
 
     #[napi(catch_unwind)]
-    pub async fn execute(&self, buf: Buffer) -> napi::Result<()> {
+    pub async fn execute(&self, buf: Buffer) -> napi::Result<WriteResult> {
         let data = ipc_file_to_batches(buf.to_vec())
             .and_then(IntoArrow::into_arrow)
             .map_err(|e| {
@@ -43,12 +44,16 @@ impl NativeMergeInsertBuilder {
 
         let this = self.clone();
 
-        this.inner.execute(data).await.map_err(|e| {
-            napi::Error::from_reason(format!(
-                "Failed to execute merge insert: {}",
-                convert_error(&e)
-            ))
-        })
+        this.inner
+            .execute(data)
+            .await
+            .map(WriteResult::from)
+            .map_err(|e| {
+                napi::Error::from_reason(format!(
+                    "Failed to execute merge insert: {}",
+                    convert_error(&e)
+                ))
+            })
     }
 }
 
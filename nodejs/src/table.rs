@@ -84,7 +84,7 @@ impl Table {
     }
 
     #[napi(catch_unwind)]
-    pub async fn add(&self, buf: Buffer, mode: String) -> napi::Result<()> {
+    pub async fn add(&self, buf: Buffer, mode: String) -> napi::Result<WriteResult> {
         let batches = ipc_file_to_batches(buf.to_vec())
             .map_err(|e| napi::Error::from_reason(format!("Failed to read IPC file: {}", e)))?;
         let mut op = self.inner_ref()?.add(batches);
@@ -97,7 +97,7 @@ impl Table {
             return Err(napi::Error::from_reason(format!("Invalid mode: {}", mode)));
         };
 
-        op.execute().await.default_error()
+        op.execute().await.map(WriteResult::from).default_error()
     }
 
     #[napi(catch_unwind)]
@@ -110,8 +110,12 @@ impl Table {
     }
 
     #[napi(catch_unwind)]
-    pub async fn delete(&self, predicate: String) -> napi::Result<()> {
-        self.inner_ref()?.delete(&predicate).await.default_error()
+    pub async fn delete(&self, predicate: String) -> napi::Result<WriteResult> {
+        self.inner_ref()?
+            .delete(&predicate)
+            .await
+            .map(WriteResult::from)
+            .default_error()
     }
 
     #[napi(catch_unwind)]
@@ -302,6 +306,14 @@ impl Table {
             .collect::<Vec<_>>())
     }
 
+    #[napi(catch_unwind)]
+    pub async fn drop_index(&self, index_name: String) -> napi::Result<()> {
+        self.inner_ref()?
+            .drop_index(&index_name)
+            .await
+            .default_error()
+    }
+
     #[napi(catch_unwind)]
     pub async fn index_stats(&self, index_name: String) -> napi::Result<Option<IndexStatistics>> {
         let tbl = self.inner_ref()?;
@@ -385,6 +397,40 @@ pub struct RemovalStats {
     pub old_versions_removed: i64,
 }
 
+/// The outcome of a write operation (add, delete or merge insert)
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct WriteResult {
+    /// The version of the table created by the operation
+    pub version: i64,
+    /// The number of rows inserted into the table
+    pub num_inserted_rows: i64,
+    /// The number of existing rows that were updated
+    pub num_updated_rows: i64,
+    /// The number of rows removed from the table
+    pub num_deleted_rows: i64,
+    /// The number of new data fragments written by the operation
+    pub num_fragments_written: i64,
+    /// The total size of the data files written by the operation, in bytes
+    pub bytes_written: i64,
+    /// The number of invalid vectors found in the data
+    pub num_bad_vectors: i64,
+}
+
+impl From<lancedb::table::WriteResult> for WriteResult {
+    fn from(result: lancedb::table::WriteResult) -> Self {
+        Self {
+            version: result.version as i64,
+            num_inserted_rows: result.num_inserted_rows as i64,
+            num_updated_rows: result.num_updated_rows as i64,
+            num_deleted_rows: result.num_deleted_rows as i64,
+            num_fragments_written: result.num_fragments_written as i64,
+            bytes_written: result.bytes_written as i64,
+            num_bad_vectors: result.num_bad_vectors as i64,
+        }
+    }
+}
+
 /// Statistics about an optimize operation
 #[napi(object)]
 #[derive(Clone, Debug)]
@@ -43,7 +43,8 @@ class Table:
     def name(self) -> str: ...
     def __repr__(self) -> str: ...
     async def schema(self) -> pa.Schema: ...
-    async def add(self, data: pa.RecordBatchReader, mode: str) -> None: ...
+    async def add(self, data: pa.RecordBatchReader, mode: str) -> WriteResult: ...
+    async def delete(self, condition: str) -> WriteResult: ...
     async def update(self, updates: Dict[str, str], where: Optional[str]) -> None: ...
     async def count_rows(self, filter: Optional[str]) -> int: ...
     async def create_index(
@@ -54,6 +55,7 @@ class Table:
     async def checkout_latest(self): ...
     async def restore(self): ...
     async def list_indices(self) -> List[IndexConfig]: ...
+    async def drop_index(self, index_name: str) -> None: ...
     def query(self) -> Query: ...
     def vector_search(self) -> VectorQuery: ...
 
@@ -109,3 +111,12 @@ class RemovalStats:
 class OptimizeStats:
     compaction: CompactionStats
     prune: RemovalStats
+
+class WriteResult:
+    version: int
+    num_inserted_rows: int
+    num_updated_rows: int
+    num_deleted_rows: int
+    num_fragments_written: int
+    bytes_written: int
+    num_bad_vectors: int
@@ -94,6 +94,10 @@ class RemoteTable(Table):
         """List all the stats of a specified index"""
         return self._loop.run_until_complete(self._table.index_stats(index_uuid))
 
+    def drop_index(self, index_name: str):
+        """Drop an index from the table"""
+        return self._loop.run_until_complete(self._table.drop_index(index_name))
+
     def create_scalar_index(
         self,
         column: str,
@@ -63,7 +63,7 @@ from ._lancedb import connect as lancedb_connect
 if TYPE_CHECKING:
     import PIL
     from lance.dataset import CleanupStats, ReaderLike
-    from ._lancedb import Table as LanceDBTable, OptimizeStats
+    from ._lancedb import Table as LanceDBTable, OptimizeStats, WriteResult
     from .db import LanceDBConnection
     from .index import BTree, IndexConfig, IvfPq, Bitmap, LabelList, FTS, HnswPq, HnswSq
 
@@ -2553,7 +2553,7 @@ class AsyncTable:
         mode: Optional[Literal["append", "overwrite"]] = "append",
         on_bad_vectors: Optional[str] = None,
         fill_value: Optional[float] = None,
-    ):
+    ) -> WriteResult:
         """Add more data to the [Table](Table).
 
         Parameters
@@ -2590,7 +2590,7 @@ class AsyncTable:
         )
         if isinstance(data, pa.Table):
             data = pa.RecordBatchReader.from_batches(data.schema, data.to_batches())
-        await self._inner.add(data, mode)
+        return await self._inner.add(data, mode)
 
     def merge_insert(self, on: Union[str, Iterable[str]]) -> LanceMergeInsertBuilder:
         """
@@ -2713,7 +2713,7 @@ class AsyncTable:
         new_data: DATA,
         on_bad_vectors: str,
         fill_value: float,
-    ):
+    ) -> WriteResult:
         schema = await self.schema()
         if on_bad_vectors is None:
             on_bad_vectors = "error"
@@ -2728,7 +2728,7 @@ class AsyncTable:
         )
         if isinstance(data, pa.Table):
             data = pa.RecordBatchReader.from_batches(data.schema, data.to_batches())
-        await self._inner.execute_merge_insert(
+        return await self._inner.execute_merge_insert(
             data,
             dict(
                 on=merge._on,
@@ -2740,7 +2740,7 @@ class AsyncTable:
             ),
         )
 
-    async def delete(self, where: str):
+    async def delete(self, where: str) -> WriteResult:
         """Delete rows from the table.
 
         This can be used to delete a single row, many rows, all rows, or
@@ -2957,6 +2957,18 @@ class AsyncTable:
         """
         return await self._inner.list_indices()
 
+    async def drop_index(self, index_name: str):
+        """
+        Drop an index from the table.
+
+        Parameters
+        ----------
+        index_name: str
+            The name of the index to drop, as returned by
+            [list_indices][lancedb.table.AsyncTable.list_indices]
+        """
+        await self._inner.drop_index(index_name)
+
     async def index_stats(self, index_name: str) -> Optional[IndexStatistics]:
         """
         Retrieve statistics about an index
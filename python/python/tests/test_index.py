@@ -83,6 +83,19 @@ async def test_create_label_list_index(some_table: AsyncTable):
     assert str(indices) == '[Index(LabelList, columns=["tags"], name="tags_idx")]'
 
 
+@pytest.mark.asyncio
+async def test_drop_index(some_table: AsyncTable):
+    await some_table.create_index("id")
+    await some_table.create_index("tags", config=LabelList())
+    assert len(await some_table.list_indices()) == 2
+
+    await some_table.drop_index("tags_idx")
+    indices = await some_table.list_indices()
+    assert len(indices) == 1
+    assert indices[0].name == "id_idx"
+    assert await some_table.index_stats("tags_idx") is None
+
+
 @pytest.mark.asyncio
 async def test_create_vector_index(some_table: AsyncTable):
     # Can create
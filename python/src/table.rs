@@ -53,6 +53,47 @@ pub struct OptimizeStats {
     pub prune: RemovalStats,
 }
 
+/// The outcome of a write operation (add, delete or merge insert)
+#[pyclass(get_all)]
+#[derive(Clone, Debug)]
+pub struct WriteResult {
+    /// The version of the table created by the operation
+    pub version: u64,
+    /// The number of rows inserted into the table
+    pub num_inserted_rows: u64,
+    /// The number of existing rows that were updated
+    pub num_updated_rows: u64,
+    /// The number of rows removed from the table
+    pub num_deleted_rows: u64,
+    /// The number of new data fragments written by the operation
+    pub num_fragments_written: u64,
+    /// The total size of the data files written by the operation, in bytes
+    pub bytes_written: u64,
+    /// The number of invalid vectors found in the data
+    pub num_bad_vectors: u64,
+}
+
+#[pymethods]
+impl WriteResult {
+    pub fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl From<lancedb::table::WriteResult> for WriteResult {
+    fn from(result: lancedb::table::WriteResult) -> Self {
+        Self {
+            version: result.version,
+            num_inserted_rows: result.num_inserted_rows,
+            num_updated_rows: result.num_updated_rows,
+            num_deleted_rows: result.num_deleted_rows,
+            num_fragments_written: result.num_fragments_written,
+            bytes_written: result.bytes_written,
+            num_bad_vectors: result.num_bad_vectors,
+        }
+    }
+}
+
 #[pyclass]
 pub struct Table {
     // We keep a copy of the name to use if the inner table is dropped
@@ -125,15 +166,16 @@ impl Table {
         }
 
         future_into_py(self_.py(), async move {
-            op.execute().await.infer_error()?;
-            Ok(())
+            let result = op.execute().await.infer_error()?;
+            Ok(WriteResult::from(result))
         })
     }
 
     pub fn delete(self_: PyRef<'_, Self>, condition: String) -> PyResult<Bound<'_, PyAny>> {
         let inner = self_.inner_ref()?.clone();
         future_into_py(self_.py(), async move {
-            inner.delete(&condition).await.infer_error()
+            let result = inner.delete(&condition).await.infer_error()?;
+            Ok(WriteResult::from(result))
         })
     }
 
@@ -204,6 +246,13 @@ impl Table {
         })
     }
 
+    pub fn drop_index(self_: PyRef<'_, Self>, index_name: String) -> PyResult<Bound<'_, PyAny>> {
+        let inner = self_.inner_ref()?.clone();
+        future_into_py(self_.py(), async move {
+            inner.drop_index(&index_name).await.infer_error()
+        })
+    }
+
     pub fn index_stats(self_: PyRef<'_, Self>, index_name: String) -> PyResult<Bound<'_, PyAny>> {
         let inner = self_.inner_ref()?.clone();
         future_into_py(self_.py(), async move {
@@ -351,8 +400,8 @@ impl Table {
         }
 
         future_into_py(self_.py(), async move {
-            builder.execute(Box::new(batches)).await.infer_error()?;
-            Ok(())
+            let result = builder.execute(Box::new(batches)).await.infer_error()?;
+            Ok(WriteResult::from(result))
         })
     }
 
@@ -0,0 +1,78 @@
+// --8<-- [start:imports]
+
+use std::sync::Arc;
+
+use arrow_array::{Int32Array, RecordBatch, RecordBatchIterator, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use futures::StreamExt;
+use lancedb::{
+    arrow::IntoArrow,
+    connect,
+    embeddings::{http::HttpEmbeddingFunction, EmbeddingDefinition},
+    query::{ExecutableQuery, QueryBase},
+    Result,
+};
+
+// --8<-- [end:imports]
+
+// --8<-- [start:http_embeddings]
+#[tokio::main]
+async fn main() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    // A local Ollama server, started with `ollama pull nomic-embed-text`
+    let embedding = HttpEmbeddingFunction::ollama("http://localhost:11434", "nomic-embed-text")
+        .max_batch_size(64)
+        .build()?;
+
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("nomic", Arc::new(embedding))?;
+
+    let table = db
+        .create_table("vectors", make_data())
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "nomic",
+            Some("embeddings"),
+        ))?
+        .execute()
+        .await?;
+
+    let mut results = table
+        .query()
+        .nearest_to_text("something warm")
+        .limit(1)
+        .execute()
+        .await?;
+
+    let rb = results.next().await.unwrap()?;
+    let out = rb
+        .column_by_name("text")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    let text = out.iter().next().unwrap().unwrap();
+    println!("Closest match: {}", text);
+    Ok(())
+}
+// --8<-- [end:http_embeddings]
+
+fn make_data() -> impl IntoArrow {
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Int32, true),
+        Field::new("text", DataType::Utf8, false),
+    ]);
+
+    let id = Int32Array::from(vec![1, 2, 3, 4]);
+    let text = StringArray::from_iter_values(vec![
+        "Black T-Shirt",
+        "Leather Jacket",
+        "Winter Parka",
+        "Hooded Sweatshirt",
+    ]);
+    let schema = Arc::new(schema);
+    let rb = RecordBatch::try_new(schema.clone(), vec![Arc::new(id), Arc::new(text)]).unwrap();
+    Box::new(RecordBatchIterator::new(vec![Ok(rb)], schema))
+}
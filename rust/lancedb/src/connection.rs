@@ -23,7 +23,7 @@ use arrow_array::{RecordBatchIterator, RecordBatchReader};
 use arrow_schema::SchemaRef;
 use lance::dataset::{ReadParams, WriteMode};
 use lance::io::{ObjectStore, ObjectStoreParams, ObjectStoreRegistry, WrappingObjectStore};
-use object_store::{aws::AwsCredential, local::LocalFileSystem};
+use object_store::{aws::AwsCredential, local::LocalFileSystem, PutMode, PutOptions};
 use snafu::prelude::*;
 
 use crate::arrow::IntoArrow;
@@ -34,9 +34,14 @@ use crate::error::{CreateDirSnafu, Error, InvalidTableNameSnafu, Result};
 use crate::io::object_store::MirroringObjectStoreWrapper;
 #[cfg(feature = "remote")]
 use crate::remote::client::ClientConfig;
-use crate::table::{NativeTable, TableDefinition, WriteOptions};
+use crate::table::auto_optimize::AutoOptimize;
+use crate::table::{NativeTable, TableDefinition, TableInternal, WriteOptions};
 use crate::utils::validate_table_name;
 use crate::Table;
+
+use self::snapshot::{CommitRecord, SnapshotCommit};
+
+pub mod snapshot;
 pub use lance_encoding::version::LanceFileVersion;
 
 pub const LANCE_FILE_EXTENSION: &str = "lance";
@@ -123,6 +128,7 @@ impl TableNamesBuilder {
     }
 }
 
+#[derive(Clone)]
 pub struct NoData {}
 
 impl IntoArrow for NoData {
@@ -314,19 +320,26 @@ impl<const HAS_DATA: bool, T: IntoArrow> CreateTableBuilder<HAS_DATA, T> {
     /// Add an embedding definition to the table.
     ///
     /// The `embedding_name` must match the name of an embedding function that
-    /// was previously registered with the connection's [`EmbeddingRegistry`].
+    /// was previously registered with the connection's [`EmbeddingRegistry`],
+    /// or the definition must have a configuration the registry can create the
+    /// function from.
+    ///
+    /// The function's configuration is stored with the table, so that it can
+    /// be recreated when the table is opened from another connection.
     pub fn add_embedding(mut self, definition: EmbeddingDefinition) -> Result<Self> {
         // Early verification of the embedding name
         let embedding_func = self
             .parent
             .embedding_registry()
-            .get(&definition.embedding_name)
+            .get_or_create(&definition)?
             .ok_or_else(|| Error::EmbeddingFunctionNotFound {
                 name: definition.embedding_name.clone(),
                 reason: "No embedding function found in the connection's embedding_registry"
                     .to_string(),
             })?;
 
+        let definition = definition.with_config_from(embedding_func.as_ref());
+        let embedding_func = definition.wrap_function(embedding_func);
         self.embeddings.push((definition, embedding_func));
         Ok(self)
     }
@@ -338,6 +351,7 @@ pub struct OpenTableBuilder {
     pub(crate) name: String,
     index_cache_size: u32,
     lance_read_params: Option<ReadParams>,
+    auto_optimize: Option<AutoOptimize>,
 }
 
 impl OpenTableBuilder {
@@ -347,6 +361,7 @@ impl OpenTableBuilder {
             name,
             index_cache_size: 256,
             lance_read_params: None,
+            auto_optimize: None,
         }
     }
 
@@ -374,6 +389,15 @@ impl OpenTableBuilder {
         self
     }
 
+    /// Optimize the table in the background when it needs it
+    ///
+    /// This takes precedence over a policy set with
+    /// [`ConnectBuilder::auto_optimize`].  See [`AutoOptimize`] for details.
+    pub fn auto_optimize(mut self, policy: AutoOptimize) -> Self {
+        self.auto_optimize = Some(policy);
+        self
+    }
+
     /// Set an option for the storage layer.
     ///
     /// Options already set on the connection will be inherited by the table,
@@ -437,6 +461,8 @@ pub(crate) trait ConnectionInternal:
     async fn rename_table(&self, old_name: &str, new_name: &str) -> Result<()>;
     async fn drop_table(&self, name: &str) -> Result<()>;
     async fn drop_db(&self) -> Result<()>;
+    async fn latest_commit(&self) -> Result<Option<CommitRecord>>;
+    async fn write_commit(&self, record: &CommitRecord) -> Result<()>;
 
     async fn do_create_empty_table(
         &self,
@@ -545,6 +571,54 @@ impl Connection {
         self.internal.drop_db().await
     }
 
+    /// Stage writes to several tables and record them as one consistent
+    /// snapshot in the connection's commit log
+    ///
+    /// The tables are committed one after another, which is not atomic, and
+    /// then a new entry is written to the commit log recording the new version
+    /// of each table.  Readers that check out the versions of
+    /// [`Self::latest_commit`] see either all of the writes or none of them.
+    /// Readers of the latest version of each table can see some of the writes
+    /// before the others, or without the others if the commit fails part way.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use arrow_array::RecordBatchReader;
+    /// # async fn replace_document(
+    /// #     db: lancedb::Connection,
+    /// #     new_document: Box<dyn RecordBatchReader + Send>,
+    /// #     new_chunks: Box<dyn RecordBatchReader + Send>,
+    /// # ) -> lancedb::Result<()> {
+    /// let documents = db.open_table("documents").execute().await?;
+    /// let chunks = db.open_table("chunks").execute().await?;
+    /// db.commit_snapshot()
+    ///     .table(documents.transaction().delete("id = 7").add(new_document))
+    ///     .table(chunks.transaction().delete("doc_id = 7").add(new_chunks))
+    ///     .commit()
+    ///     .await?;
+    ///
+    /// // Read a consistent snapshot of both tables
+    /// if let Some(commit) = db.latest_commit().await? {
+    ///     commit.checkout(&documents).await?;
+    ///     commit.checkout(&chunks).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    pub fn commit_snapshot(&self) -> SnapshotCommit {
+        SnapshotCommit::new(self.internal.clone())
+    }
+
+    /// The latest entry of the commit log written by [`Self::commit_snapshot`]
+    ///
+    /// Returns `None` if no transaction has been committed.
+    pub async fn latest_commit(&self) -> Result<Option<CommitRecord>> {
+        self.internal.latest_commit().await
+    }
+
     /// Get the in-memory embedding registry.
     /// It's important to note that the embedding registry is not persisted across connections.
     /// So if a table contains embeddings, you will need to make sure that you are using a connection that has the same embedding functions registered
@@ -587,6 +661,7 @@ pub struct ConnectBuilder {
     /// always consistent.
     read_consistency_interval: Option<std::time::Duration>,
     embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    auto_optimize: Option<AutoOptimize>,
 }
 
 impl ConnectBuilder {
@@ -602,6 +677,7 @@ impl ConnectBuilder {
             read_consistency_interval: None,
             storage_options: HashMap::new(),
             embedding_registry: None,
+            auto_optimize: None,
         }
     }
 
@@ -708,6 +784,17 @@ impl ConnectBuilder {
         self
     }
 
+    /// Optimize the tables of this connection in the background when they
+    /// need it.  This only affects LanceDB OSS.
+    ///
+    /// The policy applies to every table that is opened or created through
+    /// the connection.  It can be overridden for a table with
+    /// [`OpenTableBuilder::auto_optimize`].  See [`AutoOptimize`] for details.
+    pub fn auto_optimize(mut self, policy: AutoOptimize) -> Self {
+        self.auto_optimize = Some(policy);
+        self
+    }
+
     #[cfg(feature = "remote")]
     fn execute_remote(self) -> Result<Connection> {
         let region = self.region.ok_or_else(|| Error::InvalidInput {
@@ -778,6 +865,9 @@ struct Database {
     // Storage options to be inherited by tables created from this connection
     storage_options: HashMap<String, String>,
     embedding_registry: Arc<dyn EmbeddingRegistry>,
+
+    // Maintenance policy for the tables opened or created by this connection
+    auto_optimize: Option<AutoOptimize>,
 }
 
 impl std::fmt::Display for Database {
@@ -799,6 +889,9 @@ impl std::fmt::Display for Database {
 }
 
 const LANCE_EXTENSION: &str = "lance";
+/// The directory, relative to the database, of the commit log of
+/// [`Connection::transaction`]
+const COMMIT_LOG_DIR: &str = "_transactions";
 const ENGINE: &str = "engine";
 const MIRRORED_STORE: &str = "mirroredStore";
 
@@ -815,6 +908,7 @@ impl Database {
                     uri,
                     options.read_consistency_interval,
                     options.embedding_registry.clone(),
+                    options.auto_optimize.clone(),
                 )
                 .await
             }
@@ -900,6 +994,7 @@ impl Database {
                     read_consistency_interval: options.read_consistency_interval,
                     storage_options,
                     embedding_registry,
+                    auto_optimize: options.auto_optimize.clone(),
                 })
             }
             Err(_) => {
@@ -907,6 +1002,7 @@ impl Database {
                     uri,
                     options.read_consistency_interval,
                     options.embedding_registry.clone(),
+                    options.auto_optimize.clone(),
                 )
                 .await
             }
@@ -917,6 +1013,7 @@ impl Database {
         path: &str,
         read_consistency_interval: Option<std::time::Duration>,
         embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+        auto_optimize: Option<AutoOptimize>,
     ) -> Result<Self> {
         let (object_store, base_path) = ObjectStore::from_uri(path).await?;
         if object_store.is_local() {
@@ -935,6 +1032,7 @@ impl Database {
             read_consistency_interval,
             storage_options: HashMap::new(),
             embedding_registry,
+            auto_optimize,
         })
     }
 
@@ -971,6 +1069,12 @@ impl Database {
 
         Ok(uri)
     }
+
+    fn commit_path(&self, sequence: u64) -> object_store::path::Path {
+        self.base_path
+            .child(COMMIT_LOG_DIR)
+            .child(format!("{:020}.json", sequence))
+    }
 }
 
 #[async_trait::async_trait]
@@ -1054,10 +1158,16 @@ impl ConnectionInternal for Database {
         )
         .await
         {
-            Ok(table) => Ok(Table::new_with_embedding_registry(
-                Arc::new(table),
-                embedding_registry,
-            )),
+            Ok(table) => {
+                let table: Arc<dyn TableInternal> = Arc::new(table);
+                if let Some(policy) = self.auto_optimize.clone() {
+                    policy.spawn(&table);
+                }
+                Ok(Table::new_with_embedding_registry(
+                    table,
+                    embedding_registry,
+                ))
+            }
             Err(Error::TableAlreadyExists { name }) => match options.mode {
                 CreateTableMode::Create => Err(Error::TableAlreadyExists { name }),
                 CreateTableMode::ExistOk(callback) => {
@@ -1099,7 +1209,7 @@ impl ConnectionInternal for Database {
             ..Default::default()
         });
 
-        let native_table = Arc::new(
+        let native_table: Arc<dyn TableInternal> = Arc::new(
             NativeTable::open_with_params(
                 &table_uri,
                 &options.name,
@@ -1109,6 +1219,9 @@ impl ConnectionInternal for Database {
             )
             .await?,
         );
+        if let Some(policy) = options.auto_optimize.or_else(|| self.auto_optimize.clone()) {
+            policy.spawn(&native_table);
+        }
         Ok(Table::new(native_table))
     }
 
@@ -1141,6 +1254,63 @@ impl ConnectionInternal for Database {
             .await?;
         Ok(())
     }
+
+    async fn latest_commit(&self) -> Result<Option<CommitRecord>> {
+        let latest = self
+            .object_store
+            .read_dir(self.base_path.child(COMMIT_LOG_DIR))
+            .await?
+            .iter()
+            .filter_map(|name| name.strip_suffix(".json")?.parse::<u64>().ok())
+            .max();
+        let Some(sequence) = latest else {
+            return Ok(None);
+        };
+        let bytes = self
+            .object_store
+            .inner
+            .get(&self.commit_path(sequence))
+            .await?
+            .bytes()
+            .await?;
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| Error::Runtime {
+                message: format!("failed to parse commit log entry {}: {}", sequence, e),
+            })
+    }
+
+    async fn write_commit(&self, record: &CommitRecord) -> Result<()> {
+        let path = self.commit_path(record.sequence);
+        let payload = serde_json::to_vec(record).map_err(|e| Error::Runtime {
+            message: format!("failed to serialize commit log entry: {}", e),
+        })?;
+        let options = PutOptions {
+            mode: PutMode::Create,
+            ..Default::default()
+        };
+        match self
+            .object_store
+            .inner
+            .put_opts(&path, payload.into(), options)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(object_store::Error::AlreadyExists { .. }) => Err(Error::Runtime {
+                message: format!(
+                    "another transaction committed entry {} of the commit log first",
+                    record.sequence
+                ),
+            }),
+            // Without a conditional put concurrent transactions could overwrite
+            // each other's entries
+            Err(object_store::Error::NotImplemented) => Err(Error::NotSupported {
+                message: "transactions need an object store that supports conditional puts"
+                    .to_string(),
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
 }
 
 #[cfg(all(test, feature = "remote"))]
@@ -1383,4 +1553,120 @@ mod tests {
             .unwrap();
         assert_eq!(other_schema, overwritten.schema().await.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_commit_snapshot() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let db = connect(uri).execute().await.unwrap();
+
+        let ids = |column: &str, values: Vec<i32>| {
+            let schema = Arc::new(Schema::new(vec![Field::new(
+                column,
+                DataType::Int32,
+                false,
+            )]));
+            let batch = arrow_array::RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(arrow_array::Int32Array::from(values))],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema)
+        };
+        let documents = db
+            .create_table("documents", ids("id", vec![0, 1, 2]))
+            .execute()
+            .await
+            .unwrap();
+        let chunks = db
+            .create_table("chunks", ids("doc_id", vec![0, 0, 1, 1, 2, 2]))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(db.latest_commit().await.unwrap(), None);
+
+        // Replace document 1 and its chunks
+        let record = db
+            .commit_snapshot()
+            .table(
+                documents
+                    .transaction()
+                    .delete("id = 1")
+                    .add(ids("id", vec![1])),
+            )
+            .table(
+                chunks
+                    .transaction()
+                    .delete("doc_id = 1")
+                    .add(ids("doc_id", vec![1, 1, 1])),
+            )
+            .commit()
+            .await
+            .unwrap();
+        assert_eq!(record.sequence, 1);
+        assert_eq!(
+            record.versions["documents"].version,
+            documents.version().await.unwrap()
+        );
+        assert_eq!(
+            record.versions["chunks"].version,
+            chunks.version().await.unwrap()
+        );
+        assert_eq!(record.results["chunks"].num_deleted_rows, 2);
+        assert_eq!(record.results["chunks"].num_inserted_rows, 3);
+        assert_eq!(db.latest_commit().await.unwrap(), Some(record.clone()));
+
+        // Writes made outside of the transaction are not part of the snapshot
+        chunks.add(ids("doc_id", vec![3])).execute().await.unwrap();
+        record.checkout(&chunks).await.unwrap();
+        assert_eq!(chunks.count_rows(None).await.unwrap(), 7);
+        chunks.checkout_latest().await.unwrap();
+        assert_eq!(chunks.count_rows(None).await.unwrap(), 8);
+
+        // If one table fails the others keep their new versions, but nothing
+        // is recorded
+        let err = db
+            .commit_snapshot()
+            .table(documents.transaction().delete("true"))
+            .table(chunks.transaction().delete("missing_column = 1"))
+            .commit()
+            .await;
+        assert!(err.is_err());
+        assert_eq!(documents.count_rows(None).await.unwrap(), 0);
+        assert_eq!(db.latest_commit().await.unwrap(), Some(record.clone()));
+        record.checkout(&documents).await.unwrap();
+        assert_eq!(documents.count_rows(None).await.unwrap(), 3);
+        documents.checkout_latest().await.unwrap();
+        assert_eq!(chunks.count_rows(None).await.unwrap(), 8);
+
+        // Writes made outside of a snapshot commit survive the next one
+        let record = db
+            .commit_snapshot()
+            .table(documents.transaction().add(ids("id", vec![3])))
+            .table(chunks.transaction().add(ids("doc_id", vec![3])))
+            .commit()
+            .await
+            .unwrap();
+        assert_eq!(record.sequence, 2);
+        assert_eq!(chunks.count_rows(None).await.unwrap(), 9);
+
+        // A table that was created again is not part of the snapshot
+        db.drop_table("documents").await.unwrap();
+        let documents = db
+            .create_table("documents", ids("id", vec![0]))
+            .execute()
+            .await
+            .unwrap();
+        let err = record.checkout(&documents).await;
+        assert!(matches!(err, Err(Error::InvalidInput { .. })));
+
+        // A table can only be staged once
+        let err = db
+            .commit_snapshot()
+            .table(chunks.transaction().delete("doc_id = 0"))
+            .table(chunks.transaction().delete("doc_id = 2"))
+            .commit()
+            .await;
+        assert!(matches!(err, Err(Error::InvalidInput { .. })));
+    }
 }
@@ -0,0 +1,178 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A commit log of consistent snapshots across several tables
+//!
+//! Writes to several tables are committed one table at a time, which is not
+//! atomic.  Once all of them have been committed, an entry of the connection's
+//! commit log records the version of each table.  Readers that check out the
+//! versions of an entry see a consistent snapshot of the tables.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::table::transaction::TransactionBuilder;
+use crate::table::WriteResult;
+use crate::Table;
+
+use super::ConnectionInternal;
+
+/// The version of a table that belongs to a snapshot
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableSnapshot {
+    /// The version of the table
+    pub version: u64,
+    /// When the version was committed, in microseconds since the UNIX epoch
+    ///
+    /// This tells the table apart from a table that was dropped and created
+    /// again with the same name.
+    pub timestamp: i64,
+}
+
+/// An entry of the connection's commit log
+///
+/// Each entry records, for every table that has been written by a
+/// [`SnapshotCommit`], the version of the table that belongs to the
+/// consistent snapshot created by that commit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommitRecord {
+    /// The position of the entry in the commit log, starting at 1
+    pub sequence: u64,
+    /// The version of each table in the snapshot, by table name
+    pub versions: BTreeMap<String, TableSnapshot>,
+    /// The result of the writes made by the commit, by table name
+    pub results: BTreeMap<String, WriteResult>,
+}
+
+impl CommitRecord {
+    /// Check out the version of the table that belongs to this snapshot
+    ///
+    /// Tables that have never been written by a [`SnapshotCommit`] are left
+    /// unchanged.  An error is returned if the table was dropped and created
+    /// again since the snapshot.  Use [`Table::checkout_latest`] to return to
+    /// the latest version.
+    pub async fn checkout(&self, table: &Table) -> Result<()> {
+        let Some(snapshot) = self.versions.get(table.name()) else {
+            return Ok(());
+        };
+        let timestamp = table.version_timestamp(snapshot.version).await.ok();
+        if timestamp != Some(snapshot.timestamp) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "table '{}' was created again since entry {} of the commit log",
+                    table.name(),
+                    self.sequence
+                ),
+            });
+        }
+        table.checkout(snapshot.version).await
+    }
+}
+
+/// A builder that stages writes to several tables and records them as one
+/// snapshot in the connection's commit log
+///
+/// See [`super::Connection::commit_snapshot`] for more context
+pub struct SnapshotCommit {
+    connection: Arc<dyn ConnectionInternal>,
+    tables: Vec<TransactionBuilder>,
+}
+
+impl std::fmt::Debug for SnapshotCommit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotCommit")
+            .field("connection", &self.connection)
+            .field("tables", &self.tables)
+            .finish()
+    }
+}
+
+impl SnapshotCommit {
+    pub(super) fn new(connection: Arc<dyn ConnectionInternal>) -> Self {
+        Self {
+            connection,
+            tables: Vec::new(),
+        }
+    }
+
+    /// Stage the operations of a single-table transaction, created with
+    /// [`Table::transaction`]
+    ///
+    /// The table must belong to this connection and can only be staged once.
+    pub fn table(mut self, transaction: TransactionBuilder) -> Self {
+        self.tables.push(transaction);
+        self
+    }
+
+    /// Commit the staged operations of every table and record the new
+    /// versions as one entry of the commit log
+    ///
+    /// Each table is committed in turn, and this is not atomic.  If a table
+    /// fails to commit, or the process stops, the tables that were already
+    /// committed keep their new versions and readers of the latest version of
+    /// those tables see them.  No entry is added to the commit log in that
+    /// case, so readers that use [`super::Connection::latest_commit`] still
+    /// see the previous snapshot.
+    ///
+    /// The commit log is written with a conditional put so that concurrent
+    /// commits are detected: only one of them records its snapshot and the
+    /// other returns an error, leaving its table versions outside of the
+    /// commit log.  An error is returned if the object store does not support
+    /// conditional puts (for example S3 without a conditional put
+    /// configuration).
+    pub async fn commit(self) -> Result<CommitRecord> {
+        let mut names = Vec::with_capacity(self.tables.len());
+        for transaction in &self.tables {
+            let name = transaction.table().name().to_string();
+            if names.contains(&name) {
+                return Err(Error::InvalidInput {
+                    message: format!("table '{}' is staged more than once", name),
+                });
+            }
+            names.push(name);
+        }
+
+        let previous = self.connection.latest_commit().await?;
+        let mut record = CommitRecord {
+            sequence: previous.as_ref().map(|r| r.sequence).unwrap_or_default() + 1,
+            versions: previous.map(|r| r.versions).unwrap_or_default(),
+            results: BTreeMap::new(),
+        };
+
+        for transaction in self.tables {
+            let table = transaction.table().clone();
+            let result = transaction.commit().await?;
+            let timestamp = table.version_timestamp(result.version).await?;
+            record.versions.insert(
+                table.name().to_string(),
+                TableSnapshot {
+                    version: result.version,
+                    timestamp,
+                },
+            );
+            record.results.insert(table.name().to_string(), result);
+        }
+
+        self.connection.write_commit(&record).await?;
+        Ok(record)
+    }
+
+    /// Discard the staged operations
+    pub fn discard(self) {}
+}
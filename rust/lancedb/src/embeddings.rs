@@ -11,22 +11,35 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+#[cfg(feature = "clip")]
+pub mod clip;
+
+#[cfg(feature = "http-embeddings")]
+pub mod http;
+
 #[cfg(feature = "openai")]
 pub mod openai;
 
 #[cfg(feature = "sentence-transformers")]
 pub mod sentence_transformers;
 
+#[cfg(feature = "splade")]
+pub mod splade;
+
+pub mod batched;
+pub mod cache;
+pub mod matryoshka;
+
 use lance::arrow::RecordBatchExt;
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     sync::{Arc, RwLock},
 };
 
-use arrow_array::{Array, RecordBatch, RecordBatchReader};
-use arrow_schema::{DataType, Field, SchemaBuilder};
-// use async_trait::async_trait;
+use arrow_array::{Array, ArrayRef, RecordBatch, RecordBatchReader};
+use arrow_schema::{ArrowError, DataType, Field, SchemaBuilder};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
 use crate::{
@@ -60,6 +73,102 @@ pub trait EmbeddingFunction: std::fmt::Debug + Send + Sync {
     fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>>;
     /// Compute the embeddings for a given user query
     fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>>;
+    /// The configuration needed to recreate this function
+    ///
+    /// This is stored in the table metadata, so that a registry with an
+    /// [`EmbeddingFunctionFactory`] for the provider can recreate the function
+    /// when the table is opened elsewhere.  It must not contain secrets.
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        None
+    }
+}
+
+/// An embedding function that computes embeddings asynchronously
+///
+/// This is the natural interface for embedding models behind a remote service,
+/// where each call is a network request.  Wrap it in a
+/// [`batched::BatchedEmbeddingFunction`] to register it: that splits the input
+/// into batches, sends several of them at once and limits the request rate.
+#[async_trait]
+pub trait AsyncEmbeddingFunction: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+    /// The type of the input data
+    fn source_type(&self) -> Result<Cow<DataType>>;
+    /// The type of the output data
+    /// This should **always** match the output of the `embed` function
+    fn dest_type(&self) -> Result<Cow<DataType>>;
+    /// Compute the embeddings for the source column in the database
+    async fn compute_source_embeddings(&self, source: ArrayRef) -> Result<ArrayRef>;
+    /// Compute the embeddings for a given user query
+    async fn compute_query_embeddings(&self, input: ArrayRef) -> Result<ArrayRef>;
+    /// The configuration needed to recreate this function
+    ///
+    /// See [`EmbeddingFunction::config`].
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        None
+    }
+}
+
+/// The configuration of an embedding function
+///
+/// This describes the function well enough to recreate it from the table
+/// metadata.  Secrets such as API keys are never part of the configuration,
+/// factories read them from the environment instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct EmbeddingFunctionConfig {
+    /// The provider of the function, used to find its [`EmbeddingFunctionFactory`]
+    pub provider: String,
+    /// The name of the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The number of dimensions of the embeddings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+    /// Whether the embeddings are normalized
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalize: Option<bool>,
+    /// Any other provider specific options
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, String>,
+}
+
+impl EmbeddingFunctionConfig {
+    pub fn new<S: Into<String>>(provider: S) -> Self {
+        Self {
+            provider: provider.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn model<S: Into<String>>(mut self, model: S) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = Some(normalize);
+        self
+    }
+
+    pub fn option<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.options.insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Creates embedding functions of one provider from their configuration
+///
+/// Register a factory with [`EmbeddingRegistry::register_factory`] so tables
+/// whose embedding columns were created with that provider can be used without
+/// registering the function by hand.
+pub trait EmbeddingFunctionFactory: std::fmt::Debug + Send + Sync {
+    /// Create the embedding function described by `config`
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>>;
 }
 
 /// Defines an embedding from input data into a lower-dimensional space
@@ -72,6 +181,39 @@ pub struct EmbeddingDefinition {
     pub dest_column: Option<String>,
     /// The name of the embedding function to apply
     pub embedding_name: String,
+    /// The configuration of the embedding function, used to recreate it if
+    /// it is not in the registry
+    ///
+    /// If not specified it is taken from the function when the embedding
+    /// column is created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<EmbeddingFunctionConfig>,
+    /// Keep only the first `truncate_dim` values of each embedding
+    ///
+    /// This is meant for models trained with Matryoshka representation
+    /// learning, whose embeddings can be cut down to a prefix of their
+    /// dimensions.  Query embeddings are truncated the same way.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncate_dim: Option<usize>,
+    /// Whether to scale the embeddings, and query embeddings, to unit length
+    ///
+    /// This is done after truncating them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub normalize: bool,
+    /// The embedding column that replaced this one, see
+    /// [`crate::Table::reembed`]
+    ///
+    /// Text queries of the source column, or without a column, use the
+    /// replacement instead.  The column can still be queried by its name, and
+    /// is still computed for new rows until it is dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub superseded_by: Option<String>,
+    /// Whether [`crate::Table::reembed`] is still filling this column
+    ///
+    /// The column is computed for new rows, but text queries only use it when
+    /// it is named.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pending: bool,
 }
 
 impl EmbeddingDefinition {
@@ -80,8 +222,63 @@ impl EmbeddingDefinition {
             source_column: source_column.into(),
             dest_column: dest.map(|d| d.into()),
             embedding_name: embedding_name.into(),
+            config: None,
+            truncate_dim: None,
+            normalize: false,
+            superseded_by: None,
+            pending: false,
         }
     }
+
+    /// Truncate the embeddings to their first `dim` values
+    pub fn truncate_dim(mut self, dim: usize) -> Self {
+        self.truncate_dim = Some(dim);
+        self
+    }
+
+    /// Scale the embeddings to unit length
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Wrap `function` so that it truncates and normalizes its embeddings as
+    /// this definition asks
+    pub(crate) fn wrap_function(
+        &self,
+        function: Arc<dyn EmbeddingFunction>,
+    ) -> Arc<dyn EmbeddingFunction> {
+        if self.truncate_dim.is_none() && !self.normalize {
+            return function;
+        }
+        let mut wrapped =
+            matryoshka::MatryoshkaEmbeddingFunction::new(function).normalize(self.normalize);
+        if let Some(dim) = self.truncate_dim {
+            wrapped = wrapped.truncate_dim(dim);
+        }
+        Arc::new(wrapped)
+    }
+
+    /// Set the configuration of the embedding function
+    pub fn with_config(mut self, config: EmbeddingFunctionConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Fill in the configuration from `function`, unless one was given
+    pub(crate) fn with_config_from(mut self, function: &dyn EmbeddingFunction) -> Self {
+        if self.config.is_none() {
+            self.config = function.config();
+        }
+        self
+    }
+
+    /// The name of the embedding column
+    pub fn dest_column_name(&self) -> String {
+        self.dest_column
+            .clone()
+            .unwrap_or_else(|| format!("{}_embedding", &self.source_column))
+    }
 }
 
 /// A registry of embedding
@@ -93,12 +290,40 @@ pub trait EmbeddingRegistry: Send + Sync + std::fmt::Debug {
     fn register(&self, name: &str, function: Arc<dyn EmbeddingFunction>) -> Result<()>;
     /// Get an embedding function by name
     fn get(&self, name: &str) -> Option<Arc<dyn EmbeddingFunction>>;
+    /// Register an [`EmbeddingFunctionFactory`] for a provider
+    /// Returns an error if the registry does not support factories
+    fn register_factory(
+        &self,
+        provider: &str,
+        _factory: Arc<dyn EmbeddingFunctionFactory>,
+    ) -> Result<()> {
+        Err(Error::NotSupported {
+            message: format!(
+                "this registry can not register a factory for provider '{}'",
+                provider
+            ),
+        })
+    }
+    /// Get the embedding function of a definition
+    ///
+    /// By default this looks up the function by name.  Registries with
+    /// factories also create the function from the definition's configuration
+    /// when it has not been registered.
+    fn get_or_create(
+        &self,
+        definition: &EmbeddingDefinition,
+    ) -> Result<Option<Arc<dyn EmbeddingFunction>>> {
+        Ok(self.get(&definition.embedding_name))
+    }
 }
 
 /// A [`EmbeddingRegistry`] that uses in-memory [`HashMap`]s
-#[derive(Debug, Default, Clone)]
+///
+/// It has factories for the built in embedding functions enabled by features.
+#[derive(Debug, Clone)]
 pub struct MemoryRegistry {
     functions: Arc<RwLock<HashMap<String, Arc<dyn EmbeddingFunction>>>>,
+    factories: Arc<RwLock<HashMap<String, Arc<dyn EmbeddingFunctionFactory>>>>,
 }
 
 impl EmbeddingRegistry for MemoryRegistry {
@@ -117,6 +342,83 @@ impl EmbeddingRegistry for MemoryRegistry {
     fn get(&self, name: &str) -> Option<Arc<dyn EmbeddingFunction>> {
         self.functions.read().unwrap().get(name).cloned()
     }
+
+    fn register_factory(
+        &self,
+        provider: &str,
+        factory: Arc<dyn EmbeddingFunctionFactory>,
+    ) -> Result<()> {
+        self.factories
+            .write()
+            .unwrap()
+            .insert(provider.to_string(), factory);
+
+        Ok(())
+    }
+
+    fn get_or_create(
+        &self,
+        definition: &EmbeddingDefinition,
+    ) -> Result<Option<Arc<dyn EmbeddingFunction>>> {
+        if let Some(function) = self.get(&definition.embedding_name) {
+            return Ok(Some(function));
+        }
+        let Some(config) = &definition.config else {
+            return Ok(None);
+        };
+        let factory = self
+            .factories
+            .read()
+            .unwrap()
+            .get(&config.provider)
+            .cloned();
+        let Some(factory) = factory else {
+            return Ok(None);
+        };
+        let function = factory.create(config)?;
+        // Register the function so later lookups by name find it
+        let function = self
+            .functions
+            .write()
+            .unwrap()
+            .entry(definition.embedding_name.clone())
+            .or_insert(function)
+            .clone();
+        Ok(Some(function))
+    }
+}
+
+impl Default for MemoryRegistry {
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut factories: HashMap<String, Arc<dyn EmbeddingFunctionFactory>> = HashMap::new();
+        #[cfg(feature = "clip")]
+        factories.insert("clip".to_string(), Arc::new(clip::ClipEmbeddingsFactory));
+        #[cfg(feature = "http-embeddings")]
+        factories.insert(
+            "http".to_string(),
+            Arc::new(http::HttpEmbeddingFunctionFactory),
+        );
+        #[cfg(feature = "openai")]
+        factories.insert(
+            "openai".to_string(),
+            Arc::new(openai::OpenAIEmbeddingFunctionFactory),
+        );
+        #[cfg(feature = "sentence-transformers")]
+        factories.insert(
+            "sentence-transformers".to_string(),
+            Arc::new(sentence_transformers::SentenceTransformersEmbeddingsFactory),
+        );
+        #[cfg(feature = "splade")]
+        factories.insert(
+            "splade".to_string(),
+            Arc::new(splade::SpladeEmbeddingsFactory),
+        );
+        Self {
+            functions: Default::default(),
+            factories: Arc::new(RwLock::new(factories)),
+        }
+    }
 }
 
 impl MemoryRegistry {
@@ -153,25 +455,13 @@ impl<R: RecordBatchReader> MaybeEmbedded<R> {
         registry: Option<Arc<dyn EmbeddingRegistry>>,
     ) -> Result<Self> {
         if let Some(registry) = registry {
-            let mut embeddings = Vec::with_capacity(table_definition.column_definitions.len());
-            for cd in table_definition.column_definitions.iter() {
-                if let ColumnKind::Embedding(embedding_def) = &cd.kind {
-                    match registry.get(&embedding_def.embedding_name) {
-                        Some(func) => {
-                            embeddings.push((embedding_def.clone(), func));
-                        }
-                        None => {
-                            return Err(Error::EmbeddingFunctionNotFound {
-                                name: embedding_def.embedding_name.clone(),
-                                reason: format!(
-                                    "Table was defined with an embedding column `{}` but no embedding function was found with that name within the registry.",
-                                    embedding_def.embedding_name
-                                ),
-                            });
-                        }
-                    }
-                }
-            }
+            // Embeddings are computed from the source column, unless the data
+            // already has them
+            let schema = inner.schema();
+            let embeddings = get_embedding_functions(&table_definition, registry.as_ref(), |ed| {
+                schema.field_with_name(&ed.source_column).is_ok()
+                    && schema.field_with_name(&ed.dest_column_name()).is_err()
+            })?;
 
             if !embeddings.is_empty() {
                 return Ok(Self::Yes(WithEmbeddings { inner, embeddings }));
@@ -183,6 +473,40 @@ impl<R: RecordBatchReader> MaybeEmbedded<R> {
     }
 }
 
+/// Look up the embedding functions of the table's embedding columns, for the
+/// definitions that match `include`
+///
+/// Returns an error if one of the functions is not in the registry.
+pub(crate) fn get_embedding_functions(
+    table_definition: &TableDefinition,
+    registry: &dyn EmbeddingRegistry,
+    include: impl Fn(&EmbeddingDefinition) -> bool,
+) -> Result<Vec<(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)>> {
+    let mut embeddings = Vec::with_capacity(table_definition.column_definitions.len());
+    for cd in table_definition.column_definitions.iter() {
+        if let ColumnKind::Embedding(embedding_def) = &cd.kind {
+            if !include(embedding_def) {
+                continue;
+            }
+            match registry.get_or_create(embedding_def)? {
+                Some(func) => {
+                    embeddings.push((embedding_def.clone(), embedding_def.wrap_function(func)));
+                }
+                None => {
+                    return Err(Error::EmbeddingFunctionNotFound {
+                        name: embedding_def.embedding_name.clone(),
+                        reason: format!(
+                            "Table was defined with an embedding column `{}` but no embedding function was found with that name within the registry, and none could be created from its configuration.",
+                            embedding_def.embedding_name
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    Ok(embeddings)
+}
+
 impl<R: RecordBatchReader> WithEmbeddings<R> {
     pub fn new(
         inner: R,
@@ -200,12 +524,8 @@ impl<R: RecordBatchReader> WithEmbeddings<R> {
             .map(|(ed, func)| {
                 let src_field = schema.field_with_name(&ed.source_column).unwrap();
 
-                let field_name = ed
-                    .dest_column
-                    .clone()
-                    .unwrap_or_else(|| format!("{}_embedding", &ed.source_column));
                 Ok(Field::new(
-                    field_name,
+                    ed.dest_column_name(),
                     func.dest_type()?.into_owned(),
                     src_field.is_nullable(),
                 ))
@@ -263,6 +583,51 @@ impl<R: RecordBatchReader> RecordBatchReader for MaybeEmbedded<R> {
     }
 }
 
+impl<R: RecordBatchReader> WithEmbeddings<R> {
+    /// Compute the embedding columns of a batch
+    ///
+    /// Embedding functions may block on a model or a remote service, so when
+    /// there are several columns each is computed on its own thread.
+    fn compute_embeddings(
+        &self,
+        batch: &RecordBatch,
+    ) -> std::result::Result<Vec<ArrayRef>, ArrowError> {
+        let compute = |(fld, func): &(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)| {
+            let src_column = batch.column_by_name(&fld.source_column).unwrap();
+            func.compute_source_embeddings(src_column.clone())
+                .map_err(|e| ArrowError::ComputeError(format!("Error computing embedding: {}", e)))
+        };
+        if self.embeddings.len() <= 1 {
+            return self.embeddings.iter().map(compute).collect();
+        }
+
+        // Functions that call async code need the runtime of the caller
+        let handle = tokio::runtime::Handle::try_current().ok();
+        let compute = &compute;
+        std::thread::scope(|scope| {
+            let threads = self
+                .embeddings
+                .iter()
+                .map(|embedding| {
+                    let handle = handle.clone();
+                    scope.spawn(move || {
+                        let _guard = handle.as_ref().map(|handle| handle.enter());
+                        compute(embedding)
+                    })
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+}
+
 impl<R: RecordBatchReader> Iterator for WithEmbeddings<R> {
     type Item = std::result::Result<RecordBatch, arrow_schema::ArrowError>;
 
@@ -270,25 +635,13 @@ impl<R: RecordBatchReader> Iterator for WithEmbeddings<R> {
         let batch = self.inner.next()?;
         match batch {
             Ok(mut batch) => {
-                // todo: parallelize this
-                for (fld, func) in self.embeddings.iter() {
-                    let src_column = batch.column_by_name(&fld.source_column).unwrap();
-                    let embedding = match func.compute_source_embeddings(src_column.clone()) {
-                        Ok(embedding) => embedding,
-                        Err(e) => {
-                            return Some(Err(arrow_schema::ArrowError::ComputeError(format!(
-                                "Error computing embedding: {}",
-                                e
-                            ))))
-                        }
-                    };
-                    let dst_field_name = fld
-                        .dest_column
-                        .clone()
-                        .unwrap_or_else(|| format!("{}_embedding", &fld.source_column));
-
+                let embeddings = match self.compute_embeddings(&batch) {
+                    Ok(embeddings) => embeddings,
+                    Err(e) => return Some(Err(e)),
+                };
+                for ((fld, _), embedding) in self.embeddings.iter().zip(embeddings) {
                     let dst_field = Field::new(
-                        dst_field_name,
+                        fld.dest_column_name(),
                         embedding.data_type().clone(),
                         embedding.nulls().is_some(),
                     );
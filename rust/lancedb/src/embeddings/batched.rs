@@ -0,0 +1,192 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Batched, concurrent and rate limited embedding functions
+
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use arrow::compute::concat;
+use arrow_array::{new_empty_array, Array, ArrayRef};
+use arrow_schema::DataType;
+use futures::{StreamExt, TryStreamExt};
+use tokio::{sync::Mutex, time::Instant};
+
+use crate::utils::block_on;
+use crate::Result;
+
+use super::{AsyncEmbeddingFunction, EmbeddingFunction, EmbeddingFunctionConfig};
+
+/// The default number of values embedded by one call to the wrapped function
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 1024;
+/// The default number of calls to the wrapped function that run at once
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// Runs an [`AsyncEmbeddingFunction`] on batches of the input concurrently
+///
+/// The input is split into batches of at most [`Self::max_batch_size`] values
+/// and up to [`Self::max_concurrent_requests`] of them are embedded at a time,
+/// optionally limited to [`Self::max_requests_per_second`].  This turns many
+/// serial round trips to an embedding service into a few parallel ones.
+///
+/// It implements [`EmbeddingFunction`], so it can be registered like any other
+/// embedding function.  The limits apply across all calls to the function, so
+/// they hold even when several columns or tables use it at once.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "openai")]
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::{batched::BatchedEmbeddingFunction, openai::OpenAIEmbeddingFunction};
+///
+/// let openai = OpenAIEmbeddingFunction::new_with_model("sk-...", "text-embedding-3-small")?;
+/// let func = BatchedEmbeddingFunction::new(Arc::new(openai))
+///     .max_batch_size(2048)
+///     .max_concurrent_requests(8)
+///     .max_requests_per_second(50.0);
+/// db.embedding_registry().register("openai", Arc::new(func))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct BatchedEmbeddingFunction {
+    inner: Arc<dyn AsyncEmbeddingFunction>,
+    max_batch_size: usize,
+    max_concurrent_requests: usize,
+    rate_limiter: RateLimiter,
+}
+
+impl BatchedEmbeddingFunction {
+    pub fn new(inner: Arc<dyn AsyncEmbeddingFunction>) -> Self {
+        Self {
+            inner,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            rate_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// The maximum number of values to embed in one call
+    ///
+    /// The default is [`DEFAULT_MAX_BATCH_SIZE`].
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    /// The maximum number of calls to run at once
+    ///
+    /// The default is [`DEFAULT_MAX_CONCURRENT_REQUESTS`].
+    pub fn max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    /// The maximum number of calls to start per second
+    ///
+    /// By default the rate is not limited.
+    pub fn max_requests_per_second(mut self, max_requests_per_second: f64) -> Self {
+        self.rate_limiter = RateLimiter {
+            interval: Some(Duration::from_secs_f64(1.0 / max_requests_per_second)),
+            next: Mutex::new(Instant::now()),
+        };
+        self
+    }
+
+    /// Embed the source in batches, running several batches at once
+    pub async fn compute_batched(&self, source: ArrayRef) -> Result<ArrayRef> {
+        let len = source.len();
+        if len == 0 {
+            return Ok(new_empty_array(&self.inner.dest_type()?));
+        }
+        let embeddings = futures::stream::iter((0..len).step_by(self.max_batch_size))
+            .map(|offset| {
+                let batch = source.slice(offset, self.max_batch_size.min(len - offset));
+                async move {
+                    self.rate_limiter.acquire().await;
+                    self.inner.compute_source_embeddings(batch).await
+                }
+            })
+            .buffered(self.max_concurrent_requests)
+            .try_collect::<Vec<_>>()
+            .await?;
+        if embeddings.len() == 1 {
+            return Ok(embeddings.into_iter().next().unwrap());
+        }
+        let embeddings = embeddings.iter().map(|e| e.as_ref()).collect::<Vec<_>>();
+        Ok(concat(&embeddings)?)
+    }
+}
+
+impl EmbeddingFunction for BatchedEmbeddingFunction {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        self.inner.source_type()
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        self.inner.dest_type()
+    }
+
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        block_on(self.compute_batched(source))
+    }
+
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        block_on(async {
+            self.rate_limiter.acquire().await;
+            self.inner.compute_query_embeddings(input).await
+        })
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        self.inner.config()
+    }
+}
+
+/// Spaces out the start of calls to at least `interval` apart
+#[derive(Debug)]
+struct RateLimiter {
+    interval: Option<Duration>,
+    /// The earliest time the next call may start
+    next: Mutex<Instant>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl RateLimiter {
+    async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let start = {
+            let mut next = self.next.lock().await;
+            let start = (*next).max(Instant::now());
+            *next = start + interval;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
@@ -0,0 +1,416 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Caching of computed embeddings
+
+use std::{borrow::Cow, collections::HashMap, sync::Arc};
+
+use arrow::compute::{interleave, take};
+use arrow_array::{
+    cast::AsArray, Array, ArrayRef, FixedSizeListArray, RecordBatch, RecordBatchIterator,
+    StringArray, UInt32Array,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema};
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    connection::Connection,
+    query::{ExecutableQuery, QueryBase, Select},
+    utils::block_on,
+    Error, Result, Table,
+};
+
+use super::{EmbeddingFunction, EmbeddingFunctionConfig};
+
+/// The default number of embeddings kept by a [`MemoryEmbeddingCache`]
+pub const DEFAULT_MEMORY_CACHE_CAPACITY: u64 = 100_000;
+
+/// A store of embeddings, keyed by a hash of the input and the function that
+/// computed them
+///
+/// Each embedding is stored as an array with one value.
+pub trait EmbeddingCache: std::fmt::Debug + Send + Sync {
+    /// Look up the embeddings for the keys, `None` for those not in the cache
+    fn get(&self, keys: &[String]) -> Result<Vec<Option<ArrayRef>>>;
+    /// Store embeddings, one per key
+    fn insert(&self, keys: &[String], embeddings: &ArrayRef) -> Result<()>;
+}
+
+/// An in-memory cache of embeddings, shared by everything that holds it
+#[derive(Debug, Clone)]
+pub struct MemoryEmbeddingCache {
+    cache: moka::sync::Cache<String, ArrayRef>,
+}
+
+impl Default for MemoryEmbeddingCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MEMORY_CACHE_CAPACITY)
+    }
+}
+
+impl MemoryEmbeddingCache {
+    /// Create a cache that keeps at most `capacity` embeddings
+    pub fn new(capacity: u64) -> Self {
+        Self {
+            cache: moka::sync::Cache::new(capacity),
+        }
+    }
+}
+
+impl EmbeddingCache for MemoryEmbeddingCache {
+    fn get(&self, keys: &[String]) -> Result<Vec<Option<ArrayRef>>> {
+        Ok(keys.iter().map(|key| self.cache.get(key)).collect())
+    }
+
+    fn insert(&self, keys: &[String], embeddings: &ArrayRef) -> Result<()> {
+        for (i, key) in keys.iter().enumerate() {
+            self.cache.insert(key.clone(), embeddings.slice(i, 1));
+        }
+        Ok(())
+    }
+}
+
+/// A persistent cache of embeddings, stored in a LanceDB table
+///
+/// The table has a `key` column and an `embedding` column.  It is created on
+/// the first insert, with the type of the embeddings inserted, so a table
+/// should only be used to cache functions that produce the same type.
+pub struct TableEmbeddingCache {
+    connection: Connection,
+    table_name: String,
+    table: tokio::sync::Mutex<Option<Table>>,
+}
+
+impl std::fmt::Debug for TableEmbeddingCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TableEmbeddingCache")
+            .field("connection", &self.connection.to_string())
+            .field("table_name", &self.table_name)
+            .finish()
+    }
+}
+
+impl TableEmbeddingCache {
+    /// Cache embeddings in the table `table_name` of the connection
+    pub fn new(connection: Connection, table_name: impl Into<String>) -> Self {
+        Self {
+            connection,
+            table_name: table_name.into(),
+            table: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// The cache table, or `None` if nothing has been cached yet
+    async fn table(&self) -> Result<Option<Table>> {
+        let mut table = self.table.lock().await;
+        if table.is_none() {
+            match self.connection.open_table(&self.table_name).execute().await {
+                Ok(opened) => *table = Some(opened),
+                Err(Error::TableNotFound { .. }) => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(table.clone())
+    }
+
+    async fn get_async(&self, keys: &[String]) -> Result<Vec<Option<ArrayRef>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let Some(table) = self.table().await? else {
+            return Ok(vec![None; keys.len()]);
+        };
+        // Keys are hex digests, so they can be quoted as they are
+        let filter = format!(
+            "key IN ({})",
+            keys.iter()
+                .map(|key| format!("'{}'", key))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let batches = table
+            .query()
+            .only_if(filter)
+            .select(Select::columns(&["key", "embedding"]))
+            .limit(keys.len())
+            .execute()
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut found = HashMap::new();
+        for batch in batches {
+            let batch_keys = batch.column(0).as_string::<i32>();
+            for (i, key) in batch_keys.iter().enumerate() {
+                if let Some(key) = key {
+                    found.insert(key.to_string(), batch.column(1).slice(i, 1));
+                }
+            }
+        }
+        Ok(keys.iter().map(|key| found.get(key).cloned()).collect())
+    }
+
+    async fn insert_async(&self, keys: &[String], embeddings: &ArrayRef) -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("key", DataType::Utf8, false),
+            Field::new("embedding", embeddings.data_type().clone(), true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from_iter_values(keys)),
+                embeddings.clone(),
+            ],
+        )?;
+        let data = RecordBatchIterator::new(vec![Ok(batch)], schema);
+
+        let Some(table) = self.table().await? else {
+            let created = self
+                .connection
+                .create_table(&self.table_name, data)
+                .execute()
+                .await?;
+            *self.table.lock().await = Some(created);
+            return Ok(());
+        };
+        let mut merge_insert = table.merge_insert(&["key"]);
+        merge_insert.when_not_matched_insert_all();
+        merge_insert.execute(Box::new(data)).await?;
+        Ok(())
+    }
+}
+
+impl EmbeddingCache for TableEmbeddingCache {
+    fn get(&self, keys: &[String]) -> Result<Vec<Option<ArrayRef>>> {
+        block_on(self.get_async(keys))
+    }
+
+    fn insert(&self, keys: &[String], embeddings: &ArrayRef) -> Result<()> {
+        block_on(self.insert_async(keys, embeddings))
+    }
+}
+
+/// An embedding function that reuses previously computed embeddings
+///
+/// Each input value is hashed together with the function's namespace, which
+/// identifies the function and model, see [`Self::namespace`].  Values whose embedding is already in the
+/// cache are not sent to the wrapped function, so re-ingesting unchanged
+/// documents or repeating a query costs nothing.  Null values are never cached.
+///
+/// Only string and binary inputs are cached; other inputs are passed straight
+/// to the wrapped function.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "openai")]
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::{
+///     cache::{CachedEmbeddingFunction, TableEmbeddingCache},
+///     openai::OpenAIEmbeddingFunction,
+/// };
+///
+/// let openai = OpenAIEmbeddingFunction::new_with_model("sk-...", "text-embedding-3-small")?;
+/// let cache = TableEmbeddingCache::new(db.clone(), "embedding_cache");
+/// let func = CachedEmbeddingFunction::new(Arc::new(openai), Arc::new(cache))
+///     .namespace("openai/text-embedding-3-small");
+/// db.embedding_registry().register("openai", Arc::new(func))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CachedEmbeddingFunction {
+    inner: Arc<dyn EmbeddingFunction>,
+    cache: Arc<dyn EmbeddingCache>,
+    namespace: Option<String>,
+}
+
+impl CachedEmbeddingFunction {
+    pub fn new(inner: Arc<dyn EmbeddingFunction>, cache: Arc<dyn EmbeddingCache>) -> Self {
+        Self {
+            inner,
+            cache,
+            namespace: None,
+        }
+    }
+
+    /// Identify the function and model in the cache keys
+    ///
+    /// By default the [`EmbeddingFunction::config`] of the function (its
+    /// provider, model, dimensions and options) is used, so that changing the
+    /// model does not reuse embeddings from the old one.  Functions without a
+    /// configuration must be given a namespace, or computing embeddings fails.
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    fn namespace_or_default(&self) -> Result<String> {
+        if let Some(namespace) = &self.namespace {
+            return Ok(namespace.clone());
+        }
+        let config = self.inner.config().ok_or_else(|| Error::InvalidInput {
+            message: format!(
+                "the embedding function '{}' has no configuration to identify its model, \
+                 so a cache namespace must be set",
+                self.inner.name()
+            ),
+        })?;
+        let config = serde_json::to_string(&config).map_err(|e| Error::Runtime {
+            message: format!("Failed to serialize embedding function config: {}", e),
+        })?;
+        Ok(format!("{}/{}", config, self.inner.dest_type()?))
+    }
+
+    /// The cache key of each value, `None` for nulls, or `None` if the input
+    /// type cannot be cached
+    fn keys(&self, kind: &str, source: &ArrayRef) -> Result<Option<Vec<Option<String>>>> {
+        let bytes = match source.data_type() {
+            DataType::Utf8 | DataType::LargeUtf8 | DataType::Binary | DataType::LargeBinary => {
+                cast(source, &DataType::LargeBinary)?
+            }
+            _ => return Ok(None),
+        };
+        let namespace = self.namespace_or_default()?;
+        let keys = bytes
+            .as_binary::<i64>()
+            .iter()
+            .map(|value| {
+                value.map(|value| {
+                    let mut hasher = Sha256::new();
+                    hasher.update(kind.as_bytes());
+                    hasher.update([0]);
+                    hasher.update(namespace.as_bytes());
+                    hasher.update([0]);
+                    hasher.update(value);
+                    format!("{:x}", hasher.finalize())
+                })
+            })
+            .collect();
+        Ok(Some(keys))
+    }
+}
+
+impl EmbeddingFunction for CachedEmbeddingFunction {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        self.inner.source_type()
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        self.inner.dest_type()
+    }
+
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        let Some(keys) = self.keys("source", &source)? else {
+            return self.inner.compute_source_embeddings(source);
+        };
+        let present = keys.iter().flatten().cloned().collect::<Vec<_>>();
+        let mut cached = self.cache.get(&present)?.into_iter();
+
+        // Where each row's embedding comes from: the cache or the function
+        let mut hits = Vec::new();
+        let mut misses = Vec::new();
+        let mut sources = Vec::with_capacity(keys.len());
+        for (row, key) in keys.iter().enumerate() {
+            match key.as_ref().and_then(|_| cached.next().flatten()) {
+                Some(embedding) => {
+                    sources.push((hits.len() + 1, 0));
+                    hits.push(embedding);
+                }
+                None => {
+                    sources.push((0, misses.len()));
+                    misses.push(row as u32);
+                }
+            }
+        }
+        if hits.is_empty() {
+            let embeddings = self.inner.compute_source_embeddings(source)?;
+            let present = keys.iter().enumerate().filter(|(_, key)| key.is_some());
+            let (rows, present): (Vec<_>, Vec<_>) = present
+                .map(|(row, key)| (row as u32, key.clone().unwrap()))
+                .unzip();
+            if !present.is_empty() {
+                let embeddings = take(&embeddings, &UInt32Array::from(rows), None)?;
+                self.cache.insert(&present, &embeddings)?;
+            }
+            return Ok(embeddings);
+        }
+
+        let computed = if misses.is_empty() {
+            arrow_array::new_empty_array(&self.inner.dest_type()?)
+        } else {
+            let missed = take(&source, &UInt32Array::from(misses.clone()), None)?;
+            let computed = self.inner.compute_source_embeddings(missed)?;
+            let (rows, new_keys): (Vec<_>, Vec<_>) = misses
+                .iter()
+                .enumerate()
+                .filter_map(|(i, row)| keys[*row as usize].clone().map(|key| (i as u32, key)))
+                .unzip();
+            if !new_keys.is_empty() {
+                let embeddings = take(&computed, &UInt32Array::from(rows), None)?;
+                self.cache.insert(&new_keys, &embeddings)?;
+            }
+            computed
+        };
+
+        // Cached embeddings may differ from the function's in nullability
+        let hits = hits
+            .iter()
+            .map(|hit| cast(hit, computed.data_type()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let arrays = std::iter::once(computed.as_ref())
+            .chain(hits.iter().map(|hit| hit.as_ref()))
+            .collect::<Vec<_>>();
+        Ok(interleave(&arrays, &sources)?)
+    }
+
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        // Query embeddings are flat arrays, so a single query value is cached
+        // as a vector of the function's output type
+        let DataType::FixedSizeList(item, dim) = self.inner.dest_type()?.into_owned() else {
+            return self.inner.compute_query_embeddings(input);
+        };
+        let key = match self.keys("query", &input)? {
+            Some(keys) if keys.len() == 1 => keys.into_iter().next().unwrap(),
+            _ => None,
+        };
+        let Some(key) = key else {
+            return self.inner.compute_query_embeddings(input);
+        };
+        let key = [key];
+        if let Some(Some(embedding)) = self.cache.get(&key)?.into_iter().next() {
+            return Ok(embedding.as_fixed_size_list().value(0));
+        }
+        let embedding = self.inner.compute_query_embeddings(input)?;
+        if embedding.len() == dim as usize {
+            let vector = FixedSizeListArray::try_new(
+                item.clone(),
+                dim,
+                cast(&embedding, item.data_type())?,
+                None,
+            )?;
+            self.cache.insert(&key, &(Arc::new(vector) as ArrayRef))?;
+        }
+        Ok(embedding)
+    }
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        self.inner.config()
+    }
+}
@@ -0,0 +1,582 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Image and text embeddings in a shared space with CLIP
+
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
+
+use arrow::buffer::NullBuffer;
+use arrow_array::{cast::AsArray, Array, ArrayRef, FixedSizeListArray, Float32Array};
+use arrow_schema::{DataType, Field};
+use candle_core::{DType, Device, Tensor, D};
+use candle_nn::VarBuilder;
+use candle_transformers::models::clip::{ClipConfig, ClipModel};
+use hf_hub::{api::sync::Api, Repo, RepoType};
+use image::{imageops::FilterType, DynamicImage};
+use tokenizers::Tokenizer;
+
+use crate::{Error, Result};
+
+use super::{EmbeddingFunction, EmbeddingFunctionConfig, EmbeddingFunctionFactory};
+
+/// The Hugging Face repository of the default model
+pub const DEFAULT_CLIP_MODEL: &str = "openai/clip-vit-base-patch32";
+/// The revision of the default model that has safetensors weights
+const DEFAULT_CLIP_REVISION: &str = "refs/pr/15";
+/// The number of images or texts run through the model at once
+const BATCH_SIZE: usize = 32;
+
+/// Builds [`ClipEmbeddings`]
+pub struct ClipEmbeddingsBuilder {
+    /// The Hugging Face repository of the model.
+    /// Defaults to 'openai/clip-vit-base-patch32'
+    model: Option<String>,
+    revision: Option<String>,
+    /// Path on disk to the model weights
+    model_path: Option<PathBuf>,
+    /// Path on disk to the tokenizer
+    tokenizer_path: Option<PathBuf>,
+    /// The device to use for computation.
+    /// Defaults to 'cpu'
+    device: Option<Device>,
+    /// Defaults to true
+    normalize: bool,
+    /// Whether the source column holds paths to images rather than the images.
+    /// Defaults to false
+    image_paths: bool,
+}
+
+impl Default for ClipEmbeddingsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClipEmbeddingsBuilder {
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            revision: None,
+            model_path: None,
+            tokenizer_path: None,
+            device: None,
+            normalize: true,
+            image_paths: false,
+        }
+    }
+
+    /// The Hugging Face repository to download the model from
+    ///
+    /// The model must have the ViT-B/32 architecture of the default model.
+    pub fn model<S: Into<String>>(mut self, name: S) -> Self {
+        self.model = Some(name.into());
+        self
+    }
+
+    /// If you want to use a specific revision of the model, you can set it here.
+    pub fn revision<S: Into<String>>(mut self, revision: S) -> Self {
+        self.revision = Some(revision.into());
+        self
+    }
+
+    /// Load the model weights from a `.safetensors` file on disk
+    ///
+    /// Note: unlike the `model_path` of the sentence-transformers builder this
+    /// is a **path on disk**.  If both this and [`Self::tokenizer_path`] are set
+    /// then nothing is downloaded.
+    pub fn model_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.model_path = Some(path.into());
+        self
+    }
+
+    /// Load the tokenizer from a `tokenizer.json` file on disk
+    pub fn tokenizer_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tokenizer_path = Some(path.into());
+        self
+    }
+
+    pub fn device<D: Into<Device>>(mut self, device: D) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Whether to scale the embeddings to unit length, which is usual for
+    /// comparing them by cosine distance
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Whether the source column holds paths to image files (`Utf8`) instead of
+    /// encoded images (`Binary`)
+    pub fn image_paths(mut self, image_paths: bool) -> Self {
+        self.image_paths = image_paths;
+        self
+    }
+
+    pub fn build(self) -> Result<ClipEmbeddings> {
+        let model_id = self.model.as_deref().unwrap_or(DEFAULT_CLIP_MODEL);
+        let mut embedding_config = EmbeddingFunctionConfig::new("clip")
+            .model(model_id)
+            .normalize(self.normalize);
+        if let Some(revision) = &self.revision {
+            embedding_config = embedding_config.option("revision", revision);
+        }
+        if self.image_paths {
+            embedding_config = embedding_config.option("image_paths", "true");
+        }
+        if let Some(model_path) = &self.model_path {
+            embedding_config = embedding_config.option("model_path", model_path.to_string_lossy());
+        }
+        if let Some(tokenizer_path) = &self.tokenizer_path {
+            embedding_config =
+                embedding_config.option("tokenizer_path", tokenizer_path.to_string_lossy());
+        }
+
+        let (model_file, tokenizer_file) = match (self.model_path, self.tokenizer_path) {
+            (Some(model_path), Some(tokenizer_path)) => (model_path, tokenizer_path),
+            (model_path, tokenizer_path) => {
+                let revision = match &self.revision {
+                    Some(revision) => revision.clone(),
+                    None if model_id == DEFAULT_CLIP_MODEL => DEFAULT_CLIP_REVISION.to_string(),
+                    None => "main".to_string(),
+                };
+                let api = Api::new()?;
+                let repo = api.repo(Repo::with_revision(
+                    model_id.to_string(),
+                    RepoType::Model,
+                    revision,
+                ));
+                let model_file = match model_path {
+                    Some(path) => path,
+                    None => repo.get("model.safetensors")?,
+                };
+                let tokenizer_file = match tokenizer_path {
+                    Some(path) => path,
+                    None => repo.get("tokenizer.json")?,
+                };
+                (model_file, tokenizer_file)
+            }
+        };
+
+        let device = self.device.unwrap_or(Device::Cpu);
+        let config = ClipConfig::vit_base_patch32();
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, &device)? };
+        let model = ClipModel::new(vb, &config)?;
+        let tokenizer = Tokenizer::from_file(tokenizer_file).map_err(|e| Error::Runtime {
+            message: format!("Error loading tokenizer: {}", e),
+        })?;
+        let pad_id = tokenizer
+            .get_vocab(true)
+            .get("<|endoftext|>")
+            .copied()
+            .ok_or_else(|| Error::Runtime {
+                message: "the tokenizer has no <|endoftext|> token".to_string(),
+            })?;
+        embedding_config = embedding_config.dimensions(config.text_config.projection_dim);
+
+        Ok(ClipEmbeddings {
+            model,
+            config,
+            tokenizer,
+            pad_id,
+            device,
+            normalize: self.normalize,
+            image_paths: self.image_paths,
+            embedding_config,
+        })
+    }
+}
+
+/// Compute embeddings of images and text in a shared space with CLIP
+///
+/// The source column holds images encoded (for example as PNG or JPEG) in a
+/// `Binary` or `LargeBinary` column or, with
+/// [`ClipEmbeddingsBuilder::image_paths`], paths to image files in a `Utf8` or
+/// `LargeUtf8` column.  Queries are text, or images given as binary data, so
+/// text can be used to find images, or images to find similar images.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::clip::ClipEmbeddings;
+///
+/// let clip = ClipEmbeddings::builder()
+///     .model_path("/models/clip/model.safetensors")
+///     .tokenizer_path("/models/clip/tokenizer.json")
+///     .build()?;
+/// db.embedding_registry().register("clip", Arc::new(clip))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ClipEmbeddings {
+    model: ClipModel,
+    config: ClipConfig,
+    tokenizer: Tokenizer,
+    /// The token that text is padded with
+    pad_id: u32,
+    device: Device,
+    normalize: bool,
+    image_paths: bool,
+    /// The options the embeddings were built with, see [`EmbeddingFunction::config`]
+    embedding_config: EmbeddingFunctionConfig,
+}
+
+impl std::fmt::Debug for ClipEmbeddings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClipEmbeddings")
+            .field("config", &self.embedding_config)
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+impl ClipEmbeddings {
+    pub fn builder() -> ClipEmbeddingsBuilder {
+        ClipEmbeddingsBuilder::new()
+    }
+
+    fn ndims(&self) -> usize {
+        self.config.text_config.projection_dim
+    }
+
+    fn embed_texts(&self, texts: &[&str]) -> Result<Tensor> {
+        let max_len = self.config.text_config.max_position_embeddings;
+        let mut tokens = Vec::with_capacity(texts.len());
+        for text in texts {
+            let encoding = self
+                .tokenizer
+                .encode(*text, true)
+                .map_err(|e| Error::Runtime {
+                    message: format!("Error tokenizing text: {}", e),
+                })?;
+            let mut ids = encoding.get_ids().to_vec();
+            truncate_tokens(&mut ids, max_len);
+            tokens.push(ids);
+        }
+        let len = tokens.iter().map(Vec::len).max().unwrap_or_default();
+        for ids in tokens.iter_mut() {
+            ids.resize(len, self.pad_id);
+        }
+        let input_ids = Tensor::new(tokens, &self.device)?;
+        Ok(self.model.get_text_features(&input_ids)?)
+    }
+
+    fn embed_images(&self, images: Vec<DynamicImage>) -> Result<Tensor> {
+        let size = self.config.image_size;
+        let pixels = images
+            .into_iter()
+            .map(|image| {
+                // Scale and crop to the model's input size, with values in [-1, 1]
+                let image = image
+                    .resize_to_fill(size as u32, size as u32, FilterType::Triangle)
+                    .to_rgb8()
+                    .into_raw();
+                Tensor::from_vec(image, (size, size, 3), &self.device)?
+                    .permute((2, 0, 1))?
+                    .to_dtype(DType::F32)?
+                    .affine(2. / 255., -1.)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let pixels = Tensor::stack(&pixels, 0)?;
+        Ok(self.model.get_image_features(&pixels)?)
+    }
+
+    /// Embed the non-null values of `input`, images or text, in batches
+    ///
+    /// Returns the flattened embeddings, with zeros for null values.
+    fn compute_inner(&self, input: &dyn Array, images: bool) -> Result<Float32Array> {
+        embed_rows(input, self.ndims(), |batch| {
+            let features = if images {
+                let images = batch
+                    .iter()
+                    .map(|row| load_image(input, *row))
+                    .collect::<Result<Vec<_>>>()?;
+                self.embed_images(images)?
+            } else {
+                let texts = batch
+                    .iter()
+                    .map(|row| text_value(input, *row))
+                    .collect::<Result<Vec<_>>>()?;
+                self.embed_texts(&texts)?
+            };
+            let features = if self.normalize {
+                let norm = features.sqr()?.sum_keepdim(D::Minus1)?.sqrt()?;
+                features.broadcast_div(&norm)?
+            } else {
+                features
+            };
+            Ok(features
+                .to_dtype(DType::F32)?
+                .to_device(&Device::Cpu)?
+                .to_vec2::<f32>()?)
+        })
+    }
+}
+
+/// Shorten the tokens of a text to at most `max_len`, keeping the final
+/// (end of text) token that CLIP pools the text features from
+fn truncate_tokens(ids: &mut Vec<u32>, max_len: usize) {
+    if ids.len() > max_len && max_len > 0 {
+        let last = ids[ids.len() - 1];
+        ids.truncate(max_len);
+        ids[max_len - 1] = last;
+    }
+}
+
+/// Embed the non-null rows of `input` with `embed`, [`BATCH_SIZE`] rows at a
+/// time
+///
+/// Returns the flattened embeddings, with zeros for null values.
+fn embed_rows(
+    input: &dyn Array,
+    n_dims: usize,
+    mut embed: impl FnMut(&[usize]) -> Result<Vec<Vec<f32>>>,
+) -> Result<Float32Array> {
+    let mut values = vec![0.0; input.len() * n_dims];
+    let rows = (0..input.len())
+        .filter(|row| input.is_valid(*row))
+        .collect::<Vec<_>>();
+    for batch in rows.chunks(BATCH_SIZE) {
+        for (row, embedding) in batch.iter().zip(embed(batch)?) {
+            values[row * n_dims..(row + 1) * n_dims].copy_from_slice(&embedding);
+        }
+    }
+    Ok(Float32Array::from(values))
+}
+
+/// Wrap flattened embeddings in a list array, with the nulls of `source`
+fn embeddings_array(values: Float32Array, n_dims: usize, source: &dyn Array) -> Result<ArrayRef> {
+    let nulls = source
+        .logical_nulls()
+        .filter(|nulls: &NullBuffer| nulls.null_count() > 0);
+    Ok(Arc::new(FixedSizeListArray::try_new(
+        Arc::new(Field::new("item", DataType::Float32, true)),
+        n_dims as i32,
+        Arc::new(values),
+        nulls,
+    )?))
+}
+
+/// The text in a row of a string array
+fn text_value(input: &dyn Array, row: usize) -> Result<&str> {
+    match input.data_type() {
+        DataType::Utf8 => Ok(input.as_string::<i32>().value(row)),
+        DataType::LargeUtf8 => Ok(input.as_string::<i64>().value(row)),
+        other => Err(Error::InvalidInput {
+            message: format!("expected text, got {}", other),
+        }),
+    }
+}
+
+/// Decode the image in a row of a binary array, or load it from the path in a
+/// row of a string array
+fn load_image(input: &dyn Array, row: usize) -> Result<DynamicImage> {
+    let image = match input.data_type() {
+        DataType::Binary => image::load_from_memory(input.as_binary::<i32>().value(row)),
+        DataType::LargeBinary => image::load_from_memory(input.as_binary::<i64>().value(row)),
+        DataType::Utf8 | DataType::LargeUtf8 => image::open(text_value(input, row)?),
+        other => {
+            return Err(Error::InvalidInput {
+                message: format!("expected images or paths to images, got {}", other),
+            })
+        }
+    };
+    image.map_err(|e| Error::InvalidInput {
+        message: format!("failed to load the image in row {}: {}", row, e),
+    })
+}
+
+/// Check that a source column holds what the function was built for, images
+/// or paths to images
+fn check_source_type(data_type: &DataType, image_paths: bool) -> Result<()> {
+    let expected = if image_paths {
+        matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)
+    } else {
+        matches!(data_type, DataType::Binary | DataType::LargeBinary)
+    };
+    if expected {
+        Ok(())
+    } else if image_paths {
+        Err(Error::InvalidInput {
+            message: format!("expected paths to images, got {}", data_type),
+        })
+    } else {
+        Err(Error::InvalidInput {
+            message: format!(
+                "expected images, got {}; use `image_paths` for paths to images",
+                data_type
+            ),
+        })
+    }
+}
+
+impl EmbeddingFunction for ClipEmbeddings {
+    fn name(&self) -> &str {
+        "clip"
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        if self.image_paths {
+            Ok(Cow::Owned(DataType::Utf8))
+        } else {
+            Ok(Cow::Owned(DataType::Binary))
+        }
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::new_fixed_size_list(
+            DataType::Float32,
+            self.ndims() as i32,
+            true,
+        )))
+    }
+
+    fn compute_source_embeddings(&self, source: ArrayRef) -> Result<ArrayRef> {
+        check_source_type(source.data_type(), self.image_paths)?;
+        let values = self.compute_inner(source.as_ref(), true)?;
+        embeddings_array(values, self.ndims(), source.as_ref())
+    }
+
+    /// Embed text, or images given as binary data
+    fn compute_query_embeddings(&self, input: ArrayRef) -> Result<ArrayRef> {
+        let images = matches!(input.data_type(), DataType::Binary | DataType::LargeBinary);
+        Ok(Arc::new(self.compute_inner(input.as_ref(), images)?))
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        Some(self.embedding_config.clone())
+    }
+}
+
+/// Creates [`ClipEmbeddings`] from their configuration
+///
+/// The model is loaded on the CPU.
+#[derive(Debug, Default)]
+pub struct ClipEmbeddingsFactory;
+
+impl EmbeddingFunctionFactory for ClipEmbeddingsFactory {
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>> {
+        let mut builder = ClipEmbeddings::builder();
+        if let Some(model) = &config.model {
+            builder = builder.model(model);
+        }
+        if let Some(normalize) = config.normalize {
+            builder = builder.normalize(normalize);
+        }
+        if let Some(revision) = config.options.get("revision") {
+            builder = builder.revision(revision);
+        }
+        if let Some(image_paths) = config.options.get("image_paths") {
+            builder = builder.image_paths(image_paths == "true");
+        }
+        if let Some(model_path) = config.options.get("model_path") {
+            builder = builder.model_path(model_path);
+        }
+        if let Some(tokenizer_path) = config.options.get("tokenizer_path") {
+            builder = builder.tokenizer_path(tokenizer_path);
+        }
+        Ok(Arc::new(builder.build()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use arrow_array::{BinaryArray, StringArray};
+    use image::{ImageFormat, Rgb, RgbImage};
+
+    use super::*;
+
+    fn png_bytes() -> Vec<u8> {
+        let image = RgbImage::from_pixel(3, 2, Rgb([255, 0, 0]));
+        let mut bytes = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut bytes, ImageFormat::Png)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn test_load_image() {
+        let png = png_bytes();
+        let images =
+            BinaryArray::from(vec![Some(png.as_slice()), Some(b"not an image".as_slice())]);
+        let image = load_image(&images, 0).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert!(matches!(
+            load_image(&images, 1),
+            Err(Error::InvalidInput { .. })
+        ));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("red.png");
+        std::fs::write(&path, &png).unwrap();
+        let paths = StringArray::from(vec![
+            path.to_str().unwrap(),
+            dir.path().join("missing.png").to_str().unwrap(),
+        ]);
+        let image = load_image(&paths, 0).unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+        assert!(load_image(&paths, 1).is_err());
+
+        let numbers = Float32Array::from(vec![1.0]);
+        assert!(load_image(&numbers, 0).is_err());
+    }
+
+    #[test]
+    fn test_source_type() {
+        assert!(check_source_type(&DataType::Binary, false).is_ok());
+        assert!(check_source_type(&DataType::LargeBinary, false).is_ok());
+        assert!(check_source_type(&DataType::Utf8, false).is_err());
+        assert!(check_source_type(&DataType::Utf8, true).is_ok());
+        assert!(check_source_type(&DataType::Binary, true).is_err());
+    }
+
+    #[test]
+    fn test_null_rows() {
+        let png = png_bytes();
+        let images = BinaryArray::from(vec![Some(png.as_slice()), None, Some(png.as_slice())]);
+        let mut embedded = Vec::new();
+        let values = embed_rows(&images, 2, |batch| {
+            embedded.extend_from_slice(batch);
+            Ok(batch.iter().map(|row| vec![*row as f32; 2]).collect())
+        })
+        .unwrap();
+        // Null rows are not embedded, and are zeros in the output
+        assert_eq!(embedded, vec![0, 2]);
+        assert_eq!(values.values().as_ref(), &[0.0, 0.0, 0.0, 0.0, 2.0, 2.0]);
+
+        let embeddings = embeddings_array(values, 2, &images).unwrap();
+        assert_eq!(embeddings.len(), 3);
+        assert!(embeddings.is_valid(0));
+        assert!(embeddings.is_null(1));
+        assert!(embeddings.is_valid(2));
+    }
+
+    #[test]
+    fn test_truncate_tokens() {
+        let mut ids = vec![1, 2, 3, 4, 99];
+        truncate_tokens(&mut ids, 3);
+        assert_eq!(ids, vec![1, 2, 99]);
+
+        let mut ids = vec![1, 99];
+        truncate_tokens(&mut ids, 3);
+        assert_eq!(ids, vec![1, 99]);
+    }
+}
@@ -0,0 +1,572 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Embedding functions served over HTTP by OpenAI compatible and Ollama servers
+
+use std::{borrow::Cow, str::FromStr, sync::Arc, sync::OnceLock};
+
+use arrow::buffer::NullBuffer;
+use arrow_array::{cast::AsArray, Array, ArrayRef, FixedSizeListArray, Float32Array, StringArray};
+use arrow_schema::{DataType, Field};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use serde::Deserialize;
+
+use crate::utils::block_on;
+use crate::{Error, Result};
+
+use super::{
+    AsyncEmbeddingFunction, EmbeddingFunction, EmbeddingFunctionConfig, EmbeddingFunctionFactory,
+};
+
+/// The default number of values sent in one request
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 256;
+
+/// The API spoken by an embedding server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpEmbeddingApi {
+    /// The OpenAI embeddings API, `POST {base_url}/embeddings`
+    ///
+    /// This is also served by vLLM, llama.cpp, LocalAI, text-embeddings-inference
+    /// and many other servers.
+    OpenAI,
+    /// The Ollama embed API, `POST {base_url}/api/embed`
+    Ollama,
+}
+
+impl FromStr for HttpEmbeddingApi {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "openai" => Ok(Self::OpenAI),
+            "ollama" => Ok(Self::Ollama),
+            _ => Err(Error::InvalidInput {
+                message: format!(
+                    "unknown embedding API '{}', expected 'openai' or 'ollama'",
+                    s
+                ),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for HttpEmbeddingApi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OpenAI => write!(f, "openai"),
+            Self::Ollama => write!(f, "ollama"),
+        }
+    }
+}
+
+/// Builds an [`HttpEmbeddingFunction`]
+#[derive(Clone)]
+pub struct HttpEmbeddingFunctionBuilder {
+    api: HttpEmbeddingApi,
+    base_url: String,
+    model: String,
+    dimensions: Option<usize>,
+    headers: Vec<(String, String)>,
+    header_envs: Vec<(String, String)>,
+    api_key: Option<String>,
+    api_key_env: Option<String>,
+    max_batch_size: usize,
+}
+
+impl HttpEmbeddingFunctionBuilder {
+    fn new(api: HttpEmbeddingApi, base_url: String, model: String) -> Self {
+        Self {
+            api,
+            base_url,
+            model,
+            dimensions: None,
+            headers: Vec::new(),
+            header_envs: Vec::new(),
+            api_key: None,
+            api_key_env: None,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        }
+    }
+
+    /// The number of dimensions of the embeddings
+    ///
+    /// If not set this is found by embedding a short text on first use.  That
+    /// first use is usually creating a table, which asks for the embedding
+    /// type synchronously, so the request blocks the calling thread and a
+    /// server that is down fails the table creation.  Set the dimensions to
+    /// create tables without contacting the server.
+    pub fn dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = Some(dimensions);
+        self
+    }
+
+    /// Send an extra header with each request
+    ///
+    /// The value is not stored with the table, so the function cannot be
+    /// recreated from the table's metadata.  Use [`Self::header_env`] if it
+    /// should be.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Send an extra header with each request, its value read from an
+    /// environment variable
+    ///
+    /// The names of the header and of the variable, but not the value, are
+    /// stored with the table.
+    pub fn header_env<K: Into<String>, V: Into<String>>(mut self, name: K, env_var: V) -> Self {
+        self.header_envs.push((name.into(), env_var.into()));
+        self
+    }
+
+    /// Authenticate with a bearer token
+    ///
+    /// The key is not stored with the table.  Use [`Self::api_key_env`] if the
+    /// function should be recreated from the table's metadata.
+    pub fn api_key<S: Into<String>>(mut self, api_key: S) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Authenticate with a bearer token read from an environment variable
+    ///
+    /// The name of the variable, but not its value, is stored with the table.
+    pub fn api_key_env<S: Into<String>>(mut self, name: S) -> Self {
+        self.api_key_env = Some(name.into());
+        self
+    }
+
+    /// The maximum number of values sent in one request
+    ///
+    /// The default is [`DEFAULT_MAX_BATCH_SIZE`].
+    pub fn max_batch_size(mut self, max_batch_size: usize) -> Self {
+        self.max_batch_size = max_batch_size.max(1);
+        self
+    }
+
+    pub fn build(self) -> Result<HttpEmbeddingFunction> {
+        let mut header_values = self.headers.clone();
+        for (name, env_var) in &self.header_envs {
+            let value = std::env::var(env_var).map_err(|_| Error::InvalidInput {
+                message: format!("the environment variable {} is not set", env_var),
+            })?;
+            header_values.push((name.clone(), value));
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &header_values {
+            let name =
+                HeaderName::from_bytes(name.as_bytes()).map_err(|e| Error::InvalidInput {
+                    message: format!("invalid header name '{}': {}", name, e),
+                })?;
+            let value = HeaderValue::from_str(value).map_err(|e| Error::InvalidInput {
+                message: format!("invalid value for header '{}': {}", name, e),
+            })?;
+            headers.insert(name, value);
+        }
+        let api_key = match (&self.api_key, &self.api_key_env) {
+            (Some(api_key), _) => Some(api_key.clone()),
+            (None, Some(name)) => Some(std::env::var(name).map_err(|_| Error::InvalidInput {
+                message: format!("the environment variable {} is not set", name),
+            })?),
+            (None, None) => None,
+        };
+        if let Some(api_key) = api_key {
+            let mut value =
+                HeaderValue::from_str(&format!("Bearer {}", api_key)).map_err(|_| {
+                    Error::InvalidInput {
+                        message: "the API key is not a valid header value".to_string(),
+                    }
+                })?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .map_err(|e| Error::Runtime {
+                message: format!("failed to create the HTTP client: {}", e),
+            })?;
+
+        let dimensions = OnceLock::new();
+        if let Some(n) = self.dimensions {
+            dimensions.set(n).unwrap();
+        }
+        Ok(HttpEmbeddingFunction {
+            client,
+            api: self.api,
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            model: self.model,
+            dimensions,
+            headers: self.headers.into_iter().map(|(name, _)| name).collect(),
+            header_envs: self.header_envs,
+            api_key_env: self.api_key_env,
+            max_batch_size: self.max_batch_size,
+        })
+    }
+}
+
+/// An embedding function served by an OpenAI compatible or Ollama server
+///
+/// This works with any model such a server hosts, including local ones.
+///
+/// Unless [`HttpEmbeddingFunctionBuilder::dimensions`] is set, finding the
+/// output type sends a request to the server; see there for details.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::http::HttpEmbeddingFunction;
+///
+/// let func = HttpEmbeddingFunction::ollama("http://localhost:11434", "nomic-embed-text")
+///     .max_batch_size(64)
+///     .build()?;
+/// db.embedding_registry().register("nomic", Arc::new(func))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HttpEmbeddingFunction {
+    client: reqwest::Client,
+    api: HttpEmbeddingApi,
+    base_url: String,
+    model: String,
+    dimensions: OnceLock<usize>,
+    /// The names of the headers set with literal values
+    headers: Vec<String>,
+    header_envs: Vec<(String, String)>,
+    api_key_env: Option<String>,
+    max_batch_size: usize,
+}
+
+impl HttpEmbeddingFunction {
+    /// An embedding function using the OpenAI embeddings API
+    ///
+    /// `base_url` is the URL up to the `/embeddings` path, for example
+    /// `http://localhost:8000/v1`.
+    pub fn openai_compatible<S: Into<String>, M: Into<String>>(
+        base_url: S,
+        model: M,
+    ) -> HttpEmbeddingFunctionBuilder {
+        HttpEmbeddingFunctionBuilder::new(HttpEmbeddingApi::OpenAI, base_url.into(), model.into())
+    }
+
+    /// An embedding function using the Ollama embed API
+    ///
+    /// `base_url` is the URL of the server, for example `http://localhost:11434`.
+    pub fn ollama<S: Into<String>, M: Into<String>>(
+        base_url: S,
+        model: M,
+    ) -> HttpEmbeddingFunctionBuilder {
+        HttpEmbeddingFunctionBuilder::new(HttpEmbeddingApi::Ollama, base_url.into(), model.into())
+    }
+
+    /// The number of dimensions, embedding a short text to find it if needed
+    async fn ndims(&self) -> Result<usize> {
+        if let Some(n) = self.dimensions.get() {
+            return Ok(*n);
+        }
+        let embeddings = self.request(&["hello"]).await?;
+        let n = embeddings.first().map(|e| e.len()).unwrap_or_default();
+        if n == 0 {
+            return Err(Error::Runtime {
+                message: format!("the model '{}' returned an empty embedding", self.model),
+            });
+        }
+        Ok(*self.dimensions.get_or_init(|| n))
+    }
+
+    /// Embed the texts with a single request
+    async fn request(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let (url, body) = match self.api {
+            HttpEmbeddingApi::OpenAI => (
+                format!("{}/embeddings", self.base_url),
+                serde_json::json!({
+                    "model": self.model,
+                    "input": texts,
+                    "encoding_format": "float",
+                }),
+            ),
+            HttpEmbeddingApi::Ollama => (
+                format!("{}/api/embed", self.base_url),
+                serde_json::json!({ "model": self.model, "input": texts }),
+            ),
+        };
+        let response = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Error::Runtime {
+                message: format!("embedding request to {} failed: {}", url, e),
+            })?;
+        let status = response.status();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(Error::Runtime {
+                message: format!(
+                    "embedding request to {} failed with status {}: {}",
+                    url, status, text
+                ),
+            });
+        }
+        let invalid_response = |e: reqwest::Error| Error::Runtime {
+            message: format!("invalid response from {}: {}", url, e),
+        };
+        let embeddings = match self.api {
+            HttpEmbeddingApi::OpenAI => {
+                let mut response = response
+                    .json::<OpenAIResponse>()
+                    .await
+                    .map_err(invalid_response)?;
+                response.data.sort_by_key(|e| e.index);
+                response.data.into_iter().map(|e| e.embedding).collect()
+            }
+            HttpEmbeddingApi::Ollama => {
+                response
+                    .json::<OllamaResponse>()
+                    .await
+                    .map_err(invalid_response)?
+                    .embeddings
+            }
+        };
+        if embeddings.len() != texts.len() {
+            return Err(Error::Runtime {
+                message: format!(
+                    "{} returned {} embeddings for {} inputs",
+                    url,
+                    embeddings.len(),
+                    texts.len()
+                ),
+            });
+        }
+        Ok(embeddings)
+    }
+
+    /// Embed the non-null values of `source`, in batches
+    ///
+    /// Returns the flattened embeddings, with zeros for null values, and the
+    /// nulls of the source.
+    async fn compute_inner(&self, source: ArrayRef) -> Result<(Float32Array, usize)> {
+        let source = match source.data_type() {
+            DataType::Utf8 => source,
+            DataType::LargeUtf8 => arrow_cast::cast(&source, &DataType::Utf8)?,
+            other => {
+                return Err(Error::InvalidInput {
+                    message: format!("expected text to embed, got {}", other),
+                })
+            }
+        };
+        let source: &StringArray = source.as_string::<i32>();
+        let texts = source.iter().flatten().collect::<Vec<_>>();
+
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.max_batch_size) {
+            embeddings.extend(self.request(batch).await?);
+        }
+        let n_dims = match embeddings.first() {
+            Some(embedding) => *self.dimensions.get_or_init(|| embedding.len()),
+            None => self.ndims().await?,
+        };
+
+        let mut values = Vec::with_capacity(source.len() * n_dims);
+        let mut embeddings = embeddings.into_iter();
+        for i in 0..source.len() {
+            if source.is_null(i) {
+                values.extend(std::iter::repeat(0.0).take(n_dims));
+                continue;
+            }
+            let embedding = embeddings.next().unwrap();
+            if embedding.len() != n_dims {
+                return Err(Error::Runtime {
+                    message: format!(
+                        "the model '{}' returned an embedding with {} dimensions instead of {}",
+                        self.model,
+                        embedding.len(),
+                        n_dims
+                    ),
+                });
+            }
+            values.extend(embedding);
+        }
+        Ok((Float32Array::from(values), n_dims))
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAIEmbedding {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Deserialize)]
+struct OllamaResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
+impl EmbeddingFunction for HttpEmbeddingFunction {
+    fn name(&self) -> &str {
+        "http"
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        let n_dims = match self.dimensions.get() {
+            Some(n) => *n,
+            // Blocks on a request to the server, see the builder's `dimensions`
+            None => block_on(self.ndims())?,
+        };
+        Ok(Cow::Owned(DataType::new_fixed_size_list(
+            DataType::Float32,
+            n_dims as i32,
+            true,
+        )))
+    }
+
+    fn compute_source_embeddings(&self, source: ArrayRef) -> Result<ArrayRef> {
+        block_on(AsyncEmbeddingFunction::compute_source_embeddings(
+            self, source,
+        ))
+    }
+
+    fn compute_query_embeddings(&self, input: ArrayRef) -> Result<ArrayRef> {
+        block_on(AsyncEmbeddingFunction::compute_query_embeddings(
+            self, input,
+        ))
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        let mut config = EmbeddingFunctionConfig::new("http")
+            .model(&self.model)
+            .option("api", self.api.to_string())
+            .option("base_url", &self.base_url)
+            .option("max_batch_size", self.max_batch_size.to_string());
+        if let Some(n) = self.dimensions.get() {
+            config = config.dimensions(*n);
+        }
+        if let Some(name) = &self.api_key_env {
+            config = config.option("api_key_env", name);
+        }
+        for (name, env_var) in &self.header_envs {
+            config = config.option(format!("header_env.{}", name), env_var);
+        }
+        if !self.headers.is_empty() {
+            config = config.option("headers", self.headers.join(","));
+        }
+        Some(config)
+    }
+}
+
+#[async_trait]
+impl AsyncEmbeddingFunction for HttpEmbeddingFunction {
+    fn name(&self) -> &str {
+        EmbeddingFunction::name(self)
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        EmbeddingFunction::source_type(self)
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        EmbeddingFunction::dest_type(self)
+    }
+
+    async fn compute_source_embeddings(&self, source: ArrayRef) -> Result<ArrayRef> {
+        let nulls = source.logical_nulls();
+        let (values, n_dims) = self.compute_inner(source).await?;
+        let nulls = nulls.filter(|nulls: &NullBuffer| nulls.null_count() > 0);
+        Ok(Arc::new(FixedSizeListArray::try_new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            n_dims as i32,
+            Arc::new(values),
+            nulls,
+        )?))
+    }
+
+    async fn compute_query_embeddings(&self, input: ArrayRef) -> Result<ArrayRef> {
+        let (values, _) = self.compute_inner(input).await?;
+        Ok(Arc::new(values))
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        EmbeddingFunction::config(self)
+    }
+}
+
+/// Creates [`HttpEmbeddingFunction`]s from their configuration
+///
+/// If the configuration names an `api_key_env` then the API key is read from
+/// that environment variable, and so are the values of headers set with
+/// [`HttpEmbeddingFunctionBuilder::header_env`].  Functions with headers set
+/// with [`HttpEmbeddingFunctionBuilder::header`] cannot be recreated.
+#[derive(Debug, Default)]
+pub struct HttpEmbeddingFunctionFactory;
+
+impl EmbeddingFunctionFactory for HttpEmbeddingFunctionFactory {
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>> {
+        let option = |key: &str| {
+            config.options.get(key).ok_or_else(|| Error::InvalidInput {
+                message: format!("the HTTP embedding configuration has no '{}'", key),
+            })
+        };
+        let model = config.model.clone().ok_or_else(|| Error::InvalidInput {
+            message: "the HTTP embedding configuration has no model".to_string(),
+        })?;
+        let mut builder = HttpEmbeddingFunctionBuilder::new(
+            option("api")?.parse()?,
+            option("base_url")?.clone(),
+            model,
+        );
+        if let Some(n) = config.dimensions {
+            builder = builder.dimensions(n);
+        }
+        if let Some(max_batch_size) = config.options.get("max_batch_size") {
+            let max_batch_size = max_batch_size.parse().map_err(|_| Error::InvalidInput {
+                message: format!("invalid max_batch_size '{}'", max_batch_size),
+            })?;
+            builder = builder.max_batch_size(max_batch_size);
+        }
+        if let Some(name) = config.options.get("api_key_env") {
+            builder = builder.api_key_env(name);
+        }
+        if let Some(headers) = config.options.get("headers") {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the values of the headers {} are not stored with the table, set them with \
+                     header_env so that the function can be recreated",
+                    headers
+                ),
+            });
+        }
+        for (key, env_var) in &config.options {
+            if let Some(name) = key.strip_prefix("header_env.") {
+                builder = builder.header_env(name, env_var);
+            }
+        }
+        Ok(Arc::new(builder.build()?))
+    }
+}
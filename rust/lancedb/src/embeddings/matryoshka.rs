@@ -0,0 +1,258 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Truncated and normalized embeddings
+
+use std::{borrow::Cow, sync::Arc};
+
+use arrow_array::{
+    builder::{Float32Builder, ListBuilder},
+    cast::AsArray,
+    types::Float32Type,
+    Array, ArrayRef, FixedSizeListArray, Float32Array,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field};
+
+use crate::{Error, Result};
+
+use super::{EmbeddingFunction, EmbeddingFunctionConfig};
+
+/// Truncates and normalizes the embeddings of another function
+///
+/// Models trained with Matryoshka representation learning, such as OpenAI's
+/// `text-embedding-3` models, put the most important information first, so
+/// their embeddings can be cut down to a prefix of their dimensions to save
+/// space.  The prefix is no longer unit length, so it is usually normalized.
+///
+/// The same is applied to query embeddings, so they match the stored ones.
+/// Embedding columns use this when their [`super::EmbeddingDefinition`] has
+/// `truncate_dim` or `normalize` set.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "openai")]
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::{matryoshka::MatryoshkaEmbeddingFunction, openai::OpenAIEmbeddingFunction};
+///
+/// let openai = OpenAIEmbeddingFunction::new_with_model("sk-...", "text-embedding-3-large")?;
+/// let func = MatryoshkaEmbeddingFunction::new(Arc::new(openai))
+///     .truncate_dim(256)
+///     .normalize(true);
+/// db.embedding_registry().register("openai-256", Arc::new(func))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct MatryoshkaEmbeddingFunction {
+    inner: Arc<dyn EmbeddingFunction>,
+    truncate_dim: Option<usize>,
+    normalize: bool,
+}
+
+impl MatryoshkaEmbeddingFunction {
+    pub fn new(inner: Arc<dyn EmbeddingFunction>) -> Self {
+        Self {
+            inner,
+            truncate_dim: None,
+            normalize: false,
+        }
+    }
+
+    /// Keep only the first `dim` values of each embedding
+    pub fn truncate_dim(mut self, dim: usize) -> Self {
+        self.truncate_dim = Some(dim);
+        self
+    }
+
+    /// Whether to scale the embeddings to unit length, after truncating them
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// The number of dimensions kept of embeddings with `dim` dimensions
+    fn kept_dim(&self, dim: usize) -> Result<usize> {
+        match self.truncate_dim {
+            Some(truncate_dim) if truncate_dim == 0 || truncate_dim > dim => {
+                Err(Error::InvalidInput {
+                    message: format!(
+                        "can not truncate embeddings with {} dimensions to {} dimensions",
+                        dim, truncate_dim
+                    ),
+                })
+            }
+            Some(truncate_dim) => Ok(truncate_dim),
+            None => Ok(dim),
+        }
+    }
+
+    /// Check the fixed number of dimensions of embeddings, which are split
+    /// into vectors by it
+    fn check_fixed_dim(dim: usize) -> Result<()> {
+        if dim == 0 {
+            return Err(Error::InvalidInput {
+                message: "can not truncate or normalize embeddings with 0 dimensions".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Truncate and normalize one vector
+    fn process(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        let mut vector = vector[..self.kept_dim(vector.len())?].to_vec();
+        if self.normalize {
+            let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+            if norm > 0.0 {
+                vector.iter_mut().for_each(|x| *x /= norm);
+            }
+        }
+        Ok(vector)
+    }
+
+    /// Truncate and normalize embeddings
+    ///
+    /// The embeddings are a fixed size list or list per row, or the values of
+    /// the embeddings of every row one after the other.  The type of the
+    /// values is kept.
+    fn process_array(&self, embeddings: ArrayRef) -> Result<ArrayRef> {
+        if self.truncate_dim.is_none() && !self.normalize {
+            return Ok(embeddings);
+        }
+        let data_type = embeddings.data_type().clone();
+        let processed: ArrayRef = match &data_type {
+            DataType::FixedSizeList(item, dim) => {
+                let dim = *dim as usize;
+                Self::check_fixed_dim(dim)?;
+                let kept_dim = self.kept_dim(dim)?;
+                let list = embeddings.as_fixed_size_list();
+                let start = if list.is_empty() {
+                    0
+                } else {
+                    list.value_offset(0) as usize
+                };
+                let values = list.values().slice(start, list.len() * dim);
+                let values = cast(&values, &DataType::Float32)?;
+                let mut processed = Vec::with_capacity(list.len() * kept_dim);
+                for vector in values.as_primitive::<Float32Type>().values().chunks(dim) {
+                    processed.extend(self.process(vector)?);
+                }
+                let list = FixedSizeListArray::try_new(
+                    Arc::new(Field::new(item.name(), DataType::Float32, true)),
+                    kept_dim as i32,
+                    Arc::new(Float32Array::from(processed)),
+                    list.nulls().cloned(),
+                )?;
+                return Ok(cast(
+                    &list,
+                    &DataType::new_fixed_size_list(
+                        item.data_type().clone(),
+                        kept_dim as i32,
+                        item.is_nullable(),
+                    ),
+                )?);
+            }
+            DataType::List(_) | DataType::LargeList(_) => {
+                let embeddings = cast(&embeddings, &DataType::new_list(DataType::Float32, true))?;
+                let list = embeddings.as_list::<i32>();
+                let mut builder = ListBuilder::new(Float32Builder::new());
+                for row in 0..list.len() {
+                    if list.is_null(row) {
+                        builder.append_null();
+                        continue;
+                    }
+                    let vector = list.value(row);
+                    builder.values().append_slice(
+                        &self.process(vector.as_primitive::<Float32Type>().values())?,
+                    );
+                    builder.append(true);
+                }
+                Arc::new(builder.finish())
+            }
+            data_type if data_type.is_floating() => {
+                let values = cast(&embeddings, &DataType::Float32)?;
+                let values = values.as_primitive::<Float32Type>().values();
+                // The values of the query embeddings of all rows, one after
+                // the other, so they are split by the inner function's
+                // dimensions when it has a fixed number
+                let dim = match self.inner.dest_type()?.as_ref() {
+                    DataType::FixedSizeList(_, dim) => {
+                        let dim = *dim as usize;
+                        Self::check_fixed_dim(dim)?;
+                        if values.len() % dim != 0 {
+                            return Err(Error::InvalidInput {
+                                message: format!(
+                                    "expected query embeddings with {} dimensions, got {} values",
+                                    dim,
+                                    values.len()
+                                ),
+                            });
+                        }
+                        dim
+                    }
+                    _ => values.len().max(1),
+                };
+                let mut processed = Vec::with_capacity(values.len());
+                for vector in values.chunks(dim) {
+                    processed.extend(self.process(vector)?);
+                }
+                Arc::new(Float32Array::from(processed))
+            }
+            other => {
+                return Err(Error::InvalidInput {
+                    message: format!("can not truncate or normalize embeddings of type {}", other),
+                })
+            }
+        };
+        Ok(cast(&processed, &data_type)?)
+    }
+}
+
+impl EmbeddingFunction for MatryoshkaEmbeddingFunction {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        self.inner.source_type()
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        let dest_type = self.inner.dest_type()?;
+        match dest_type.as_ref() {
+            DataType::FixedSizeList(item, dim) if self.truncate_dim.is_some() => {
+                Ok(Cow::Owned(DataType::new_fixed_size_list(
+                    item.data_type().clone(),
+                    self.kept_dim(*dim as usize)? as i32,
+                    item.is_nullable(),
+                )))
+            }
+            _ => Ok(dest_type),
+        }
+    }
+
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        self.process_array(self.inner.compute_source_embeddings(source)?)
+    }
+
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        self.process_array(self.inner.compute_query_embeddings(input)?)
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        self.inner.config()
+    }
+}
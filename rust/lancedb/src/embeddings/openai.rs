@@ -9,11 +9,21 @@ use async_openai::{
     types::{CreateEmbeddingRequest, Embedding, EmbeddingInput, EncodingFormat},
     Client,
 };
-use tokio::{runtime::Handle, task};
+use async_trait::async_trait;
 
+use crate::utils::block_on;
 use crate::{Error, Result};
 
-use super::EmbeddingFunction;
+use super::{
+    AsyncEmbeddingFunction, EmbeddingFunction, EmbeddingFunctionConfig, EmbeddingFunctionFactory,
+};
+
+/// The environment variable the API key is read from when a function is
+/// created from its configuration
+pub const OPENAI_API_KEY_ENV: &str = "OPENAI_API_KEY";
+/// The environment variable the organization id is read from when a function
+/// is created from its configuration
+pub const OPENAI_ORG_ID_ENV: &str = "OPENAI_ORG_ID";
 
 #[derive(Debug)]
 pub enum EmbeddingModel {
@@ -155,9 +165,93 @@ impl EmbeddingFunction for OpenAIEmbeddingFunction {
     }
 
     fn compute_source_embeddings(&self, source: ArrayRef) -> crate::Result<ArrayRef> {
+        block_on(AsyncEmbeddingFunction::compute_source_embeddings(
+            self, source,
+        ))
+    }
+
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        block_on(AsyncEmbeddingFunction::compute_query_embeddings(
+            self, input,
+        ))
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        // The API key and organization are left out, they come from the
+        // environment when the function is recreated
+        let mut config = EmbeddingFunctionConfig::new("openai")
+            .model(self.model.to_string())
+            .dimensions(self.model.ndims());
+        if let Some(api_base) = &self.api_base {
+            config = config.option("api_base", api_base);
+        }
+        Some(config)
+    }
+}
+
+/// Creates [`OpenAIEmbeddingFunction`]s from their configuration
+///
+/// The API key is read from the `OPENAI_API_KEY` environment variable and the
+/// organization id, if any, from `OPENAI_ORG_ID`.
+#[derive(Debug, Default)]
+pub struct OpenAIEmbeddingFunctionFactory;
+
+impl EmbeddingFunctionFactory for OpenAIEmbeddingFunctionFactory {
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>> {
+        let api_key = std::env::var(OPENAI_API_KEY_ENV).map_err(|_| Error::InvalidInput {
+            message: format!(
+                "the {} environment variable must be set to create an OpenAI embedding function",
+                OPENAI_API_KEY_ENV
+            ),
+        })?;
+        let mut function = match &config.model {
+            Some(model) => OpenAIEmbeddingFunction::new_with_model(api_key, model.as_str())?,
+            None => OpenAIEmbeddingFunction::new(api_key),
+        };
+        if let Some(dimensions) = config.dimensions {
+            if dimensions != function.model.ndims() {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the OpenAI model {} has {} dimensions, not {}",
+                        function.model,
+                        function.model.ndims(),
+                        dimensions
+                    ),
+                });
+            }
+        }
+        if let Some(api_base) = config.options.get("api_base") {
+            function = function.api_base(api_base);
+        }
+        if let Ok(org_id) = std::env::var(OPENAI_ORG_ID_ENV) {
+            function = function.org_id(org_id);
+        }
+        Ok(Arc::new(function))
+    }
+}
+
+#[async_trait]
+impl AsyncEmbeddingFunction for OpenAIEmbeddingFunction {
+    fn name(&self) -> &str {
+        EmbeddingFunction::name(self)
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        EmbeddingFunction::source_type(self)
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        EmbeddingFunction::dest_type(self)
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        EmbeddingFunction::config(self)
+    }
+
+    async fn compute_source_embeddings(&self, source: ArrayRef) -> crate::Result<ArrayRef> {
         let len = source.len();
         let n_dims = self.model.ndims();
-        let inner = self.compute_inner(source)?;
+        let inner = self.compute_inner(source).await?;
 
         let fsl = DataType::new_fixed_size_list(DataType::Float32, n_dims as i32, false);
 
@@ -171,14 +265,14 @@ impl EmbeddingFunction for OpenAIEmbeddingFunction {
         Ok(Arc::new(FixedSizeListArray::from(array_data)))
     }
 
-    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
-        let arr = self.compute_inner(input)?;
+    async fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        let arr = self.compute_inner(input).await?;
         Ok(Arc::new(arr))
     }
 }
 
 impl OpenAIEmbeddingFunction {
-    fn compute_inner(&self, source: Arc<dyn Array>) -> Result<Float32Array> {
+    async fn compute_inner(&self, source: Arc<dyn Array>) -> Result<Float32Array> {
         // OpenAI only supports non-nullable string arrays
         if source.is_nullable() {
             return Err(crate::Error::InvalidInput {
@@ -238,21 +332,17 @@ impl OpenAIEmbeddingFunction {
             dimensions: None,
         };
 
-        // TODO: request batching and retry logic
-        task::block_in_place(move || {
-            Handle::current().block_on(async {
-                let mut builder = Float32Builder::new();
+        // TODO: retry logic.  Use a BatchedEmbeddingFunction for batching.
+        let mut builder = Float32Builder::new();
 
-                let res = embed.create(req).await.map_err(|e| crate::Error::Runtime {
-                    message: format!("OpenAI embed request failed: {e}"),
-                })?;
+        let res = embed.create(req).await.map_err(|e| crate::Error::Runtime {
+            message: format!("OpenAI embed request failed: {e}"),
+        })?;
 
-                for Embedding { embedding, .. } in res.data.iter() {
-                    builder.append_slice(embedding);
-                }
+        for Embedding { embedding, .. } in res.data.iter() {
+            builder.append_slice(embedding);
+        }
 
-                Ok(builder.finish())
-            })
-        })
+        Ok(builder.finish())
     }
 }
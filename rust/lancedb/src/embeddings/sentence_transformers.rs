@@ -1,6 +1,6 @@
 use std::{borrow::Cow, sync::Arc};
 
-use super::EmbeddingFunction;
+use super::{EmbeddingFunction, EmbeddingFunctionConfig, EmbeddingFunctionFactory};
 use arrow::{
     array::{AsArray, PrimitiveBuilder},
     datatypes::{
@@ -46,6 +46,8 @@ pub struct SentenceTransformersEmbeddings {
     tokenizer: Tokenizer,
     device: Device,
     n_dims: Option<usize>,
+    /// The options the embeddings were built with, see [`EmbeddingFunction::config`]
+    config: EmbeddingFunctionConfig,
 }
 
 impl std::fmt::Debug for SentenceTransformersEmbeddings {
@@ -138,6 +140,20 @@ impl SentenceTransformersEmbeddingsBuilder {
 
     pub fn build(mut self) -> crate::Result<SentenceTransformersEmbeddings> {
         let model_id = self.model.as_deref().unwrap_or("all-MiniLM-L6-v2");
+        let mut embedding_config = EmbeddingFunctionConfig::new("sentence-transformers")
+            .model(model_id)
+            .normalize(self.normalize);
+        let options = [
+            ("revision", &self.revision),
+            ("config_path", &self.config_path),
+            ("tokenizer_path", &self.tokenizer_path),
+            ("model_path", &self.model_path),
+        ];
+        for (key, value) in options {
+            if let Some(value) = value {
+                embedding_config = embedding_config.option(key, value);
+            }
+        }
         let model_id = format!("sentence-transformers/{}", model_id);
         let config = self.config_path.as_deref().unwrap_or("config.json");
         let tokenizer = self.tokenizer_path.as_deref().unwrap_or("tokenizer.json");
@@ -185,6 +201,7 @@ impl SentenceTransformersEmbeddingsBuilder {
             tokenizer,
             device,
             n_dims: self.n_dims,
+            config: embedding_config,
         })
     }
 }
@@ -438,6 +455,12 @@ impl EmbeddingFunction for SentenceTransformersEmbeddings {
         let (arr, _) = self.compute_inner(input)?;
         Ok(arr)
     }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        let mut config = self.config.clone();
+        config.dimensions = self.ndims().ok();
+        Some(config)
+    }
 }
 
 fn from_cpu_storage<T: ArrowPrimitiveType>(
@@ -468,3 +491,40 @@ fn from_cpu_storage<T: ArrowPrimitiveType>(
         }
     }
 }
+
+/// Creates [`SentenceTransformersEmbeddings`] from their configuration
+///
+/// The model is loaded on the CPU.
+#[derive(Debug, Default)]
+pub struct SentenceTransformersEmbeddingsFactory;
+
+impl EmbeddingFunctionFactory for SentenceTransformersEmbeddingsFactory {
+    fn create(
+        &self,
+        config: &EmbeddingFunctionConfig,
+    ) -> crate::Result<Arc<dyn EmbeddingFunction>> {
+        let mut builder = SentenceTransformersEmbeddings::builder();
+        if let Some(model) = &config.model {
+            builder = builder.model(model);
+        }
+        if let Some(n_dims) = config.dimensions {
+            builder = builder.ndims(n_dims);
+        }
+        if let Some(normalize) = config.normalize {
+            builder = builder.normalize(normalize);
+        }
+        if let Some(revision) = config.options.get("revision") {
+            builder = builder.revision(revision);
+        }
+        if let Some(config_path) = config.options.get("config_path") {
+            builder = builder.config_path(config_path);
+        }
+        if let Some(tokenizer_path) = config.options.get("tokenizer_path") {
+            builder = builder.tokenizer_path(tokenizer_path);
+        }
+        if let Some(model_path) = config.options.get("model_path") {
+            builder = builder.model_path(model_path);
+        }
+        Ok(Arc::new(builder.build()?))
+    }
+}
@@ -0,0 +1,470 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sparse text embeddings with SPLADE models
+
+use std::{borrow::Cow, path::PathBuf, sync::Arc};
+
+use arrow_array::{cast::AsArray, Array, ArrayRef};
+use arrow_schema::DataType;
+use candle_core::{DType, Device, Module, Tensor};
+use candle_nn::{layer_norm, linear, LayerNorm, Linear, VarBuilder};
+use candle_transformers::models::bert::{BertModel, Config as BertConfig};
+use hf_hub::{api::sync::Api, Repo, RepoType};
+use serde::Deserialize;
+use tokenizers::Tokenizer;
+
+use crate::sparse::{sparse_vector_type, sparse_vectors_to_array, SparseVector};
+use crate::{Error, Result};
+
+use super::{EmbeddingFunction, EmbeddingFunctionConfig, EmbeddingFunctionFactory};
+
+/// The Hugging Face repository of the default model
+pub const DEFAULT_SPLADE_MODEL: &str = "naver/splade-cocondenser-ensembledistil";
+
+/// The parts of a BERT `config.json` needed for the masked language model head
+#[derive(Debug, Deserialize)]
+struct HeadConfig {
+    vocab_size: usize,
+    hidden_size: usize,
+    max_position_embeddings: usize,
+    #[serde(default = "default_layer_norm_eps")]
+    layer_norm_eps: f64,
+}
+
+fn default_layer_norm_eps() -> f64 {
+    1e-12
+}
+
+/// Builds [`SpladeEmbeddings`]
+pub struct SpladeEmbeddingsBuilder {
+    /// The Hugging Face repository of the model.
+    /// Defaults to 'naver/splade-cocondenser-ensembledistil'
+    model: Option<String>,
+    revision: Option<String>,
+    /// Path on disk to `config.json`
+    config_path: Option<PathBuf>,
+    /// Path on disk to the tokenizer
+    tokenizer_path: Option<PathBuf>,
+    /// Path on disk to the model weights
+    model_path: Option<PathBuf>,
+    /// The device to use for computation.
+    /// Defaults to 'cpu'
+    device: Option<Device>,
+}
+
+impl Default for SpladeEmbeddingsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpladeEmbeddingsBuilder {
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            revision: None,
+            config_path: None,
+            tokenizer_path: None,
+            model_path: None,
+            device: None,
+        }
+    }
+
+    /// The Hugging Face repository to download the model from
+    ///
+    /// The model must be a BERT masked language model with `.safetensors`
+    /// weights.
+    pub fn model<S: Into<String>>(mut self, name: S) -> Self {
+        self.model = Some(name.into());
+        self
+    }
+
+    /// If you want to use a specific revision of the model, you can set it here.
+    pub fn revision<S: Into<String>>(mut self, revision: S) -> Self {
+        self.revision = Some(revision.into());
+        self
+    }
+
+    /// Load the model configuration from a `config.json` file on disk
+    ///
+    /// If this, [`Self::tokenizer_path`] and [`Self::model_path`] are all set
+    /// then nothing is downloaded.
+    pub fn config_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Load the tokenizer from a `tokenizer.json` file on disk
+    pub fn tokenizer_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.tokenizer_path = Some(path.into());
+        self
+    }
+
+    /// Load the model weights from a `.safetensors` file on disk
+    pub fn model_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.model_path = Some(path.into());
+        self
+    }
+
+    pub fn device<D: Into<Device>>(mut self, device: D) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    pub fn build(self) -> Result<SpladeEmbeddings> {
+        let model_id = self.model.as_deref().unwrap_or(DEFAULT_SPLADE_MODEL);
+        let mut embedding_config = EmbeddingFunctionConfig::new("splade").model(model_id);
+        if let Some(revision) = &self.revision {
+            embedding_config = embedding_config.option("revision", revision);
+        }
+        for (name, path) in [
+            ("config_path", &self.config_path),
+            ("tokenizer_path", &self.tokenizer_path),
+            ("model_path", &self.model_path),
+        ] {
+            if let Some(path) = path {
+                embedding_config = embedding_config.option(name, path.to_string_lossy());
+            }
+        }
+
+        let (config_file, tokenizer_file, model_file) =
+            match (self.config_path, self.tokenizer_path, self.model_path) {
+                (Some(config), Some(tokenizer), Some(model)) => (config, tokenizer, model),
+                (config, tokenizer, model) => {
+                    let api = Api::new()?;
+                    let repo = api.repo(Repo::with_revision(
+                        model_id.to_string(),
+                        RepoType::Model,
+                        self.revision.unwrap_or_else(|| "main".to_string()),
+                    ));
+                    (
+                        match config {
+                            Some(path) => path,
+                            None => repo.get("config.json")?,
+                        },
+                        match tokenizer {
+                            Some(path) => path,
+                            None => repo.get("tokenizer.json")?,
+                        },
+                        match model {
+                            Some(path) => path,
+                            None => repo.get("model.safetensors")?,
+                        },
+                    )
+                }
+            };
+
+        let config = std::fs::read_to_string(&config_file).map_err(|e| Error::Runtime {
+            message: format!("Error reading {}: {}", config_file.display(), e),
+        })?;
+        let parse_error = |e: serde_json::Error| Error::Runtime {
+            message: format!("Error parsing the model configuration: {}", e),
+        };
+        let bert_config: BertConfig = serde_json::from_str(&config).map_err(parse_error)?;
+        let head_config: HeadConfig = serde_json::from_str(&config).map_err(parse_error)?;
+
+        let device = self.device.unwrap_or(Device::Cpu);
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&[model_file], DType::F32, &device)? };
+        let model = BertModel::load(vb.clone(), &bert_config)?;
+        let head = MlmHead::load(vb, &head_config)?;
+        let tokenizer = Tokenizer::from_file(tokenizer_file).map_err(|e| Error::Runtime {
+            message: format!("Error loading tokenizer: {}", e),
+        })?;
+        embedding_config = embedding_config.dimensions(head_config.vocab_size);
+
+        Ok(SpladeEmbeddings {
+            model,
+            head,
+            tokenizer,
+            max_len: head_config.max_position_embeddings,
+            device,
+            embedding_config,
+        })
+    }
+}
+
+/// The masked language model head of BERT, which scores every token of the
+/// vocabulary at every position
+struct MlmHead {
+    dense: Linear,
+    layer_norm: LayerNorm,
+    decoder: Linear,
+}
+
+impl MlmHead {
+    fn load(vb: VarBuilder, config: &HeadConfig) -> Result<Self> {
+        let predictions = vb.pp("cls.predictions");
+        let dense = linear(
+            config.hidden_size,
+            config.hidden_size,
+            predictions.pp("transform.dense"),
+        )?;
+        let layer_norm = layer_norm(
+            config.hidden_size,
+            config.layer_norm_eps,
+            predictions.pp("transform.LayerNorm"),
+        )?;
+        // The decoder usually shares its weights with the word embeddings, in
+        // which case the weights are only stored once
+        let shape = (config.vocab_size, config.hidden_size);
+        let weight = predictions.get(shape, "decoder.weight").or_else(|_| {
+            vb.pp("bert.embeddings.word_embeddings")
+                .get(shape, "weight")
+        })?;
+        let bias = predictions.get(config.vocab_size, "bias")?;
+        Ok(Self {
+            dense,
+            layer_norm,
+            decoder: Linear::new(weight, Some(bias)),
+        })
+    }
+}
+
+impl Module for MlmHead {
+    fn forward(&self, xs: &Tensor) -> candle_core::Result<Tensor> {
+        let xs = self.dense.forward(xs)?.gelu_erf()?;
+        let xs = self.layer_norm.forward(&xs)?;
+        self.decoder.forward(&xs)
+    }
+}
+
+/// Compute sparse embeddings of text with a SPLADE model
+///
+/// The embeddings have a weight for each token of the model's vocabulary, most
+/// of them zero, and are stored in a sparse vector column (see
+/// [`crate::sparse::sparse_vector_type`]).  They are searched with
+/// [`crate::query::Query::nearest_to_sparse`], using [`Self::embed_query`] to
+/// embed the query text, or with [`crate::query::Query::nearest_to_text`] if
+/// the column is an embedding column of the table.
+///
+/// # Example
+///
+/// ```no_run
+/// # async fn doctest_helper(db: lancedb::Connection) -> lancedb::Result<()> {
+/// use std::sync::Arc;
+/// use lancedb::embeddings::splade::SpladeEmbeddings;
+/// use lancedb::query::{ExecutableQuery, QueryBase};
+///
+/// let splade = Arc::new(SpladeEmbeddings::builder().build()?);
+/// db.embedding_registry().register("splade", splade.clone())?;
+///
+/// let table = db.open_table("docs").execute().await?;
+/// let results = table
+///     .query()
+///     .nearest_to_sparse(splade.embed_query("what is a sparse vector?")?)
+///     .limit(5)
+///     .execute()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SpladeEmbeddings {
+    model: BertModel,
+    head: MlmHead,
+    tokenizer: Tokenizer,
+    /// The longest input the model accepts, in tokens
+    max_len: usize,
+    device: Device,
+    /// The options the embeddings were built with, see [`EmbeddingFunction::config`]
+    embedding_config: EmbeddingFunctionConfig,
+}
+
+impl std::fmt::Debug for SpladeEmbeddings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpladeEmbeddings")
+            .field("config", &self.embedding_config)
+            .field("device", &self.device)
+            .finish()
+    }
+}
+
+impl SpladeEmbeddings {
+    pub fn builder() -> SpladeEmbeddingsBuilder {
+        SpladeEmbeddingsBuilder::new()
+    }
+
+    /// Embed one text
+    ///
+    /// The weight of each token is `log(1 + relu(logit))` of its largest logit
+    /// over the positions of the text.
+    pub fn embed_query(&self, text: &str) -> Result<SparseVector> {
+        let encoding = self
+            .tokenizer
+            .encode(text, true)
+            .map_err(|e| Error::Runtime {
+                message: format!("Error tokenizing text: {}", e),
+            })?;
+        let mut ids = encoding.get_ids().to_vec();
+        ids.truncate(self.max_len);
+        // Texts are embedded one at a time, so there is no padding to mask
+        let input_ids = Tensor::new(ids.as_slice(), &self.device)?.unsqueeze(0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+        let hidden = self.model.forward(&input_ids, &token_type_ids)?;
+        let weights = self
+            .head
+            .forward(&hidden)?
+            .relu()?
+            .affine(1.0, 1.0)?
+            .log()?
+            .max(1)?
+            .squeeze(0)?
+            .to_device(&Device::Cpu)?
+            .to_vec1::<f32>()?;
+        Ok(sparse_weights(weights))
+    }
+
+    fn compute_inner(&self, input: &dyn Array) -> Result<ArrayRef> {
+        embed_texts(input, |text| self.embed_query(text))
+    }
+}
+
+/// The non-zero weights of a vector over the vocabulary
+fn sparse_weights(weights: Vec<f32>) -> SparseVector {
+    weights
+        .into_iter()
+        .enumerate()
+        .filter(|(_, weight)| *weight > 0.0)
+        .map(|(index, weight)| (index as u32, weight))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Embed each text of `input` with `embed`, into an array of sparse vectors
+/// with the nulls of `input`
+fn embed_texts(
+    input: &dyn Array,
+    mut embed: impl FnMut(&str) -> Result<SparseVector>,
+) -> Result<ArrayRef> {
+    let vectors = (0..input.len())
+        .map(|row| {
+            if input.is_null(row) {
+                return Ok(None);
+            }
+            let text = match input.data_type() {
+                DataType::Utf8 => input.as_string::<i32>().value(row),
+                DataType::LargeUtf8 => input.as_string::<i64>().value(row),
+                other => {
+                    return Err(Error::InvalidInput {
+                        message: format!("expected text, got {}", other),
+                    })
+                }
+            };
+            embed(text).map(Some)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    sparse_vectors_to_array(vectors)
+}
+
+impl EmbeddingFunction for SpladeEmbeddings {
+    fn name(&self) -> &str {
+        "splade"
+    }
+
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(sparse_vector_type()))
+    }
+
+    fn compute_source_embeddings(&self, source: ArrayRef) -> Result<ArrayRef> {
+        self.compute_inner(source.as_ref())
+    }
+
+    /// Embed text as sparse vectors, see [`Self::embed_query`]
+    fn compute_query_embeddings(&self, input: ArrayRef) -> Result<ArrayRef> {
+        self.compute_inner(input.as_ref())
+    }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        Some(self.embedding_config.clone())
+    }
+}
+
+/// Creates [`SpladeEmbeddings`] from their configuration
+///
+/// The model is loaded on the CPU.
+#[derive(Debug, Default)]
+pub struct SpladeEmbeddingsFactory;
+
+impl EmbeddingFunctionFactory for SpladeEmbeddingsFactory {
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>> {
+        let mut builder = SpladeEmbeddings::builder();
+        if let Some(model) = &config.model {
+            builder = builder.model(model);
+        }
+        if let Some(revision) = config.options.get("revision") {
+            builder = builder.revision(revision);
+        }
+        if let Some(config_path) = config.options.get("config_path") {
+            builder = builder.config_path(config_path);
+        }
+        if let Some(tokenizer_path) = config.options.get("tokenizer_path") {
+            builder = builder.tokenizer_path(tokenizer_path);
+        }
+        if let Some(model_path) = config.options.get("model_path") {
+            builder = builder.model_path(model_path);
+        }
+        Ok(Arc::new(builder.build()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, LargeStringArray, StringArray};
+
+    use super::*;
+    use crate::sparse::is_sparse_vector_type;
+
+    #[test]
+    fn test_sparse_weights() {
+        let vector = sparse_weights(vec![0.0, 1.5, 0.0, 0.0, 0.25]);
+        assert_eq!(vector.indices, vec![1, 4]);
+        assert_eq!(vector.values, vec![1.5, 0.25]);
+        assert!(sparse_weights(vec![0.0; 8]).is_empty());
+    }
+
+    #[test]
+    fn test_embed_texts_shape() {
+        // Embeds each text as its length at the index of its first byte
+        let embed = |text: &str| -> Result<SparseVector> {
+            Ok(vec![(text.as_bytes()[0] as u32, text.len() as f32)].into())
+        };
+        let input = StringArray::from(vec![Some("ab"), None, Some("cde")]);
+        let embeddings = embed_texts(&input, embed).unwrap();
+        assert_eq!(embeddings.data_type(), &sparse_vector_type());
+        assert!(is_sparse_vector_type(embeddings.data_type()));
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(
+            SparseVector::from_array(embeddings.as_ref(), 0).unwrap(),
+            Some(vec![(b'a' as u32, 2.0)].into())
+        );
+        assert!(embeddings.is_null(1));
+        assert_eq!(
+            SparseVector::from_array(embeddings.as_ref(), 2).unwrap(),
+            Some(vec![(b'c' as u32, 3.0)].into())
+        );
+
+        let input = LargeStringArray::from(vec!["x"]);
+        assert_eq!(embed_texts(&input, embed).unwrap().len(), 1);
+        let input = Int32Array::from(vec![1]);
+        assert!(matches!(
+            embed_texts(&input, embed),
+            Err(Error::InvalidInput { .. })
+        ));
+    }
+}
@@ -40,6 +40,12 @@ pub enum Error {
     Schema { message: String },
     #[snafu(display("Runtime error: {message}"))]
     Runtime { message: String },
+    #[snafu(display("Timeout error: {message}"))]
+    Timeout { message: String },
+    #[snafu(display(
+        "Commit conflict: expected the table to be at version {expected} but it is at version {actual}"
+    ))]
+    CommitConflict { expected: u64, actual: u64 },
 
     // 3rd party / external errors
     #[snafu(display("object_store error: {source}"))]
@@ -137,16 +143,24 @@ impl From<polars::prelude::PolarsError> for Error {
     }
 }
 
-#[cfg(feature = "sentence-transformers")]
+#[cfg(any(
+    feature = "sentence-transformers",
+    feature = "clip",
+    feature = "splade"
+))]
 impl From<hf_hub::api::sync::ApiError> for Error {
     fn from(source: hf_hub::api::sync::ApiError) -> Self {
         Self::Other {
-            message: "Error in Sentence Transformers integration.".to_string(),
+            message: "Error downloading from the Hugging Face Hub.".to_string(),
             source: Some(Box::new(source)),
         }
     }
 }
-#[cfg(feature = "sentence-transformers")]
+#[cfg(any(
+    feature = "sentence-transformers",
+    feature = "clip",
+    feature = "splade"
+))]
 impl From<candle_core::Error> for Error {
     fn from(source: candle_core::Error) -> Self {
         Self::Other {
@@ -12,11 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use scalar::FtsIndexBuilder;
 use serde::Deserialize;
 use serde_with::skip_serializing_none;
+use tokio::task::JoinHandle;
 
 use crate::{table::TableInternal, DistanceType, Error, Result};
 
@@ -75,6 +77,7 @@ pub struct IndexBuilder {
     parent: Arc<dyn TableInternal>,
     pub(crate) index: Index,
     pub(crate) columns: Vec<String>,
+    pub(crate) name: Option<String>,
     pub(crate) replace: bool,
 }
 
@@ -84,10 +87,24 @@ impl IndexBuilder {
             parent,
             index,
             columns,
+            name: None,
             replace: true,
         }
     }
 
+    /// The name of the index.
+    ///
+    /// If this is not set then the name will be `{column}_idx`.
+    ///
+    /// Each index on a table must have a unique name.  A column can have
+    /// several vector indices with different names, for example an IVF_PQ
+    /// index and an IVF_HNSW_SQ index.  Queries search the first one unless
+    /// another is picked with [`crate::query::VectorQuery::use_index`].
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
     /// Whether to replace the existing index, the default is `true`.
     ///
     /// If this is false, and another index already exists on the same columns
@@ -101,6 +118,203 @@ impl IndexBuilder {
     pub async fn execute(self) -> Result<()> {
         self.parent.clone().create_index(self).await
     }
+
+    /// Start building the index in a background task
+    ///
+    /// Training a vector index on a large table can take a long time.  This
+    /// returns immediately with a [`IndexBuildHandle`] that can be polled for
+    /// the status of the build, awaited, or used to cancel the build.
+    ///
+    /// This must be called from within a tokio runtime.
+    pub fn execute_in_background(self) -> IndexBuildHandle {
+        IndexBuildHandle::spawn(self)
+    }
+
+    /// The name the index will be created with
+    fn index_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            format!(
+                "{}_idx",
+                self.columns.first().map(String::as_str).unwrap_or_default()
+            )
+        })
+    }
+}
+
+/// The status of an index build started with [`IndexBuilder::execute_in_background`]
+///
+/// Lance trains and writes an index in a single call and does not report the
+/// stages of the build, so only the overall status is known.
+#[derive(Debug, Clone)]
+pub enum IndexBuildStatus {
+    /// The index is being trained and written
+    Running,
+    /// The index has been committed to the table
+    Done,
+    /// The build failed with the given error
+    Failed(Arc<Error>),
+    /// The build was cancelled with [`IndexBuildHandle::cancel`] before the
+    /// index was committed
+    Cancelled,
+}
+
+struct IndexBuildTracker {
+    status: Mutex<IndexBuildStatus>,
+    /// The uuid of the index with the same name before the build started, set
+    /// once the build has looked it up
+    replaced_uuid: Mutex<Option<Option<String>>>,
+}
+
+impl IndexBuildTracker {
+    /// Move from [`IndexBuildStatus::Running`] to a final status.  Returns false
+    /// if the build has already finished.
+    fn finish(&self, status: IndexBuildStatus) -> bool {
+        let mut current = self.status.lock().unwrap();
+        if !matches!(*current, IndexBuildStatus::Running) {
+            return false;
+        }
+        *current = status;
+        true
+    }
+}
+
+/// A handle to an index build running in the background
+///
+/// Dropping the handle does not stop the build.  Use [`Self::cancel`] for that.
+pub struct IndexBuildHandle {
+    task: JoinHandle<()>,
+    tracker: Arc<IndexBuildTracker>,
+    started: Instant,
+    parent: Arc<dyn TableInternal>,
+    index_name: String,
+}
+
+impl std::fmt::Debug for IndexBuildHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IndexBuildHandle")
+            .field("index_name", &self.index_name)
+            .field("status", &self.status())
+            .field("elapsed", &self.elapsed())
+            .finish()
+    }
+}
+
+impl IndexBuildHandle {
+    fn spawn(builder: IndexBuilder) -> Self {
+        let tracker = Arc::new(IndexBuildTracker {
+            status: Mutex::new(IndexBuildStatus::Running),
+            replaced_uuid: Mutex::new(None),
+        });
+        let parent = builder.parent.clone();
+        let index_name = builder.index_name();
+        let task_tracker = tracker.clone();
+        let task_index_name = index_name.clone();
+        let task = tokio::spawn(async move {
+            let res = async {
+                let replaced_uuid = builder.parent.index_uuid(&task_index_name).await?;
+                *task_tracker.replaced_uuid.lock().unwrap() = Some(replaced_uuid);
+                builder.execute().await
+            }
+            .await;
+            task_tracker.finish(match res {
+                Ok(()) => IndexBuildStatus::Done,
+                Err(e) => IndexBuildStatus::Failed(Arc::new(e)),
+            });
+        });
+        Self {
+            task,
+            tracker,
+            started: Instant::now(),
+            parent,
+            index_name,
+        }
+    }
+
+    /// The current status of the build
+    pub fn status(&self) -> IndexBuildStatus {
+        self.tracker.status.lock().unwrap().clone()
+    }
+
+    /// Time since the build was started
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Returns true if the build has finished, failed, or been cancelled
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Cancel the build and return its final status
+    ///
+    /// The index is only committed to the table once it has been completely
+    /// written, so a build cancelled before then leaves the table unchanged.
+    /// Any files that were already written are removed the next time old
+    /// versions are pruned.  If the build finished, or the index was committed
+    /// before the cancellation took effect, then the status is
+    /// [`IndexBuildStatus::Done`] (or [`IndexBuildStatus::Failed`]) instead of
+    /// [`IndexBuildStatus::Cancelled`].
+    ///
+    /// Cancelling stops the build at its next await point.  Training steps
+    /// that lance runs on blocking threads, such as k-means, can't be
+    /// interrupted and keep using CPU until they finish, but their results are
+    /// discarded.
+    pub async fn cancel(self) -> Result<IndexBuildStatus> {
+        self.task.abort();
+        match self.task.await {
+            Ok(_) => {}
+            Err(e) if e.is_cancelled() => {
+                let replaced_uuid = self.tracker.replaced_uuid.lock().unwrap().clone();
+                let committed = match replaced_uuid {
+                    // The build never started
+                    None => false,
+                    Some(replaced_uuid) => {
+                        let uuid = self.parent.index_uuid(&self.index_name).await?;
+                        uuid.is_some() && uuid != replaced_uuid
+                    }
+                };
+                self.tracker.finish(if committed {
+                    IndexBuildStatus::Done
+                } else {
+                    IndexBuildStatus::Cancelled
+                });
+            }
+            Err(e) => {
+                self.tracker
+                    .finish(IndexBuildStatus::Failed(Arc::new(Error::Runtime {
+                        message: format!("the index build panicked: {}", e),
+                    })));
+            }
+        }
+        Ok(self.status())
+    }
+
+    /// Wait for the build to finish
+    ///
+    /// Returns the error the build failed with, if any.
+    pub async fn wait(self) -> Result<()> {
+        if let Err(e) = self.task.await {
+            self.tracker
+                .finish(IndexBuildStatus::Failed(Arc::new(Error::Runtime {
+                    message: format!("the index build panicked: {}", e),
+                })));
+        }
+        // The handle is consumed, so the error can be moved out of the tracker.
+        // It is only still shared if a status returned by `Self::status` is
+        // being held on to.
+        let status = std::mem::replace(
+            &mut *self.tracker.status.lock().unwrap(),
+            IndexBuildStatus::Done,
+        );
+        match status {
+            IndexBuildStatus::Failed(e) => {
+                Err(Arc::try_unwrap(e).unwrap_or_else(|e| Error::Runtime {
+                    message: e.to_string(),
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
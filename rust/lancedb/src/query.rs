@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::borrow::Cow;
 use std::future::Future;
 use std::sync::Arc;
 
-use arrow_array::{make_array, Array, Float16Array, Float32Array, Float64Array};
+use arrow_array::cast::AsArray;
+use arrow_array::{make_array, Array, Float16Array, Float32Array, Float64Array, StringArray};
 use arrow_schema::DataType;
+use datafusion_physical_plan::display::DisplayableExecutionPlan;
+use datafusion_physical_plan::memory::MemoryExec;
 use datafusion_physical_plan::ExecutionPlan;
 use half::f16;
 use lance::dataset::scanner::DatasetRecordBatchStream;
@@ -24,8 +28,10 @@ use lance_datafusion::exec::execute_plan;
 use lance_index::scalar::FullTextSearchQuery;
 
 use crate::arrow::SendableRecordBatchStream;
+use crate::embeddings::{EmbeddingDefinition, EmbeddingRegistry};
 use crate::error::{Error, Result};
-use crate::table::TableInternal;
+use crate::sparse::{is_sparse_vector_type, SparseVector};
+use crate::table::{ColumnKind, TableInternal};
 use crate::DistanceType;
 
 pub(crate) const DEFAULT_TOP_K: usize = 10;
@@ -108,6 +114,10 @@ pub trait IntoQueryVector {
     /// Note that the output is an array but, in most cases, this will be an array of
     /// length one.  The query vector is considered a single "item" and arrays of
     /// length one are how arrow represents scalars.
+    ///
+    /// Text (a string or an array with a single string value) is kept as is and
+    /// embedded with the embedding function of the queried column when the query
+    /// is executed, see [`Query::nearest_to_text`].
     fn to_query_vector(
         self,
         data_type: &DataType,
@@ -122,7 +132,10 @@ impl IntoQueryVector for Arc<dyn Array> {
         data_type: &DataType,
         _embedding_model_label: &str,
     ) -> Result<Arc<dyn Array>> {
-        if data_type != self.data_type() {
+        if is_text(self.data_type()) {
+            // Embedded when the query is executed
+            Ok(self)
+        } else if data_type != self.data_type() {
             match data_type {
                 // If the embedding wants floating point data we can try and cast
                 DataType::Float16 | DataType::Float32 | DataType::Float64 => {
@@ -157,7 +170,7 @@ impl IntoQueryVector for &dyn Array {
         data_type: &DataType,
         _embedding_model_label: &str,
     ) -> Result<Arc<dyn Array>> {
-        if data_type != self.data_type() {
+        if data_type != self.data_type() && !is_text(self.data_type()) {
             Err(Error::InvalidInput {
                 message: format!(
                 "failed to create query vector, the input data type was {:?} but the expected data type was {:?}",
@@ -171,6 +184,33 @@ impl IntoQueryVector for &dyn Array {
     }
 }
 
+impl IntoQueryVector for &str {
+    fn to_query_vector(
+        self,
+        _data_type: &DataType,
+        _embedding_model_label: &str,
+    ) -> Result<Arc<dyn Array>> {
+        // Embedded when the query is executed
+        Ok(Arc::new(StringArray::from(vec![self])))
+    }
+}
+
+impl IntoQueryVector for String {
+    fn to_query_vector(
+        self,
+        data_type: &DataType,
+        embedding_model_label: &str,
+    ) -> Result<Arc<dyn Array>> {
+        self.as_str()
+            .to_query_vector(data_type, embedding_model_label)
+    }
+}
+
+/// Whether a query vector is text that still has to be embedded
+fn is_text(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Utf8 | DataType::LargeUtf8)
+}
+
 impl IntoQueryVector for &[f16] {
     fn to_query_vector(
         self,
@@ -561,6 +601,9 @@ pub trait ExecutableQuery {
 pub struct Query {
     parent: Arc<dyn TableInternal>,
 
+    /// The registry used to embed text queries.
+    embedding_registry: Arc<dyn EmbeddingRegistry>,
+
     /// limit the number of rows to return.
     pub(crate) limit: Option<usize>,
 
@@ -592,9 +635,13 @@ pub struct Query {
 }
 
 impl Query {
-    pub(crate) fn new(parent: Arc<dyn TableInternal>) -> Self {
+    pub(crate) fn new(
+        parent: Arc<dyn TableInternal>,
+        embedding_registry: Arc<dyn EmbeddingRegistry>,
+    ) -> Self {
         Self {
             parent,
+            embedding_registry,
             limit: None,
             offset: None,
             filter: None,
@@ -653,6 +700,63 @@ impl Query {
         vector_query.query_vector = Some(query_vector);
         Ok(vector_query)
     }
+
+    /// Find the nearest vectors to the embedding of the given text.
+    ///
+    /// This converts the query from a plain query to a vector query.
+    ///
+    /// The text is embedded with the embedding function of the queried column
+    /// when the query is executed.  If the table has only one embedding column
+    /// then the column does not need to be specified, otherwise use
+    /// [`VectorQuery::column`] to specify the embedding column (or its source
+    /// column).
+    ///
+    /// The embedding function must be in the table's [`EmbeddingRegistry`], or
+    /// the registry must be able to create it from the configuration stored
+    /// with the table.
+    ///
+    /// If the embedding column holds sparse vectors, such as those of
+    /// [`crate::embeddings::EmbeddingFunction`]s for SPLADE models, the query
+    /// runs as a sparse vector search, see [`Self::nearest_to_sparse`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text that will be embedded and used for search.
+    pub fn nearest_to_text(self, text: &str) -> VectorQuery {
+        let mut vector_query = self.into_vector();
+        vector_query.query_vector = Some(Arc::new(StringArray::from(vec![text])));
+        vector_query
+    }
+
+    /// Find the rows whose sparse vectors have the largest dot product with
+    /// the given sparse vector.
+    ///
+    /// This converts the query from a plain query to a sparse vector query.
+    /// The results have a `_score` column with the dot product, best first.
+    ///
+    /// If there is only one sparse vector column (see
+    /// [`crate::sparse::sparse_vector_type`]) then the column does not need to
+    /// be specified.  Otherwise use [`SparseVectorQuery::column`] to specify
+    /// which column to search.
+    ///
+    /// This is an exhaustive search, there is no persisted index.  The first
+    /// search of a column by a table handle reads the whole column and keeps
+    /// an inverted list of its non-zero values in memory, which later searches
+    /// by the same handle reuse.  The cache is not shared with other handles or
+    /// processes, and when the table changes only the fragments that were
+    /// added or changed are read again.  Only rows that share a non-zero index
+    /// with the query are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `vector` - The sparse vector that will be used for search.
+    pub fn nearest_to_sparse(self, vector: impl Into<SparseVector>) -> SparseVectorQuery {
+        SparseVectorQuery {
+            base: self,
+            column: None,
+            query_vector: vector.into(),
+        }
+    }
 }
 
 impl HasQuery for Query {
@@ -707,6 +811,9 @@ pub struct VectorQuery {
     pub(crate) distance_type: Option<DistanceType>,
     /// Default is true. Set to false to enforce a brute force search.
     pub(crate) use_index: bool,
+    /// The name of the vector index to search, if there is more than one
+    /// index on the column.
+    pub(crate) index_name: Option<String>,
 }
 
 impl VectorQuery {
@@ -719,6 +826,7 @@ impl VectorQuery {
             refine_factor: None,
             distance_type: None,
             use_index: true,
+            index_name: None,
         }
     }
 
@@ -728,7 +836,9 @@ impl VectorQuery {
     /// the call to [`Query::nearest_to`]
     ///
     /// This parameter must be specified if the table has more than one column
-    /// whose data type is a fixed-size-list of floats.
+    /// whose data type is a fixed-size-list of floats.  For text queries it
+    /// must be specified if the table has more than one embedding column, and
+    /// may be either the embedding column or its source column.
     pub fn column(mut self, column: &str) -> Self {
         self.column = Some(column.to_string());
         self
@@ -819,11 +929,156 @@ impl VectorQuery {
         self.use_index = false;
         self
     }
+
+    /// Search using the vector index with the given name
+    ///
+    /// Indices can be given a name with [`crate::index::IndexBuilder::name`].  A
+    /// column may have several vector indices, for example an IVF_PQ index and an
+    /// IVF_HNSW_SQ index, and this picks the one to search.  If the vector column
+    /// has not been set with [`Self::column`] then it is taken from the index.
+    ///
+    /// An error will be returned when the query is executed if the index does not exist
+    /// or is not a vector index on the queried column.
+    ///
+    /// This overrides any previous call to [`Self::bypass_vector_index`].
+    pub fn use_index(mut self, index_name: impl Into<String>) -> Self {
+        self.index_name = Some(index_name.into());
+        self.use_index = true;
+        self
+    }
+
+    /// Embed a text query with the embedding function of the queried column
+    ///
+    /// Returns the query unchanged if the query vector is not text.
+    async fn embed_text(&self) -> Result<Cow<'_, Self>> {
+        let Some(text) = self
+            .query_vector
+            .as_ref()
+            .filter(|query_vector| is_text(query_vector.data_type()))
+        else {
+            return Ok(Cow::Borrowed(self));
+        };
+
+        let table_definition = self.base.parent.table_definition().await?;
+        let definitions = table_definition
+            .column_definitions
+            .iter()
+            .filter_map(|cd| match &cd.kind {
+                ColumnKind::Embedding(definition) => Some(definition),
+                ColumnKind::Physical => None,
+            })
+            .collect::<Vec<_>>();
+        // Columns replaced by `Table::reembed`, or still being filled by it,
+        // are only queried when they are named
+        let current = definitions
+            .iter()
+            .copied()
+            .filter(|definition| definition.superseded_by.is_none() && !definition.pending)
+            .collect::<Vec<_>>();
+        let definition: &EmbeddingDefinition = match &self.column {
+            Some(column) => definitions
+                .iter()
+                .find(|definition| &definition.dest_column_name() == column)
+                .or_else(|| {
+                    current
+                        .iter()
+                        .find(|definition| &definition.source_column == column)
+                })
+                .copied()
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!(
+                        "cannot query column '{}' with text, it is not an embedding column",
+                        column
+                    ),
+                })?,
+            None => match current.as_slice() {
+                [definition] => *definition,
+                [] => {
+                    return Err(Error::InvalidInput {
+                        message: "cannot query with text, the table has no embedding columns"
+                            .to_string(),
+                    })
+                }
+                _ => {
+                    return Err(Error::InvalidInput {
+                        message: "the table has more than one embedding column, use `column` to choose which one to query with text".to_string(),
+                    })
+                }
+            },
+        };
+
+        let function = self
+            .base
+            .embedding_registry
+            .get_or_create(definition)?
+            .ok_or_else(|| Error::EmbeddingFunctionNotFound {
+                name: definition.embedding_name.clone(),
+                reason: "No embedding function was found with that name within the registry."
+                    .to_string(),
+            })?;
+        let function = definition.wrap_function(function);
+        // Functions that embed text in the same space as other data (such as
+        // images) take the text as is
+        let source_type = function.source_type()?;
+        let text = if is_text(source_type.as_ref()) {
+            arrow_cast::cast(text, source_type.as_ref())?
+        } else {
+            text.clone()
+        };
+        let embedding = function.compute_query_embeddings(text)?;
+        // Sparse embeddings are kept as they are, and searched by
+        // `Self::sparse_query`
+        if is_sparse_vector_type(embedding.data_type()) {
+            let mut query = self.clone();
+            query.query_vector = Some(embedding);
+            query.column = Some(definition.dest_column_name());
+            return Ok(Cow::Owned(query));
+        }
+        // Functions may return the single query vector as a list
+        let embedding = match embedding.data_type() {
+            DataType::FixedSizeList(_, _) if embedding.len() == 1 => {
+                embedding.as_fixed_size_list().value(0)
+            }
+            DataType::List(_) if embedding.len() == 1 => embedding.as_list::<i32>().value(0),
+            _ => embedding,
+        };
+        let embedding = arrow_cast::cast(&embedding, &DataType::Float32)?;
+
+        let mut query = self.clone();
+        query.query_vector = Some(embedding);
+        query.column = Some(definition.dest_column_name());
+        Ok(Cow::Owned(query))
+    }
+
+    /// The sparse vector search of a query whose text was embedded as a sparse
+    /// vector by [`Self::embed_text`]
+    fn sparse_query(&self) -> Result<Option<SparseVectorQuery>> {
+        let Some(query_vector) = self
+            .query_vector
+            .as_ref()
+            .filter(|query_vector| is_sparse_vector_type(query_vector.data_type()))
+        else {
+            return Ok(None);
+        };
+        Ok(Some(SparseVectorQuery {
+            base: self.base.clone(),
+            column: self.column.clone(),
+            query_vector: SparseVector::from_array(query_vector.as_ref(), 0)?.unwrap_or_default(),
+        }))
+    }
 }
 
 impl ExecutableQuery for VectorQuery {
     async fn create_plan(&self, options: QueryExecutionOptions) -> Result<Arc<dyn ExecutionPlan>> {
-        self.base.parent.clone().create_plan(self, options).await
+        let query = self.embed_text().await?;
+        if let Some(sparse_query) = query.sparse_query()? {
+            return sparse_query.create_plan(options).await;
+        }
+        self.base
+            .parent
+            .clone()
+            .create_plan(query.as_ref(), options)
+            .await
     }
 
     async fn execute_with_options(
@@ -839,7 +1094,11 @@ impl ExecutableQuery for VectorQuery {
     }
 
     async fn explain_plan(&self, verbose: bool) -> Result<String> {
-        self.base.parent.explain_plan(self, verbose).await
+        let query = self.embed_text().await?;
+        if let Some(sparse_query) = query.sparse_query()? {
+            return sparse_query.explain_plan(verbose).await;
+        }
+        self.base.parent.explain_plan(query.as_ref(), verbose).await
     }
 }
 
@@ -849,14 +1108,86 @@ impl HasQuery for VectorQuery {
     }
 }
 
+/// A builder for sparse vector searches
+///
+/// See [`Query::nearest_to_sparse`] for more details.
+///
+/// See [`QueryBase`] for additional methods that can be used to
+/// parameterize the query.
+///
+/// See [`ExecutableQuery`] for methods that can be used to execute
+/// the query and retrieve results.
+#[derive(Debug, Clone)]
+pub struct SparseVectorQuery {
+    pub(crate) base: Query,
+    // The column to run the query on. If not specified, we will attempt to guess
+    // the column based on the dataset's schema.
+    pub(crate) column: Option<String>,
+    pub(crate) query_vector: SparseVector,
+}
+
+impl SparseVectorQuery {
+    /// Set the sparse vector column to query
+    ///
+    /// This parameter must be specified if the table has more than one sparse
+    /// vector column.
+    pub fn column(mut self, column: &str) -> Self {
+        self.column = Some(column.to_string());
+        self
+    }
+}
+
+impl ExecutableQuery for SparseVectorQuery {
+    async fn create_plan(&self, options: QueryExecutionOptions) -> Result<Arc<dyn ExecutionPlan>> {
+        let results = self.base.parent.sparse_search(self, options).await?;
+        let schema = results
+            .first()
+            .map(|batch| batch.schema())
+            .ok_or_else(|| Error::Runtime {
+                message: "the sparse vector search returned no batches".to_string(),
+            })?;
+        Ok(Arc::new(MemoryExec::try_new(&[results], schema, None)?))
+    }
+
+    async fn execute_with_options(
+        &self,
+        options: QueryExecutionOptions,
+    ) -> Result<SendableRecordBatchStream> {
+        Ok(SendableRecordBatchStream::from(
+            DatasetRecordBatchStream::new(execute_plan(
+                self.create_plan(options).await?,
+                Default::default(),
+            )?),
+        ))
+    }
+
+    async fn explain_plan(&self, verbose: bool) -> Result<String> {
+        let plan = self.create_plan(Default::default()).await?;
+        let display = DisplayableExecutionPlan::new(plan.as_ref());
+        Ok(format!(
+            "SparseVectorSearch: column={}, non_zeros={}\n  {}",
+            self.column.as_deref().unwrap_or("<default>"),
+            self.query_vector.len(),
+            display.indent(verbose)
+        ))
+    }
+}
+
+impl HasQuery for SparseVectorQuery {
+    fn mut_query(&mut self) -> &mut Query {
+        &mut self.base
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
 
     use super::*;
     use arrow_array::{
-        cast::AsArray, Float32Array, Int32Array, RecordBatch, RecordBatchIterator,
-        RecordBatchReader,
+        cast::AsArray,
+        types::{Float32Type, Int32Type},
+        Float32Array, Int32Array, RecordBatch, RecordBatchIterator, RecordBatchReader,
     };
     use arrow_schema::{DataType, Field as ArrowField, Schema as ArrowSchema};
     use futures::{StreamExt, TryStreamExt};
@@ -1197,4 +1528,157 @@ mod tests {
             assert!(batch.column_by_name("_rowid").is_some());
         }
     }
+
+    #[tokio::test]
+    async fn test_nearest_to_sparse() {
+        let tmp_dir = tempdir().unwrap();
+        let conn = connect(tmp_dir.path().to_str().unwrap())
+            .execute()
+            .await
+            .unwrap();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            ArrowField::new("id", DataType::Int32, false),
+            ArrowField::new("sparse", crate::sparse::sparse_vector_type(), true),
+        ]));
+        let vectors = vec![
+            Some(SparseVector::from(vec![(1, 1.0)])),
+            Some(SparseVector::from(vec![(1, 1.0), (7, 2.0)])),
+            None,
+            Some(SparseVector::from(vec![(7, 4.0), (9, 1.0)])),
+            Some(SparseVector::from(vec![(9, 1.0)])),
+        ];
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..5)),
+                crate::sparse::sparse_vectors_to_array(vectors).unwrap(),
+            ],
+        )
+        .unwrap();
+        let table = conn
+            .create_table(
+                "my_table",
+                RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        let ids_and_scores = |batches: Vec<RecordBatch>| {
+            batches
+                .iter()
+                .flat_map(|batch| {
+                    let ids = batch["id"].as_primitive::<Int32Type>().clone();
+                    let scores = batch["_score"].as_primitive::<Float32Type>().clone();
+                    ids.values()
+                        .iter()
+                        .copied()
+                        .zip(scores.values().iter().copied())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Scores: id 0 = 1, id 1 = 3, id 3 = 4, id 4 = 0
+        let query = SparseVector::from(vec![(1, 1.0), (7, 1.0)]);
+        let results = table
+            .query()
+            .nearest_to_sparse(query.clone())
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids_and_scores(results), vec![(3, 4.0), (1, 3.0), (0, 1.0)]);
+
+        let results = table
+            .query()
+            .nearest_to_sparse(query.clone())
+            .column("sparse")
+            .only_if("id < 3")
+            .limit(1)
+            .with_row_id()
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(results[0].column_by_name("_rowid").is_some());
+        assert_eq!(ids_and_scores(results), vec![(1, 3.0)]);
+
+        let results = table
+            .query()
+            .nearest_to_sparse(query)
+            .select(Select::columns(&["id"]))
+            .offset(1)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(results[0].num_columns(), 2);
+        assert_eq!(ids_and_scores(results), vec![(1, 3.0), (0, 1.0)]);
+
+        // The index is updated when the table changes
+        table.delete("id = 3").await.unwrap();
+        let results = table
+            .query()
+            .nearest_to_sparse(vec![(7, 1.0)])
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids_and_scores(results), vec![(1, 2.0)]);
+
+        let vectors = (5..8).map(|id| Some(SparseVector::from(vec![(7, id as f32)])));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(5..8)),
+                crate::sparse::sparse_vectors_to_array(vectors).unwrap(),
+            ],
+        )
+        .unwrap();
+        table
+            .add(RecordBatchIterator::new(vec![Ok(batch)], schema))
+            .execute()
+            .await
+            .unwrap();
+        let query = table.query().nearest_to_sparse(vec![(7, 1.0)]);
+        let results = query
+            .clone()
+            .execute_with_options(QueryExecutionOptions {
+                max_batch_length: 2,
+            })
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(
+            results.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 2]
+        );
+        assert_eq!(
+            ids_and_scores(results),
+            vec![(7, 7.0), (6, 6.0), (5, 5.0), (1, 2.0)]
+        );
+
+        // The filter is checked before the limit
+        let results = query
+            .only_if("id < 6")
+            .limit(2)
+            .execute()
+            .await
+            .unwrap()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(ids_and_scores(results), vec![(5, 5.0), (1, 2.0)]);
+    }
 }
@@ -23,7 +23,8 @@ use serde::Deserialize;
 use tokio::task::spawn_blocking;
 
 use crate::connection::{
-    ConnectionInternal, CreateTableBuilder, NoData, OpenTableBuilder, TableNamesBuilder,
+    snapshot::CommitRecord, ConnectionInternal, CreateTableBuilder, NoData, OpenTableBuilder,
+    TableNamesBuilder,
 };
 use crate::embeddings::EmbeddingRegistry;
 use crate::error::Result;
@@ -201,6 +202,18 @@ impl<S: HttpSend> ConnectionInternal for RemoteDatabase<S> {
         })
     }
 
+    async fn latest_commit(&self) -> Result<Option<CommitRecord>> {
+        Err(crate::Error::NotSupported {
+            message: "Transactions are not supported in the remote API".to_string(),
+        })
+    }
+
+    async fn write_commit(&self, _record: &CommitRecord) -> Result<()> {
+        Err(crate::Error::NotSupported {
+            message: "Transactions are not supported in the remote API".to_string(),
+        })
+    }
+
     fn embedding_registry(&self) -> &dyn EmbeddingRegistry {
         todo!()
     }
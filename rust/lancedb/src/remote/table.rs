@@ -1,13 +1,15 @@
 use std::io::Cursor;
 use std::sync::{Arc, Mutex};
 
+use crate::embeddings::{EmbeddingDefinition, EmbeddingFunction};
 use crate::index::Index;
 use crate::index::IndexStatistics;
 use crate::query::Select;
+use crate::table::bad_vectors::BadVectorReader;
 use crate::table::AddDataMode;
 use crate::utils::{supported_btree_data_type, supported_vector_data_type};
 use crate::Error;
-use arrow_array::RecordBatchReader;
+use arrow_array::{RecordBatch, RecordBatchReader};
 use arrow_ipc::reader::FileReader;
 use arrow_schema::{DataType, SchemaRef};
 use async_trait::async_trait;
@@ -27,10 +29,11 @@ use crate::{
     connection::NoData,
     error::Result,
     index::{IndexBuilder, IndexConfig},
-    query::{Query, QueryExecutionOptions, VectorQuery},
+    query::{Query, QueryExecutionOptions, SparseVectorQuery, VectorQuery},
     table::{
-        merge::MergeInsertBuilder, AddDataBuilder, NativeTable, OptimizeAction, OptimizeStats,
-        TableDefinition, TableInternal, UpdateBuilder,
+        merge::MergeInsertBuilder, transaction::TransactionOperation, AddDataBuilder, NativeTable,
+        OptimizeAction, OptimizeStats, TableDefinition, TableInternal, TableStatistics,
+        UpdateBuilder, WriteResult,
     },
 };
 
@@ -112,6 +115,36 @@ impl<S: HttpSend> RemoteTable<S> {
         self.client.check_response(request_id, response).await
     }
 
+    /// Writes conditional on the table version are not supported by the
+    /// server yet
+    fn check_no_expected_version(expected_version: Option<u64>) -> Result<()> {
+        if expected_version.is_some() {
+            return Err(Error::NotSupported {
+                message: "expected_version is not yet supported on LanceDB cloud.".into(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Parse the result of a write operation
+    ///
+    /// Older servers return an empty body, in which case the counts are unknown
+    /// and reported as zero.
+    async fn parse_write_result(
+        request_id: String,
+        response: reqwest::Response,
+    ) -> Result<WriteResult> {
+        let body = response.text().await.err_to_http(request_id.clone())?;
+        if body.trim().is_empty() {
+            return Ok(WriteResult::default());
+        }
+        serde_json::from_str(&body).map_err(|e| Error::Http {
+            source: format!("Failed to parse write result: {}", e).into(),
+            request_id,
+            status_code: None,
+        })
+    }
+
     async fn read_arrow_stream(
         &self,
         request_id: &str,
@@ -236,6 +269,11 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
             message: "checkout is not supported on LanceDB cloud.".into(),
         })
     }
+    async fn version_timestamp(&self, _version: u64) -> Result<i64> {
+        Err(Error::NotSupported {
+            message: "version_timestamp is not supported on LanceDB cloud.".into(),
+        })
+    }
     async fn checkout_latest(&self) -> Result<()> {
         Err(Error::NotSupported {
             message: "checkout is not supported on LanceDB cloud.".into(),
@@ -277,7 +315,9 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         &self,
         add: AddDataBuilder<NoData>,
         data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
+        Self::check_no_expected_version(add.expected_version)?;
+        let (data, num_bad_vectors) = BadVectorReader::wrap(self, &add.write_options, data).await?;
         let body = Self::reader_as_body(data)?;
         let mut request = self
             .client
@@ -294,9 +334,12 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
 
         let (request_id, response) = self.client.send(request, false).await?;
 
-        self.check_table_response(&request_id, response).await?;
+        let response = self.check_table_response(&request_id, response).await?;
 
-        Ok(())
+        let mut result = Self::parse_write_result(request_id, response).await?;
+        // The body has been sent, so every vector has been checked
+        result.num_bad_vectors = num_bad_vectors.load(std::sync::atomic::Ordering::Relaxed);
+        Ok(result)
     }
 
     async fn create_plan(
@@ -344,6 +387,10 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
             body["bypass_vector_index"] = serde_json::Value::Bool(true);
         }
 
+        if let Some(index_name) = query.index_name.as_ref() {
+            body["index_name"] = serde_json::Value::String(index_name.clone());
+        }
+
         let request = request.json(&body);
 
         let (request_id, response) = self.client.send(request, true).await?;
@@ -353,6 +400,15 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         Ok(Arc::new(OneShotExec::new(stream)))
     }
 
+    async fn sparse_search(
+        &self,
+        _query: &SparseVectorQuery,
+        _options: QueryExecutionOptions,
+    ) -> Result<Vec<RecordBatch>> {
+        Err(Error::NotSupported {
+            message: "sparse vector search is not yet supported in LanceDB Cloud".into(),
+        })
+    }
     async fn plain_query(
         &self,
         query: &Query,
@@ -377,6 +433,7 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         Ok(DatasetRecordBatchStream::new(stream))
     }
     async fn update(&self, update: UpdateBuilder) -> Result<u64> {
+        Self::check_no_expected_version(update.expected_version)?;
         let request = self
             .client
             .post(&format!("/v1/table/{}/update/", self.name));
@@ -397,15 +454,26 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
 
         Ok(0) // TODO: support returning number of modified rows once supported in SaaS.
     }
-    async fn delete(&self, predicate: &str) -> Result<()> {
+    async fn recompute_embedding(
+        &self,
+        _filter: &str,
+        _definition: EmbeddingDefinition,
+        _function: Arc<dyn EmbeddingFunction>,
+    ) -> Result<u64> {
+        Err(Error::NotSupported {
+            message: "recompute_embedding is not supported on LanceDB cloud.".into(),
+        })
+    }
+    async fn delete(&self, predicate: &str, expected_version: Option<u64>) -> Result<WriteResult> {
+        Self::check_no_expected_version(expected_version)?;
         let body = serde_json::json!({ "predicate": predicate });
         let request = self
             .client
             .post(&format!("/v1/table/{}/delete/", self.name))
             .json(&body);
         let (request_id, response) = self.client.send(request, false).await?;
-        self.check_table_response(&request_id, response).await?;
-        Ok(())
+        let response = self.check_table_response(&request_id, response).await?;
+        Self::parse_write_result(request_id, response).await
     }
 
     async fn create_index(&self, mut index: IndexBuilder) -> Result<()> {
@@ -429,6 +497,9 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         let mut body = serde_json::json!({
             "column": column
         });
+        if let Some(name) = index.name.take() {
+            body["name"] = serde_json::Value::String(name);
+        }
 
         let (index_type, distance_type) = match index.index {
             // TODO: Should we pass the actual index parameters? SaaS does not
@@ -486,7 +557,8 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         &self,
         params: MergeInsertBuilder,
         new_data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
+        Self::check_no_expected_version(params.expected_version)?;
         let query = MergeInsertRequest::try_from(params)?;
         let body = Self::reader_as_body(new_data)?;
         let request = self
@@ -498,9 +570,17 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
 
         let (request_id, response) = self.client.send(request, false).await?;
 
-        self.check_table_response(&request_id, response).await?;
+        let response = self.check_table_response(&request_id, response).await?;
 
-        Ok(())
+        Self::parse_write_result(request_id, response).await
+    }
+    async fn commit_transaction(
+        &self,
+        _operations: Vec<TransactionOperation>,
+    ) -> Result<WriteResult> {
+        Err(Error::NotSupported {
+            message: "transactions are not yet supported on LanceDB cloud.".into(),
+        })
     }
     async fn optimize(&self, _action: OptimizeAction) -> Result<OptimizeStats> {
         Err(Error::NotSupported {
@@ -526,6 +606,11 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
             message: "drop_columns is not yet supported.".into(),
         })
     }
+    async fn replace_schema_metadata(&self, _values: Vec<(String, String)>) -> Result<()> {
+        Err(Error::NotSupported {
+            message: "replace_schema_metadata is not yet supported.".into(),
+        })
+    }
 
     async fn list_indices(&self) -> Result<Vec<IndexConfig>> {
         // Make request to list the indices
@@ -580,6 +665,16 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
         Ok(index_configs)
     }
 
+    async fn drop_index(&self, index_name: &str) -> Result<()> {
+        let request = self.client.post(&format!(
+            "/v1/table/{}/index/{}/drop/",
+            self.name, index_name
+        ));
+        let (request_id, response) = self.client.send(request, false).await?;
+        self.check_table_response(&request_id, response).await?;
+        Ok(())
+    }
+
     async fn index_stats(&self, index_name: &str) -> Result<Option<IndexStatistics>> {
         let request = self.client.post(&format!(
             "/v1/table/{}/index/{}/stats/",
@@ -603,6 +698,11 @@ impl<S: HttpSend> TableInternal for RemoteTable<S> {
 
         Ok(Some(stats))
     }
+    async fn stats(&self) -> Result<TableStatistics> {
+        Err(Error::NotSupported {
+            message: "stats is not yet supported on LanceDB cloud.".into(),
+        })
+    }
     async fn table_definition(&self) -> Result<TableDefinition> {
         Err(Error::NotSupported {
             message: "table_definition is not supported on LanceDB cloud.".into(),
@@ -618,6 +718,9 @@ struct MergeInsertRequest {
     on: String,
     when_matched_update_all: bool,
     when_matched_update_all_filt: Option<String>,
+    /// JSON list of `[column, expression]` pairs
+    when_matched_update: Option<String>,
+    when_matched_update_filt: Option<String>,
     when_not_matched_insert_all: bool,
     when_not_matched_by_source_delete: bool,
     when_not_matched_by_source_delete_filt: Option<String>,
@@ -637,11 +740,20 @@ impl TryFrom<MergeInsertBuilder> for MergeInsertRequest {
             });
         }
         let on = value.on[0].clone();
+        let when_matched_update = value
+            .when_matched_update
+            .map(|updates| serde_json::to_string(&updates))
+            .transpose()
+            .map_err(|e| Error::InvalidInput {
+                message: format!("failed to serialize merge insert updates: {}", e),
+            })?;
 
         Ok(Self {
             on,
             when_matched_update_all: value.when_matched_update_all,
             when_matched_update_all_filt: value.when_matched_update_all_filt,
+            when_matched_update,
+            when_matched_update_filt: value.when_matched_update_filt,
             when_not_matched_insert_all: value.when_not_matched_insert_all,
             when_not_matched_by_source_delete: value.when_not_matched_by_source_delete,
             when_not_matched_by_source_delete_filt: value.when_not_matched_by_source_delete_filt,
@@ -697,7 +809,8 @@ mod tests {
             Box::pin(table.update().column("a", "a + 1").execute().map_ok(|_| ())),
             Box::pin(table.add(example_data()).execute().map_ok(|_| ())),
             Box::pin(table.merge_insert(&["test"]).execute(example_data())),
-            Box::pin(table.delete("false")), // TODO: other endpoints.
+            Box::pin(table.drop_index("my_index")),
+            Box::pin(table.delete("false").execute().map_ok(|_| ())), // TODO: other endpoints.
         ];
 
         for result in results {
@@ -1031,6 +1144,26 @@ mod tests {
         let body = collect_body(body).await;
         let expected_body = write_ipc_stream(&batch);
         assert_eq!(&body, &expected_body);
+
+        // Partial column updates
+        let table = Table::new_with_handler("my_table", |request| {
+            let params = request.url().query_pairs().collect::<HashMap<_, _>>();
+            assert_eq!(params["when_matched_update_all"], "false");
+            assert_eq!(
+                params["when_matched_update"],
+                r#"[["a","target.a + source.a"]]"#
+            );
+            assert_eq!(params["when_matched_update_filt"], "target.a < 10");
+
+            http::Response::builder().status(200).body("").unwrap()
+        });
+        let mut builder = table.merge_insert(&["some_col"]);
+        builder.when_matched_update([("a", "target.a + source.a")], Some("target.a < 10".into()));
+        let data = Box::new(RecordBatchIterator::new(
+            [Ok(batch.clone())],
+            batch.schema(),
+        ));
+        builder.execute(data).await.unwrap();
     }
 
     #[tokio::test]
@@ -1048,10 +1181,52 @@ mod tests {
             let predicate = body.get("predicate").unwrap().as_str().unwrap();
             assert_eq!(predicate, "id in (1, 2, 3)");
 
-            http::Response::builder().status(200).body("").unwrap()
+            http::Response::builder()
+                .status(200)
+                .body(r#"{"version": 7, "num_deleted_rows": 3}"#)
+                .unwrap()
         });
 
-        table.delete("id in (1, 2, 3)").await.unwrap();
+        let result = table.delete("id in (1, 2, 3)").await.unwrap();
+        assert_eq!(
+            result,
+            WriteResult {
+                version: 7,
+                num_deleted_rows: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expected_version_not_supported() {
+        let table = Table::new_with_handler("my_table", |request| -> http::Response<String> {
+            panic!("Unexpected request: {}", request.url().path())
+        });
+
+        let result = table.delete("id = 1").expected_version(6).await;
+        assert!(matches!(result, Err(Error::NotSupported { .. })));
+        let result = table
+            .update()
+            .column("a", "a + 1")
+            .expected_version(6)
+            .execute()
+            .await;
+        assert!(matches!(result, Err(Error::NotSupported { .. })));
+        let mut merge = table.merge_insert(&["a"]);
+        merge.when_not_matched_insert_all().expected_version(6);
+        let data = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)])),
+            vec![Arc::new(Int32Array::from(vec![1]))],
+        )
+        .unwrap();
+        let result = merge
+            .execute(Box::new(RecordBatchIterator::new(
+                [Ok(data.clone())],
+                data.schema(),
+            )))
+            .await;
+        assert!(matches!(result, Err(Error::NotSupported { .. })));
     }
 
     #[tokio::test]
@@ -1158,6 +1333,40 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_query_vector_index_name() {
+        let table = Table::new_with_handler("my_table", |request| {
+            assert_eq!(request.url().path(), "/v1/table/my_table/query/");
+
+            let body = request.body().unwrap().as_bytes().unwrap();
+            let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+            assert_eq!(body["index_name"], "my_hnsw_idx");
+            assert!(body.get("bypass_vector_index").is_none());
+
+            let data = RecordBatch::try_new(
+                Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)])),
+                vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+            )
+            .unwrap();
+            let response_body = write_ipc_file(&data);
+            http::Response::builder()
+                .status(200)
+                .header(CONTENT_TYPE, ARROW_FILE_CONTENT_TYPE)
+                .body(response_body)
+                .unwrap()
+        });
+
+        let _ = table
+            .query()
+            .nearest_to(vec![0.1, 0.2, 0.3])
+            .unwrap()
+            .bypass_vector_index()
+            .use_index("my_hnsw_idx")
+            .execute()
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_query_fts() {
         let table = Table::new_with_handler("my_table", |request| {
@@ -1254,6 +1463,31 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_create_index_with_name() {
+        let table = Table::new_with_handler("my_table", |request| {
+            assert_eq!(request.url().path(), "/v1/table/my_table/create_index/");
+            let body = request.body().unwrap().as_bytes().unwrap();
+            let body: serde_json::Value = serde_json::from_slice(body).unwrap();
+            let expected_body = serde_json::json!({
+                "column": "a",
+                "name": "my_hnsw_idx",
+                "index_type": "IVF_HNSW_SQ",
+                "metric_type": "l2",
+            });
+            assert_eq!(body, expected_body);
+
+            http::Response::builder().status(200).body("{}").unwrap()
+        });
+
+        table
+            .create_index(&["a"], Index::IvfHnswSq(Default::default()))
+            .name("my_hnsw_idx")
+            .execute()
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_list_indices() {
         let table = Table::new_with_handler("my_table", |request| {
@@ -1317,6 +1551,15 @@ mod tests {
         assert_eq!(indices, expected);
     }
 
+    #[tokio::test]
+    async fn test_stats() {
+        let table = Table::new_with_handler("my_table", |request| -> http::Response<String> {
+            panic!("Unexpected request: {}", request.url().path())
+        });
+        let result = table.stats().await;
+        assert!(matches!(result, Err(Error::NotSupported { .. })));
+    }
+
     #[tokio::test]
     async fn test_index_stats() {
         let table = Table::new_with_handler("my_table", |request| {
@@ -1361,4 +1604,18 @@ mod tests {
         let indices = table.index_stats("my_index").await.unwrap();
         assert!(indices.is_none());
     }
+
+    #[tokio::test]
+    async fn test_drop_index() {
+        let table = Table::new_with_handler("my_table", |request| {
+            assert_eq!(request.method(), "POST");
+            assert_eq!(
+                request.url().path(),
+                "/v1/table/my_table/index/my_index/drop/"
+            );
+
+            http::Response::builder().status(200).body("{}").unwrap()
+        });
+        table.drop_index("my_index").await.unwrap();
+    }
 }
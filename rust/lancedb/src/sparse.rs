@@ -0,0 +1,405 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Sparse vectors, such as those of learned sparse retrieval models like SPLADE
+//!
+//! A sparse vector column is a struct column with an `indices` field, a list of
+//! `UInt32`, and a `values` field, a list of floats of the same length.  See
+//! [`sparse_vector_type`].  Sparse vectors are searched with
+//! [`crate::query::Query::nearest_to_sparse`].
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use arrow::buffer::NullBuffer;
+use arrow_array::builder::{Float32Builder, ListBuilder, UInt32Builder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, UInt32Type};
+use arrow_array::{Array, ArrayRef, RecordBatch, StructArray};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Fields};
+use futures::TryStreamExt;
+use lance::dataset::{fragment::FileFragment, Dataset};
+use lance_table::format::Fragment;
+
+use crate::error::{Error, Result};
+
+/// The name of the field with the indices of the non-zero values
+pub const SPARSE_INDICES_FIELD: &str = "indices";
+/// The name of the field with the non-zero values
+pub const SPARSE_VALUES_FIELD: &str = "values";
+
+/// The data type of sparse vector columns
+pub fn sparse_vector_type() -> DataType {
+    DataType::Struct(Fields::from(vec![
+        Field::new_list(
+            SPARSE_INDICES_FIELD,
+            Field::new("item", DataType::UInt32, true),
+            true,
+        ),
+        Field::new_list(
+            SPARSE_VALUES_FIELD,
+            Field::new("item", DataType::Float32, true),
+            true,
+        ),
+    ]))
+}
+
+/// Whether a data type is a sparse vector type
+///
+/// This is a struct with list `indices` of integers and list `values` of
+/// floats.  The integer and float types may differ from
+/// [`sparse_vector_type`].
+pub fn is_sparse_vector_type(data_type: &DataType) -> bool {
+    let DataType::Struct(fields) = data_type else {
+        return false;
+    };
+    let item_type = |name: &str| {
+        fields
+            .iter()
+            .find(|f| f.name() == name)
+            .and_then(|f| match f.data_type() {
+                DataType::List(item) | DataType::LargeList(item) => Some(item.data_type().clone()),
+                _ => None,
+            })
+    };
+    matches!(item_type(SPARSE_INDICES_FIELD), Some(t) if t.is_integer())
+        && matches!(item_type(SPARSE_VALUES_FIELD), Some(t) if t.is_floating())
+}
+
+/// A sparse vector, the non-zero values of a vector and their indices
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SparseVector {
+    pub indices: Vec<u32>,
+    pub values: Vec<f32>,
+}
+
+impl SparseVector {
+    /// Create a sparse vector
+    ///
+    /// Returns an error if there are not as many indices as values.
+    pub fn try_new(indices: Vec<u32>, values: Vec<f32>) -> Result<Self> {
+        if indices.len() != values.len() {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "a sparse vector has {} indices but {} values",
+                    indices.len(),
+                    values.len()
+                ),
+            });
+        }
+        Ok(Self { indices, values })
+    }
+
+    /// The number of non-zero values
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Iterate over the indices and values
+    pub fn iter(&self) -> impl Iterator<Item = (u32, f32)> + '_ {
+        self.indices
+            .iter()
+            .copied()
+            .zip(self.values.iter().copied())
+    }
+
+    /// Read the sparse vector in a row of a sparse vector array
+    ///
+    /// Returns `None` if the row is null.
+    pub fn from_array(array: &dyn Array, row: usize) -> Result<Option<Self>> {
+        if !is_sparse_vector_type(array.data_type()) {
+            return Err(Error::InvalidInput {
+                message: format!("expected sparse vectors, got {}", array.data_type()),
+            });
+        }
+        if array.is_null(row) {
+            return Ok(None);
+        }
+        let array = array.as_struct();
+        let list_value = |name: &str, data_type: &DataType| -> Result<Option<ArrayRef>> {
+            let list = array.column_by_name(name).unwrap();
+            if list.is_null(row) {
+                return Ok(None);
+            }
+            let values = match list.data_type() {
+                DataType::List(_) => list.as_list::<i32>().value(row),
+                _ => list.as_list::<i64>().value(row),
+            };
+            Ok(Some(cast(&values, data_type)?))
+        };
+        let (Some(indices), Some(values)) = (
+            list_value(SPARSE_INDICES_FIELD, &DataType::UInt32)?,
+            list_value(SPARSE_VALUES_FIELD, &DataType::Float32)?,
+        ) else {
+            return Ok(None);
+        };
+        Self::try_new(
+            indices.as_primitive::<UInt32Type>().values().to_vec(),
+            values.as_primitive::<Float32Type>().values().to_vec(),
+        )
+        .map(Some)
+    }
+}
+
+impl From<Vec<(u32, f32)>> for SparseVector {
+    fn from(entries: Vec<(u32, f32)>) -> Self {
+        let (indices, values) = entries.into_iter().unzip();
+        Self { indices, values }
+    }
+}
+
+impl From<HashMap<u32, f32>> for SparseVector {
+    fn from(entries: HashMap<u32, f32>) -> Self {
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(index, _)| *index);
+        entries.into()
+    }
+}
+
+/// Create an array of [`sparse_vector_type`] from sparse vectors
+pub fn sparse_vectors_to_array(
+    vectors: impl IntoIterator<Item = Option<SparseVector>>,
+) -> Result<ArrayRef> {
+    let mut indices = ListBuilder::new(UInt32Builder::new());
+    let mut values = ListBuilder::new(Float32Builder::new());
+    let mut validity = Vec::new();
+    for vector in vectors {
+        validity.push(vector.is_some());
+        let vector = vector.unwrap_or_default();
+        indices.values().append_slice(&vector.indices);
+        indices.append(true);
+        values.values().append_slice(&vector.values);
+        values.append(true);
+    }
+    let DataType::Struct(fields) = sparse_vector_type() else {
+        unreachable!("sparse vectors are structs")
+    };
+    let nulls = NullBuffer::from(validity);
+    let nulls = (nulls.null_count() > 0).then_some(nulls);
+    Ok(Arc::new(StructArray::try_new(
+        fields,
+        vec![Arc::new(indices.finish()), Arc::new(values.finish())],
+        nulls,
+    )?))
+}
+
+/// A row and its score, ordered by score for a min-heap of the best rows
+#[derive(PartialEq)]
+struct Scored(f32, u64);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed, so the heap's top is the worst of the best rows
+        other.0.total_cmp(&self.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// The postings of the non-zero values of one fragment
+#[derive(Debug)]
+struct FragmentPostings {
+    /// The fragment as it was when it was scanned
+    fragment: Fragment,
+    postings: HashMap<u32, Vec<(u64, f32)>>,
+}
+
+impl FragmentPostings {
+    async fn build(fragment: &FileFragment, column: &str) -> Result<Self> {
+        let mut scanner = fragment.scan();
+        scanner.project(&[column])?;
+        scanner.with_row_id();
+        let mut stream = scanner.try_into_stream().await?;
+        let mut postings = Self {
+            fragment: fragment.metadata().clone(),
+            postings: HashMap::new(),
+        };
+        while let Some(batch) = stream.try_next().await? {
+            postings.add(&batch, column)?;
+        }
+        Ok(postings)
+    }
+
+    fn add(&mut self, batch: &RecordBatch, column: &str) -> Result<()> {
+        let vectors = batch.column_by_name(column).unwrap();
+        let row_ids = batch
+            .column_by_name("_rowid")
+            .unwrap()
+            .as_primitive::<arrow_array::types::UInt64Type>();
+        for row in 0..batch.num_rows() {
+            let Some(vector) = SparseVector::from_array(vectors.as_ref(), row)? else {
+                continue;
+            };
+            let row_id = row_ids.value(row);
+            for (index, value) in vector.iter() {
+                if value != 0.0 {
+                    self.postings
+                        .entry(index)
+                        .or_default()
+                        .push((row_id, value));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An inverted index of a sparse vector column
+///
+/// For each index of a non-zero value it lists the rows with a value there.
+/// The dot product of a query with every row is computed by only visiting the
+/// rows that share a non-zero index with the query.
+///
+/// The rows are listed per fragment, so that when the table changes only the
+/// fragments that were added or changed are scanned again.
+///
+/// This is not a persisted index but an in-memory cache of a full scan of the
+/// column, kept per table handle, see [`crate::query::Query::nearest_to_sparse`].
+#[derive(Debug, Default)]
+pub(crate) struct SparseIndex {
+    /// The version of the dataset the index is up to date with
+    pub(crate) version: u64,
+    fragments: HashMap<u64, Arc<FragmentPostings>>,
+}
+
+impl SparseIndex {
+    /// The index of the column at a version of the dataset
+    ///
+    /// Fragments that are unchanged since this index was built are reused.
+    /// Others, such as new fragments or fragments with new deletions, are
+    /// scanned.
+    pub(crate) async fn update(&self, dataset: &Dataset, column: &str) -> Result<Self> {
+        let mut fragments = HashMap::new();
+        for fragment in dataset.get_fragments() {
+            let metadata = fragment.metadata();
+            let postings = match self.fragments.get(&metadata.id) {
+                Some(postings) if &postings.fragment == metadata => postings.clone(),
+                _ => Arc::new(FragmentPostings::build(&fragment, column).await?),
+            };
+            fragments.insert(metadata.id, postings);
+        }
+        Ok(Self {
+            version: dataset.version().version,
+            fragments,
+        })
+    }
+
+    /// The dot product of `query` with every row that shares a non-zero index
+    /// with it
+    fn scores(&self, query: &SparseVector) -> HashMap<u64, f32> {
+        let mut scores = HashMap::<u64, f32>::new();
+        for fragment in self.fragments.values() {
+            for (index, weight) in query.iter() {
+                let Some(postings) = fragment.postings.get(&index) else {
+                    continue;
+                };
+                for (row_id, value) in postings {
+                    *scores.entry(*row_id).or_default() += weight * value;
+                }
+            }
+        }
+        scores
+    }
+
+    /// Find the `k` rows with the largest dot product with `query`
+    ///
+    /// Rows that share no non-zero index with the query are never returned.
+    /// The rows are returned best first.
+    pub(crate) fn search(&self, query: &SparseVector, k: usize) -> Vec<(u64, f32)> {
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        for (row_id, score) in self.scores(query) {
+            best.push(Scored(score, row_id));
+            if best.len() > k {
+                best.pop();
+            }
+        }
+        // The heap's order is reversed, so this is best first
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Scored(score, row_id)| (row_id, score))
+            .collect()
+    }
+
+    /// All rows that share a non-zero index with `query`, best first
+    pub(crate) fn rank(&self, query: &SparseVector) -> Vec<(u64, f32)> {
+        let mut ranked = self
+            .scores(query)
+            .into_iter()
+            .map(|(row_id, score)| Scored(score, row_id))
+            .collect::<Vec<_>>();
+        ranked.sort();
+        ranked
+            .into_iter()
+            .map(|Scored(score, row_id)| (row_id, score))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_vectors_roundtrip() {
+        let vectors = vec![
+            Some(SparseVector::try_new(vec![1, 5], vec![0.5, 2.0]).unwrap()),
+            None,
+            Some(vec![(3, 1.0)].into()),
+        ];
+        let array = sparse_vectors_to_array(vectors.clone()).unwrap();
+        assert_eq!(array.data_type(), &sparse_vector_type());
+        assert!(is_sparse_vector_type(array.data_type()));
+        for (row, vector) in vectors.into_iter().enumerate() {
+            assert_eq!(
+                SparseVector::from_array(array.as_ref(), row).unwrap(),
+                vector
+            );
+        }
+    }
+
+    #[test]
+    fn test_sparse_index_search() {
+        let mut index = SparseIndex::default();
+        let fragments = [
+            vec![(1, vec![(0, 1.0), (1, 2.0)]), (2, vec![(1, 1.0)])],
+            vec![(2, vec![(1 << 32, 5.0)])],
+        ];
+        for (id, postings) in fragments.into_iter().enumerate() {
+            index.fragments.insert(
+                id as u64,
+                Arc::new(FragmentPostings {
+                    fragment: Fragment::new(id as u64),
+                    postings: postings.into_iter().collect(),
+                }),
+            );
+        }
+        let query = SparseVector::from(vec![(1, 1.0), (2, 1.0)]);
+        // Scores: row 0 = 1, row 1 = 3, row 1 << 32 = 5
+        assert_eq!(index.search(&query, 2), vec![(1 << 32, 5.0), (1, 3.0)]);
+        assert_eq!(index.rank(&query), vec![(1 << 32, 5.0), (1, 3.0), (0, 1.0)]);
+        assert!(index.search(&vec![(7, 1.0)].into(), 10).is_empty());
+    }
+}
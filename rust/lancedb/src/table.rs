@@ -14,44 +14,56 @@
 
 //! LanceDB Table APIs
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use arrow::array::AsArray;
-use arrow::datatypes::Float32Type;
-use arrow_array::{RecordBatchIterator, RecordBatchReader};
-use arrow_schema::{Field, Schema, SchemaRef};
+use arrow::datatypes::{Float32Type, UInt64Type};
+use arrow_array::{Float32Array, RecordBatch, RecordBatchIterator, RecordBatchReader, UInt64Array};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
 use async_trait::async_trait;
+use datafusion_common::DataFusionError;
 use datafusion_physical_plan::display::DisplayableExecutionPlan;
-use datafusion_physical_plan::ExecutionPlan;
+use datafusion_physical_plan::memory::MemoryExec;
+use datafusion_physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion_physical_plan::{ExecutionPlan, SendableRecordBatchStream};
 use futures::{StreamExt, TryStreamExt};
 use lance::dataset::builder::DatasetBuilder;
 use lance::dataset::cleanup::RemovalStats;
 use lance::dataset::optimize::{compact_files, CompactionMetrics, IndexRemapperOptions};
 use lance::dataset::scanner::{DatasetRecordBatchStream, Scanner};
+use lance::dataset::transaction::Operation;
 pub use lance::dataset::ColumnAlteration;
 pub use lance::dataset::NewColumnTransform;
 pub use lance::dataset::ReadParams;
+pub use lance::dataset::{BatchUDF, UDFCheckpointStore};
 use lance::dataset::{
     Dataset, UpdateBuilder as LanceUpdateBuilder, WhenMatched, WriteMode, WriteParams,
 };
 use lance::dataset::{MergeInsertBuilder as LanceMergeInsertBuilder, WhenNotMatchedBySource};
-use lance::io::WrappingObjectStore;
+use lance::io::exec::knn::{new_knn_exec, PreFilterSource};
+use lance::io::{ObjectStoreParams, WrappingObjectStore};
 use lance_datafusion::exec::execute_plan;
+use lance_datafusion::planner::Planner;
 use lance_index::vector::hnsw::builder::HnswBuildParams;
 use lance_index::vector::ivf::IvfBuildParams;
 use lance_index::vector::pq::PQBuildParams;
 use lance_index::vector::sq::builder::SQBuildParams;
+use lance_index::vector::Query as LanceVectorQuery;
 use lance_index::DatasetIndexExt;
 use lance_index::IndexType;
-use lance_table::io::commit::ManifestNamingScheme;
-use log::info;
+use lance_linalg::distance::DistanceType as LanceDistanceType;
+use lance_table::io::commit::{CommitHandler, ManifestNamingScheme};
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 
 use crate::arrow::IntoArrow;
 use crate::connection::NoData;
-use crate::embeddings::{EmbeddingDefinition, EmbeddingRegistry, MaybeEmbedded, MemoryRegistry};
+use crate::embeddings::{
+    get_embedding_functions, EmbeddingDefinition, EmbeddingFunction, EmbeddingRegistry,
+    MaybeEmbedded, MemoryRegistry,
+};
 use crate::error::{Error, Result};
 use crate::index::scalar::FtsIndexBuilder;
 use crate::index::vector::{
@@ -65,19 +77,29 @@ use crate::index::{
 };
 use crate::index::{IndexConfig, IndexStatisticsImpl};
 use crate::query::{
-    IntoQueryVector, Query, QueryExecutionOptions, Select, VectorQuery, DEFAULT_TOP_K,
+    IntoQueryVector, Query, QueryExecutionOptions, Select, SparseVectorQuery, VectorQuery,
+    DEFAULT_TOP_K,
 };
+use crate::sparse::{is_sparse_vector_type, SparseIndex};
 use crate::utils::{
-    default_vector_column, supported_bitmap_data_type, supported_btree_data_type,
-    supported_fts_data_type, supported_label_list_data_type, supported_vector_data_type,
-    PatchReadParam, PatchWriteParam,
+    default_sparse_vector_column, default_vector_column, supported_bitmap_data_type,
+    supported_btree_data_type, supported_fts_data_type, supported_label_list_data_type,
+    supported_vector_data_type, PatchReadParam, PatchWriteParam,
 };
+use crate::DistanceType;
 
-use self::dataset::DatasetConsistencyWrapper;
+use self::bad_vectors::BadVectorReader;
+use self::dataset::{DatasetConsistencyWrapper, ExpectedVersionCommitHandler};
 use self::merge::MergeInsertBuilder;
+use self::transaction::{StagedTable, TransactionBuilder, TransactionOperation};
+use self::writer::TableWriter;
 
+pub mod auto_optimize;
+pub(crate) mod bad_vectors;
 pub(crate) mod dataset;
 pub mod merge;
+pub mod transaction;
+pub mod writer;
 
 pub use chrono::Duration;
 pub use lance::dataset::optimize::CompactionOptions;
@@ -235,12 +257,203 @@ pub struct OptimizeStats {
     pub prune: Option<RemovalStats>,
 }
 
+/// The outcome of a write operation (add, delete or merge insert)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WriteResult {
+    /// The version of the table created by the operation
+    pub version: u64,
+    /// The number of rows inserted into the table
+    pub num_inserted_rows: u64,
+    /// The number of existing rows that were updated
+    pub num_updated_rows: u64,
+    /// The number of rows removed from the table
+    pub num_deleted_rows: u64,
+    /// The number of new data fragments written by the operation
+    pub num_fragments_written: u64,
+    /// The total size of the data files written by the operation, in bytes
+    pub bytes_written: u64,
+    /// The number of invalid vectors found in the data, see
+    /// [`WriteOptions::on_bad_vectors`]
+    pub num_bad_vectors: u64,
+}
+
+impl WriteResult {
+    /// Describe the fragments added when a table moved from `old` to `new`
+    ///
+    /// If `old` is `None` every fragment of `new` is considered written. Inserted
+    /// rows are counted as the rows of the new fragments, so this is only accurate
+    /// for operations that do not rewrite existing rows.
+    async fn from_new_fragments(old: Option<&Dataset>, new: &Dataset) -> Result<Self> {
+        let old_ids = old
+            .map(|old| old.fragments().iter().map(|frag| frag.id).collect())
+            .unwrap_or_else(std::collections::HashSet::new);
+        let fragments = new.fragments();
+        let new_fragments = fragments
+            .iter()
+            .filter(|frag| !old_ids.contains(&frag.id))
+            .collect::<Vec<_>>();
+        Ok(Self {
+            version: new.version().version,
+            num_inserted_rows: new_fragments
+                .iter()
+                .map(|frag| frag.physical_rows.unwrap_or_default() as u64)
+                .sum(),
+            num_fragments_written: new_fragments.len() as u64,
+            bytes_written: data_files_bytes(new, new_fragments).await?,
+            ..Default::default()
+        })
+    }
+
+    /// The number of rows visible in `dataset`, per fragment id, taken from the
+    /// manifest (no data files are read)
+    fn live_rows(dataset: &Dataset) -> HashMap<u64, u64> {
+        dataset
+            .fragments()
+            .iter()
+            .map(|frag| (frag.id, transaction::fragment_live_rows(frag)))
+            .collect()
+    }
+}
+
+/// The total size of the data files of `fragments`, in bytes
+///
+/// File sizes are not recorded in the manifest, so they are read from the
+/// object store.
+pub(crate) async fn data_files_bytes(
+    dataset: &Dataset,
+    fragments: impl IntoIterator<Item = &lance_table::format::Fragment>,
+) -> Result<u64> {
+    let object_store = dataset.object_store();
+    let data_dir = dataset.data_dir();
+    let paths = fragments
+        .into_iter()
+        .flat_map(|frag| frag.files.iter())
+        .map(|file| data_dir.child(file.path.as_str()))
+        .collect::<Vec<_>>();
+    futures::stream::iter(paths)
+        .map(|path| async move { Result::Ok(object_store.inner.head(&path).await?.size as u64) })
+        .buffered(16)
+        .try_fold(0, |total, size| async move { Ok(total + size) })
+        .await
+}
+
+/// Statistics about a table, see [`Table::stats`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TableStatistics {
+    /// The number of rows in the table
+    pub num_rows: u64,
+    /// The number of rows that have been deleted but are still stored in the
+    /// data files, until the table is compacted
+    pub num_deleted_rows: u64,
+    /// The number of versions of the table that have not been pruned
+    pub num_versions: u64,
+    /// The total size of the data files of the current version, in bytes
+    pub total_bytes: u64,
+    /// Statistics about the fragments of the table
+    pub fragment_stats: FragmentStatistics,
+    /// The size on disk of each group of columns of the table
+    ///
+    /// Lance stores columns that are written together in the same data files,
+    /// so sizes are reported per group of columns.  A column that was added on
+    /// its own (e.g. with [`Table::add_columns`]) is a group by itself.
+    pub column_group_stats: Vec<ColumnGroupStatistics>,
+    /// How much of the table each index covers
+    pub index_stats: Vec<IndexCoverage>,
+}
+
+/// Statistics about the fragments of a table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FragmentStatistics {
+    /// The number of fragments in the table
+    pub num_fragments: u64,
+    /// The number of fragments, by number of rows (deleted rows included)
+    pub size_histogram: Vec<FragmentSizeBucket>,
+}
+
+/// A bucket of [`FragmentStatistics::size_histogram`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FragmentSizeBucket {
+    /// The fragments in this bucket have fewer than this many rows.  The last
+    /// bucket has no upper bound.
+    pub max_rows: Option<u64>,
+    /// The number of fragments in this bucket
+    pub num_fragments: u64,
+}
+
+/// The size on disk of a group of columns that are stored in the same files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColumnGroupStatistics {
+    /// The (top level) columns in the group
+    pub columns: Vec<String>,
+    /// The total size of the data files holding these columns, in bytes
+    pub bytes_on_disk: u64,
+}
+
+/// How much of a table an index covers
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexCoverage {
+    /// The name of the index
+    pub name: String,
+    /// The columns in the index
+    pub columns: Vec<String>,
+    /// The number of rows that are covered by the index
+    pub num_indexed_rows: u64,
+    /// The number of rows that have not been added to the index yet
+    pub num_unindexed_rows: u64,
+}
+
+/// The number of candidate rows of a filtered sparse vector search that are
+/// read at once to check the filter
+const SPARSE_FILTER_CHUNK_SIZE: usize = 1024;
+
+/// The upper bounds of the buckets of [`FragmentStatistics::size_histogram`]
+const FRAGMENT_SIZE_BUCKETS: [u64; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+impl FragmentStatistics {
+    fn from_row_counts(row_counts: impl IntoIterator<Item = u64>) -> Self {
+        let mut size_histogram = FRAGMENT_SIZE_BUCKETS
+            .iter()
+            .map(|max_rows| Some(*max_rows))
+            .chain(std::iter::once(None))
+            .map(|max_rows| FragmentSizeBucket {
+                max_rows,
+                num_fragments: 0,
+            })
+            .collect::<Vec<_>>();
+        let mut num_fragments = 0;
+        for rows in row_counts {
+            num_fragments += 1;
+            let bucket = FRAGMENT_SIZE_BUCKETS
+                .iter()
+                .position(|max_rows| rows < *max_rows)
+                .unwrap_or(FRAGMENT_SIZE_BUCKETS.len());
+            size_histogram[bucket].num_fragments += 1;
+        }
+        Self {
+            num_fragments,
+            size_histogram,
+        }
+    }
+}
+
 /// Options to use when writing data
 #[derive(Clone, Debug, Default)]
 pub struct WriteOptions {
-    // Coming soon: https://github.com/lancedb/lancedb/issues/992
-    // /// What behavior to take if the data contains invalid vectors
-    // pub on_bad_vectors: BadVectorHandling,
+    /// What behavior to take if the data contains invalid vectors.  If `None`
+    /// then the vectors are not checked.
+    ///
+    /// See [`BadVectorHandling`] for what counts as an invalid vector.
+    pub on_bad_vectors: Option<BadVectorHandling>,
+    /// The distance type the vectors will be searched with, used to decide
+    /// whether all-zero vectors are invalid.  If `None` then the distance type
+    /// of the column's vector index is used, if it has one.
+    pub vector_distance_type: Option<DistanceType>,
     /// Advanced parameters that can be used to customize table creation
     ///
     /// Overlapping `OpenTableBuilder` options (e.g. [AddDataBuilder::mode]) will take
@@ -248,6 +461,120 @@ pub struct WriteOptions {
     pub lance_write_params: Option<WriteParams>,
 }
 
+impl WriteOptions {
+    /// Check the vectors in the data and handle invalid ones with `handling`
+    pub fn on_bad_vectors(mut self, handling: BadVectorHandling) -> Self {
+        self.on_bad_vectors = Some(handling);
+        self
+    }
+
+    /// The distance type the vectors will be searched with
+    pub fn vector_distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.vector_distance_type = Some(distance_type);
+        self
+    }
+}
+
+/// What to do with invalid vectors in data written to a table
+///
+/// A vector, a value of a fixed size list column of floats, is invalid if:
+///
+/// * it contains NaN or infinite values
+/// * it has a different number of values than the column's dimension, which
+///   can only happen when the data has variable length list columns
+/// * it is all zeros and the column is searched by cosine distance, for which
+///   zero vectors have no direction
+///
+/// The number of invalid vectors found is reported in
+/// [`WriteResult::num_bad_vectors`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BadVectorHandling {
+    /// Fail the write
+    Error,
+    /// Drop the rows with invalid vectors
+    Drop,
+    /// Replace the invalid vectors with a vector with this value in every
+    /// position
+    Fill(f32),
+    /// Replace the invalid vectors with null
+    Null,
+}
+
+/// How to retry a write that fails because another writer committed a
+/// conflicting change to the table first.
+///
+/// Each retry re-reads the latest version of the table and applies the
+/// operation again on top of it.  This is only done for operations that are
+/// safe to repeat: appending data ([`AddDataBuilder::conflict_retry`]),
+/// deleting by predicate ([`DeleteBuilder::conflict_retry`]) and merge inserts
+/// ([`MergeInsertBuilder::conflict_retry`]).  Overwrites are never retried,
+/// since repeating one could discard the conflicting write.  The data for an
+/// append or merge insert is buffered in memory so that it can be written
+/// again.
+///
+/// Writes made with an expected version are never retried, since the conflict
+/// is the answer the caller asked for.  Conflicts on remote tables are
+/// resolved by the server.
+#[derive(Default, Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of times to attempt the write, including the first
+    /// attempt.
+    ///
+    /// The default is 5 attempts.
+    pub max_attempts: Option<u8>,
+    /// The exponential backoff factor to use between attempts.
+    ///
+    /// Before each retry, the writer will wait for the amount of seconds:
+    ///
+    /// ```text
+    /// {backoff factor} * (2 ** ({number of previous retries}))
+    /// ```
+    ///
+    /// The default is 0.1. So the first retry will wait 0.1 seconds, the second
+    /// retry will wait 0.2 seconds, the third retry will wait 0.4 seconds, etc.
+    pub backoff_factor: Option<f32>,
+    /// The backoff jitter factor to use between attempts.
+    ///
+    /// The backoff jitter is a random value between 0 and the jitter factor in
+    /// seconds.  It keeps writers that conflicted with each other from retrying
+    /// in lockstep.
+    ///
+    /// The default is 0.1.
+    pub backoff_jitter: Option<f32>,
+}
+
+impl RetryPolicy {
+    /// Run `operation` until it succeeds, fails with something other than a
+    /// commit conflict, or runs out of attempts
+    pub(crate) async fn run<T, Fut>(&self, mut operation: impl FnMut() -> Fut) -> Result<T>
+    where
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let max_attempts = self.max_attempts.unwrap_or(5).max(1);
+        let backoff_factor = self.backoff_factor.unwrap_or(0.1);
+        let backoff_jitter = self.backoff_jitter.unwrap_or(0.1);
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Err(Error::Lance {
+                    source: lance::Error::CommitConflict { .. },
+                }) if attempt < max_attempts => {
+                    let backoff = backoff_factor * 2.0f32.powi(attempt as i32 - 1);
+                    let jitter = rand::random::<f32>() * backoff_jitter;
+                    let sleep_time = std::time::Duration::from_secs_f32(backoff + jitter);
+                    debug!(
+                        "Commit conflict on attempt {}/{}, retrying in {:?}",
+                        attempt, max_attempts, sleep_time
+                    );
+                    tokio::time::sleep(sleep_time).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub enum AddDataMode {
     /// Rows will be appended to the table (the default)
@@ -259,11 +586,14 @@ pub enum AddDataMode {
 
 /// A builder for configuring a [`crate::connection::Connection::create_table`] or [`Table::add`]
 /// operation
+#[derive(Clone)]
 pub struct AddDataBuilder<T: IntoArrow> {
     parent: Arc<dyn TableInternal>,
     pub(crate) data: T,
     pub(crate) mode: AddDataMode,
     pub(crate) write_options: WriteOptions,
+    pub(crate) expected_version: Option<u64>,
+    pub(crate) conflict_retry: Option<RetryPolicy>,
     embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
 }
 
@@ -273,6 +603,8 @@ impl<T: IntoArrow> std::fmt::Debug for AddDataBuilder<T> {
             .field("parent", &self.parent)
             .field("mode", &self.mode)
             .field("write_options", &self.write_options)
+            .field("expected_version", &self.expected_version)
+            .field("conflict_retry", &self.conflict_retry)
             .finish()
     }
 }
@@ -288,7 +620,42 @@ impl<T: IntoArrow> AddDataBuilder<T> {
         self
     }
 
-    pub async fn execute(self) -> Result<()> {
+    /// Only write the data if the table is still at the given version
+    ///
+    /// If another writer has committed a new version in the meantime the
+    /// operation fails with [`Error::CommitConflict`] and nothing is written.
+    /// This can be used to implement read-modify-write workflows that are
+    /// safe across processes.
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    pub fn expected_version(mut self, version: u64) -> Self {
+        self.expected_version = Some(version);
+        self
+    }
+
+    /// Retry the append according to `policy` if it loses a commit race
+    ///
+    /// Only appends are retried, an overwrite (set with [`Self::mode`] or the
+    /// Lance write parameters) fails on the first conflict.
+    pub fn conflict_retry(mut self, policy: RetryPolicy) -> Self {
+        self.conflict_retry = Some(policy);
+        self
+    }
+
+    /// Whether the write can be retried after a conflict: an append without
+    /// an expected version
+    fn is_retryable(&self) -> bool {
+        let append = matches!(self.mode, AddDataMode::Append)
+            && self
+                .write_options
+                .lance_write_params
+                .as_ref()
+                .map_or(true, |params| matches!(params.mode, WriteMode::Append));
+        append && self.expected_version.is_none()
+    }
+
+    /// Write the data to the table, returning a summary of what was written
+    pub async fn execute(self) -> Result<WriteResult> {
         let parent = self.parent.clone();
         let data = self.data.into_arrow()?;
         let without_data = AddDataBuilder::<NoData> {
@@ -296,9 +663,27 @@ impl<T: IntoArrow> AddDataBuilder<T> {
             mode: self.mode,
             parent: self.parent,
             write_options: self.write_options,
+            expected_version: self.expected_version,
+            conflict_retry: self.conflict_retry,
             embedding_registry: self.embedding_registry,
         };
-        parent.add(without_data, data).await
+        match without_data.conflict_retry.clone() {
+            Some(policy) if without_data.is_retryable() => {
+                // Each attempt consumes the data so it is buffered up front
+                let schema = data.schema();
+                let batches = data.collect::<std::result::Result<Vec<_>, _>>()?;
+                policy
+                    .run(|| {
+                        let data = Box::new(RecordBatchIterator::new(
+                            batches.clone().into_iter().map(Ok),
+                            schema.clone(),
+                        ));
+                        parent.add(without_data.clone(), data)
+                    })
+                    .await
+            }
+            _ => parent.add(without_data, data).await,
+        }
     }
 }
 
@@ -308,14 +693,21 @@ pub struct UpdateBuilder {
     parent: Arc<dyn TableInternal>,
     pub(crate) filter: Option<String>,
     pub(crate) columns: Vec<(String, String)>,
+    pub(crate) expected_version: Option<u64>,
+    pub(crate) embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
 }
 
 impl UpdateBuilder {
-    fn new(parent: Arc<dyn TableInternal>) -> Self {
+    fn new(
+        parent: Arc<dyn TableInternal>,
+        embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    ) -> Self {
         Self {
             parent,
             filter: None,
             columns: Vec::new(),
+            expected_version: None,
+            embedding_registry,
         }
     }
 
@@ -355,6 +747,17 @@ impl UpdateBuilder {
         self
     }
 
+    /// Only apply the update if the table is still at the given version
+    ///
+    /// If another writer has committed a new version in the meantime the
+    /// update fails with [`Error::CommitConflict`] and no rows are changed.
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    pub fn expected_version(mut self, version: u64) -> Self {
+        self.expected_version = Some(version);
+        self
+    }
+
     /// Executes the update operation.
     /// Returns the number of rows that were updated.
     pub async fn execute(self) -> Result<u64> {
@@ -368,148 +771,710 @@ impl UpdateBuilder {
     }
 }
 
-#[async_trait]
-pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Sync {
-    #[allow(dead_code)]
-    fn as_any(&self) -> &dyn std::any::Any;
-    /// Cast as [`NativeTable`], or return None it if is not a [`NativeTable`].
-    fn as_native(&self) -> Option<&NativeTable>;
-    /// Get the name of the table.
-    fn name(&self) -> &str;
-    /// Get the arrow [Schema] of the table.
-    async fn schema(&self) -> Result<SchemaRef>;
-    /// Count the number of rows in this table.
-    async fn count_rows(&self, filter: Option<String>) -> Result<usize>;
-    async fn create_plan(
-        &self,
-        query: &VectorQuery,
-        options: QueryExecutionOptions,
-    ) -> Result<Arc<dyn ExecutionPlan>>;
-    async fn plain_query(
-        &self,
-        query: &Query,
-        options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream>;
-    async fn explain_plan(&self, query: &VectorQuery, verbose: bool) -> Result<String> {
-        let plan = self.create_plan(query, Default::default()).await?;
-        let display = DisplayableExecutionPlan::new(plan.as_ref());
+/// A builder for configuring a [`Table::delete`] operation
+///
+/// The builder can be awaited directly, which is the same as calling
+/// [`Self::execute`].
+#[derive(Debug, Clone)]
+pub struct DeleteBuilder {
+    parent: Arc<dyn TableInternal>,
+    pub(crate) predicate: String,
+    pub(crate) expected_version: Option<u64>,
+    pub(crate) conflict_retry: Option<RetryPolicy>,
+}
 
-        Ok(format!("{}", display.indent(verbose)))
+impl DeleteBuilder {
+    fn new(parent: Arc<dyn TableInternal>, predicate: String) -> Self {
+        Self {
+            parent,
+            predicate,
+            expected_version: None,
+            conflict_retry: None,
+        }
     }
-    async fn add(
-        &self,
-        add: AddDataBuilder<NoData>,
-        data: Box<dyn arrow_array::RecordBatchReader + Send>,
-    ) -> Result<()>;
-    async fn delete(&self, predicate: &str) -> Result<()>;
-    async fn update(&self, update: UpdateBuilder) -> Result<u64>;
-    async fn create_index(&self, index: IndexBuilder) -> Result<()>;
-    async fn list_indices(&self) -> Result<Vec<IndexConfig>>;
-    async fn index_stats(&self, index_name: &str) -> Result<Option<IndexStatistics>>;
-    async fn merge_insert(
-        &self,
-        params: MergeInsertBuilder,
-        new_data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()>;
-    async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats>;
-    async fn add_columns(
-        &self,
-        transforms: NewColumnTransform,
-        read_columns: Option<Vec<String>>,
-    ) -> Result<()>;
-    async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()>;
-    async fn drop_columns(&self, columns: &[&str]) -> Result<()>;
-    async fn version(&self) -> Result<u64>;
-    async fn checkout(&self, version: u64) -> Result<()>;
-    async fn checkout_latest(&self) -> Result<()>;
-    async fn restore(&self) -> Result<()>;
-    async fn table_definition(&self) -> Result<TableDefinition>;
-    fn dataset_uri(&self) -> &str;
-}
 
-/// A Table is a collection of strong typed Rows.
-///
-/// The type of the each row is defined in Apache Arrow [Schema].
-#[derive(Clone)]
-pub struct Table {
-    inner: Arc<dyn TableInternal>,
-    embedding_registry: Arc<dyn EmbeddingRegistry>,
-}
+    /// Only delete the rows if the table is still at the given version
+    ///
+    /// If another writer has committed a new version in the meantime the
+    /// delete fails with [`Error::CommitConflict`] and no rows are deleted.
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use lancedb::{Error, Table};
+    /// # async fn doctest_helper(tbl: Table) -> lancedb::Result<()> {
+    /// let version = tbl.version().await?;
+    /// // ... decide what to delete based on the contents at `version` ...
+    /// match tbl.delete("id > 5").expected_version(version).await {
+    ///     Ok(_) => {}
+    ///     Err(Error::CommitConflict { actual, .. }) => {
+    ///         // Someone else wrote version `actual` first, re-read and try again
+    ///     }
+    ///     Err(err) => return Err(err),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn expected_version(mut self, version: u64) -> Self {
+        self.expected_version = Some(version);
+        self
+    }
 
-#[cfg(all(test, feature = "remote"))]
-mod test_utils {
-    use super::*;
+    /// Retry the delete according to `policy` if it loses a commit race
+    ///
+    /// A delete with an expected version is never retried.
+    pub fn conflict_retry(mut self, policy: RetryPolicy) -> Self {
+        self.conflict_retry = Some(policy);
+        self
+    }
 
-    impl Table {
-        pub fn new_with_handler<T>(
-            name: impl Into<String>,
-            handler: impl Fn(reqwest::Request) -> http::Response<T> + Clone + Send + Sync + 'static,
-        ) -> Self
-        where
-            T: Into<reqwest::Body>,
-        {
-            let inner = Arc::new(crate::remote::table::RemoteTable::new_mock(
-                name.into(),
-                handler,
-            ));
-            Self {
-                inner,
-                // Registry is unused.
-                embedding_registry: Arc::new(MemoryRegistry::new()),
+    /// Delete the rows, returning a summary of what was deleted
+    pub async fn execute(self) -> Result<WriteResult> {
+        match &self.conflict_retry {
+            Some(policy) if self.expected_version.is_none() => {
+                policy
+                    .run(|| self.parent.delete(&self.predicate, None))
+                    .await
+            }
+            _ => {
+                self.parent
+                    .delete(&self.predicate, self.expected_version)
+                    .await
             }
         }
     }
 }
 
-impl std::fmt::Display for Table {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.inner)
+impl std::future::IntoFuture for DeleteBuilder {
+    type Output = Result<WriteResult>;
+    type IntoFuture = futures::future::BoxFuture<'static, Result<WriteResult>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.execute())
     }
 }
 
-impl Table {
-    pub(crate) fn new(inner: Arc<dyn TableInternal>) -> Self {
-        Self {
-            inner,
-            embedding_registry: Arc::new(MemoryRegistry::new()),
-        }
+/// A builder for configuring an [`Table::add_embedding_column`] operation
+pub struct AddEmbeddingColumnBuilder {
+    parent: Arc<dyn TableInternal>,
+    embedding_registry: Arc<dyn EmbeddingRegistry>,
+    definition: EmbeddingDefinition,
+    checkpoint: Option<Arc<dyn UDFCheckpointStore>>,
+}
+
+impl std::fmt::Debug for AddEmbeddingColumnBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AddEmbeddingColumnBuilder")
+            .field("parent", &self.parent)
+            .field("definition", &self.definition)
+            .finish()
     }
+}
 
-    pub(crate) fn new_with_embedding_registry(
-        inner: Arc<dyn TableInternal>,
+impl AddEmbeddingColumnBuilder {
+    fn new(
+        parent: Arc<dyn TableInternal>,
         embedding_registry: Arc<dyn EmbeddingRegistry>,
+        definition: EmbeddingDefinition,
     ) -> Self {
         Self {
-            inner,
+            parent,
             embedding_registry,
+            definition,
+            checkpoint: None,
         }
     }
 
-    /// Cast as [`NativeTable`], or return None it if is not a [`NativeTable`].
+    /// Save the embeddings of each batch to `checkpoint` as they are computed
     ///
-    /// Warning: This function will be removed soon (features exclusive to NativeTable
-    ///          will be added to Table)
-    pub fn as_native(&self) -> Option<&NativeTable> {
-        self.inner.as_native()
+    /// Computing embeddings for a large table can take a long time.  If the
+    /// operation fails part way through then running it again with the same
+    /// checkpoint store skips the batches that were already embedded.
+    pub fn checkpoint(mut self, checkpoint: Arc<dyn UDFCheckpointStore>) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
     }
 
-    /// Get the name of the table.
-    pub fn name(&self) -> &str {
-        self.inner.name()
-    }
+    /// Compute the embeddings and add the column to the table
+    pub async fn execute(self) -> Result<()> {
+        let (definition, function) =
+            resolve_embedding_function(self.embedding_registry.as_ref(), self.definition)?;
+        let dest_column = definition.dest_column_name();
+
+        let table_definition = self.parent.table_definition().await?;
+        for column_definition in &table_definition.column_definitions {
+            if let ColumnKind::Embedding(existing) = &column_definition.kind {
+                if existing.dest_column_name() == dest_column {
+                    return Err(Error::InvalidInput {
+                        message: format!("the embedding column '{}' already exists", dest_column),
+                    });
+                }
+            }
+        }
 
-    /// Get the arrow [Schema] of the table.
-    pub async fn schema(&self) -> Result<SchemaRef> {
-        self.inner.schema().await
-    }
+        compute_embedding_column(
+            self.parent.as_ref(),
+            &table_definition,
+            &definition,
+            function,
+            self.checkpoint,
+        )
+        .await?;
 
-    /// Count the number of rows in this dataset.
-    ///
-    /// # Arguments
-    ///
-    /// * `filter` if present, only count rows matching the filter
-    pub async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
-        self.inner.count_rows(filter).await
+        update_column_definitions(self.parent.as_ref(), |column| {
+            (column == dest_column).then(|| ColumnKind::Embedding(definition.clone()))
+        })
+        .await
+    }
+}
+
+/// Get the embedding function of a definition from the registry
+///
+/// Returns the definition, with the function's configuration filled in, and
+/// the function, wrapped to apply the definition's options.
+fn resolve_embedding_function(
+    embedding_registry: &dyn EmbeddingRegistry,
+    definition: EmbeddingDefinition,
+) -> Result<(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)> {
+    let function = embedding_registry
+        .get_or_create(&definition)?
+        .ok_or_else(|| Error::EmbeddingFunctionNotFound {
+            name: definition.embedding_name.clone(),
+            reason: "No embedding function was found with that name within the registry."
+                .to_string(),
+        })?;
+    let definition = definition.with_config_from(function.as_ref());
+    let function = definition.wrap_function(function);
+    Ok((definition, function))
+}
+
+/// Field metadata key recording the embedding definition a column was computed for
+const EMBEDDING_FIELD_METADATA_KEY: &str = "lancedb::embedding";
+
+/// Compute the embeddings of every row into the definition's destination column
+///
+/// The new column records the definition in its field metadata.  If the
+/// column exists and records this definition then a previous attempt added it
+/// but failed to record the column definition, so nothing is done.  Any other
+/// existing column is an error.
+async fn compute_embedding_column(
+    parent: &dyn TableInternal,
+    table_definition: &TableDefinition,
+    definition: &EmbeddingDefinition,
+    function: Arc<dyn EmbeddingFunction>,
+    checkpoint: Option<Arc<dyn UDFCheckpointStore>>,
+) -> Result<()> {
+    let dest_column = definition.dest_column_name();
+    if table_definition
+        .schema
+        .field_with_name(&definition.source_column)
+        .is_err()
+    {
+        return Err(Error::InvalidInput {
+            message: format!(
+                "the source column '{}' does not exist",
+                definition.source_column
+            ),
+        });
+    }
+    if let Ok(field) = table_definition.schema.field_with_name(&dest_column) {
+        let computed_for = field
+            .metadata()
+            .get(EMBEDDING_FIELD_METADATA_KEY)
+            .and_then(|value| serde_json::from_str::<EmbeddingDefinition>(value).ok());
+        if computed_for.as_ref() == Some(definition) {
+            return Ok(());
+        }
+        return Err(Error::InvalidInput {
+            message: format!("the column '{}' already exists", dest_column),
+        });
+    }
+
+    let output_schema = Arc::new(Schema::new(vec![Field::new(
+        &dest_column,
+        function.dest_type()?.into_owned(),
+        true,
+    )
+    .with_metadata(HashMap::from([(
+        EMBEDDING_FIELD_METADATA_KEY.to_string(),
+        serde_json::to_string(definition).map_err(|e| Error::Runtime {
+            message: format!("Failed to serialize embedding definition: {}", e),
+        })?,
+    )]))]));
+    let mapper_schema = output_schema.clone();
+    let mapper = move |batch: &RecordBatch| -> lance::Result<RecordBatch> {
+        let embeddings = function
+            .compute_source_embeddings(batch.column(0).clone())
+            .map_err(|e| ArrowError::ComputeError(format!("Error computing embedding: {}", e)))?;
+        // Functions may differ from their declared type in nullability
+        let embeddings = arrow_cast::cast(&embeddings, mapper_schema.field(0).data_type())?;
+        Ok(RecordBatch::try_new(
+            mapper_schema.clone(),
+            vec![embeddings],
+        )?)
+    };
+    let transform = NewColumnTransform::BatchUDF(BatchUDF {
+        mapper: Box::new(mapper),
+        output_schema,
+        result_checkpoint: checkpoint,
+    });
+    parent
+        .add_columns(transform, Some(vec![definition.source_column.clone()]))
+        .await
+}
+
+/// Store new column definitions in the table schema
+///
+/// `kind` gives the new kind of a column, or `None` to keep the current one.
+/// All of the changes are made in a single commit.
+async fn update_column_definitions(
+    parent: &dyn TableInternal,
+    kind: impl Fn(&str) -> Option<ColumnKind>,
+) -> Result<()> {
+    let table_definition = parent.table_definition().await?;
+    let column_definitions = table_definition
+        .schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match kind(field.name()) {
+            Some(kind) => ColumnDefinition { kind },
+            None => table_definition
+                .column_definitions
+                .get(i)
+                .cloned()
+                .unwrap_or(ColumnDefinition {
+                    kind: ColumnKind::Physical,
+                }),
+        })
+        .collect::<Vec<_>>();
+    let column_definitions =
+        serde_json::to_string(&column_definitions).map_err(|e| Error::Runtime {
+            message: format!("Failed to serialize column definitions: {}", e),
+        })?;
+    parent
+        .replace_schema_metadata(vec![(
+            "lancedb::column_definitions".to_string(),
+            column_definitions,
+        )])
+        .await
+}
+
+/// A builder for configuring a [`Table::reembed`] operation
+pub struct ReembedBuilder {
+    parent: Arc<dyn TableInternal>,
+    embedding_registry: Arc<dyn EmbeddingRegistry>,
+    column: String,
+    definition: EmbeddingDefinition,
+    checkpoint: Option<Arc<dyn UDFCheckpointStore>>,
+    index: Option<Index>,
+    skip_index: bool,
+}
+
+impl std::fmt::Debug for ReembedBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReembedBuilder")
+            .field("parent", &self.parent)
+            .field("column", &self.column)
+            .field("definition", &self.definition)
+            .finish()
+    }
+}
+
+impl ReembedBuilder {
+    fn new(
+        parent: Arc<dyn TableInternal>,
+        embedding_registry: Arc<dyn EmbeddingRegistry>,
+        column: String,
+        definition: EmbeddingDefinition,
+    ) -> Self {
+        Self {
+            parent,
+            embedding_registry,
+            column,
+            definition,
+            checkpoint: None,
+            index: None,
+            skip_index: false,
+        }
+    }
+
+    /// Save the embeddings of each batch to `checkpoint` as they are computed
+    ///
+    /// See [`AddEmbeddingColumnBuilder::checkpoint`].
+    pub fn checkpoint(mut self, checkpoint: Arc<dyn UDFCheckpointStore>) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// The index to build on the new column before switching to it
+    ///
+    /// By default an [`Index::Auto`] index is built if the old column has an
+    /// index, and none otherwise.
+    pub fn index(mut self, index: Index) -> Self {
+        self.index = Some(index);
+        self.skip_index = false;
+        self
+    }
+
+    /// Switch to the new column without building an index on it
+    pub fn skip_index(mut self) -> Self {
+        self.index = None;
+        self.skip_index = true;
+        self
+    }
+
+    /// Compute the new embeddings, index them and switch to them
+    pub async fn execute(self) -> Result<()> {
+        let table_definition = self.parent.table_definition().await?;
+        let old_definition = table_definition
+            .column_definitions
+            .iter()
+            .find_map(|cd| match &cd.kind {
+                ColumnKind::Embedding(definition)
+                    if definition.dest_column_name() == self.column =>
+                {
+                    Some(definition.clone())
+                }
+                _ => None,
+            })
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!("the column '{}' is not an embedding column", self.column),
+            })?;
+        let (definition, function) =
+            resolve_embedding_function(self.embedding_registry.as_ref(), self.definition)?;
+        let dest_column = definition.dest_column_name();
+        if dest_column == self.column {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "the new embeddings must be written to a different column than '{}'",
+                    self.column
+                ),
+            });
+        }
+        if let Some(existing) = &old_definition.superseded_by {
+            if existing != &dest_column {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the column '{}' has already been replaced by '{}'",
+                        self.column, existing
+                    ),
+                });
+            }
+        }
+        // Unless a previous attempt already started on it, the new column must
+        // not be an embedding column yet
+        let existing = table_definition
+            .column_definitions
+            .iter()
+            .find_map(|cd| match &cd.kind {
+                ColumnKind::Embedding(existing) if existing.dest_column_name() == dest_column => {
+                    Some(existing)
+                }
+                _ => None,
+            });
+        let started = match existing {
+            Some(existing) if existing.pending || old_definition.superseded_by.is_some() => true,
+            Some(_) => {
+                return Err(Error::InvalidInput {
+                    message: format!("the embedding column '{}' already exists", dest_column),
+                })
+            }
+            None => false,
+        };
+
+        // Each step is skipped if a previous attempt completed it
+        if !started {
+            compute_embedding_column(
+                self.parent.as_ref(),
+                &table_definition,
+                &definition,
+                function.clone(),
+                self.checkpoint,
+            )
+            .await?;
+
+            // From here on rows that are written get the new embeddings too
+            let pending = EmbeddingDefinition {
+                pending: true,
+                ..definition.clone()
+            };
+            update_column_definitions(self.parent.as_ref(), |column| {
+                (column == dest_column).then(|| ColumnKind::Embedding(pending.clone()))
+            })
+            .await?;
+        }
+
+        let indices = self.parent.list_indices().await?;
+        let is_indexed = |column: &str| {
+            indices
+                .iter()
+                .any(|index| index.columns.len() == 1 && index.columns[0] == column)
+        };
+        let index = match self.index {
+            Some(index) => Some(index),
+            None if !self.skip_index && is_indexed(&self.column) => Some(Index::Auto),
+            None => None,
+        };
+        if let Some(index) = index {
+            if !is_indexed(&dest_column) {
+                IndexBuilder::new(self.parent.clone(), vec![dest_column.clone()], index)
+                    .execute()
+                    .await?;
+            }
+        }
+
+        // Rows written after the column was added but before it was recorded
+        // were written without the new embeddings, so they are computed now
+        self.parent
+            .recompute_embedding(
+                &format!(
+                    "`{}` IS NULL AND `{}` IS NOT NULL",
+                    dest_column, definition.source_column
+                ),
+                definition.clone(),
+                function,
+            )
+            .await?;
+
+        let old_definition = EmbeddingDefinition {
+            superseded_by: Some(dest_column.clone()),
+            ..old_definition
+        };
+        update_column_definitions(self.parent.as_ref(), |column| {
+            if column == dest_column {
+                Some(ColumnKind::Embedding(definition.clone()))
+            } else if column == self.column {
+                Some(ColumnKind::Embedding(old_definition.clone()))
+            } else {
+                None
+            }
+        })
+        .await
+    }
+}
+
+#[async_trait]
+pub(crate) trait TableInternal: std::fmt::Display + std::fmt::Debug + Send + Sync {
+    #[allow(dead_code)]
+    fn as_any(&self) -> &dyn std::any::Any;
+    /// Cast as [`NativeTable`], or return None it if is not a [`NativeTable`].
+    fn as_native(&self) -> Option<&NativeTable>;
+    /// Get the name of the table.
+    fn name(&self) -> &str;
+    /// Get the arrow [Schema] of the table.
+    async fn schema(&self) -> Result<SchemaRef>;
+    /// Count the number of rows in this table.
+    async fn count_rows(&self, filter: Option<String>) -> Result<usize>;
+    async fn create_plan(
+        &self,
+        query: &VectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>>;
+    async fn plain_query(
+        &self,
+        query: &Query,
+        options: QueryExecutionOptions,
+    ) -> Result<DatasetRecordBatchStream>;
+    /// Run a sparse vector search, returning the results best first in at
+    /// least one batch.
+    async fn sparse_search(
+        &self,
+        query: &SparseVectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<Vec<RecordBatch>>;
+    async fn explain_plan(&self, query: &VectorQuery, verbose: bool) -> Result<String> {
+        let plan = self.create_plan(query, Default::default()).await?;
+        let display = DisplayableExecutionPlan::new(plan.as_ref());
+
+        Ok(format!("{}", display.indent(verbose)))
+    }
+    async fn add(
+        &self,
+        add: AddDataBuilder<NoData>,
+        data: Box<dyn arrow_array::RecordBatchReader + Send>,
+    ) -> Result<WriteResult>;
+    async fn delete(&self, predicate: &str, expected_version: Option<u64>) -> Result<WriteResult>;
+    async fn update(&self, update: UpdateBuilder) -> Result<u64>;
+    /// Recompute one embedding column of the rows matching the filter,
+    /// writing only that column
+    async fn recompute_embedding(
+        &self,
+        filter: &str,
+        definition: EmbeddingDefinition,
+        function: Arc<dyn EmbeddingFunction>,
+    ) -> Result<u64>;
+    async fn create_index(&self, index: IndexBuilder) -> Result<()>;
+    async fn list_indices(&self) -> Result<Vec<IndexConfig>>;
+    async fn drop_index(&self, name: &str) -> Result<()>;
+    async fn index_stats(&self, index_name: &str) -> Result<Option<IndexStatistics>>;
+    /// An identifier that changes every time the index `index_name` is
+    /// replaced, or `None` if there is no such index.  Tables that can't tell
+    /// the versions of an index apart return its name.
+    async fn index_uuid(&self, index_name: &str) -> Result<Option<String>> {
+        Ok(self
+            .list_indices()
+            .await?
+            .into_iter()
+            .find(|idx| idx.name == index_name)
+            .map(|idx| idx.name))
+    }
+    async fn stats(&self) -> Result<TableStatistics>;
+    /// How much of the table each index covers, for [`Self::stats`]
+    async fn index_coverage(&self) -> Result<Vec<IndexCoverage>> {
+        let mut coverage = Vec::new();
+        for index in self.list_indices().await? {
+            // The index may have been dropped since it was listed
+            if let Some(stats) = self.index_stats(&index.name).await? {
+                coverage.push(IndexCoverage {
+                    name: index.name,
+                    columns: index.columns,
+                    num_indexed_rows: stats.num_indexed_rows as u64,
+                    num_unindexed_rows: stats.num_unindexed_rows as u64,
+                });
+            }
+        }
+        Ok(coverage)
+    }
+    async fn wait_for_index(&self, index_name: &str, timeout: std::time::Duration) -> Result<()> {
+        let start = std::time::Instant::now();
+        let mut poll_interval = std::time::Duration::from_millis(100);
+        loop {
+            // The index may not exist yet if it is being built asynchronously
+            if let Some(stats) = self.index_stats(index_name).await? {
+                if stats.num_unindexed_rows == 0 {
+                    return Ok(());
+                }
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return Err(Error::Timeout {
+                    message: format!(
+                        "index `{}` did not cover all rows of table `{}` within {:?}",
+                        index_name,
+                        self.name(),
+                        timeout
+                    ),
+                });
+            }
+            tokio::time::sleep(poll_interval.min(timeout - elapsed)).await;
+            poll_interval = (poll_interval * 2).min(std::time::Duration::from_secs(5));
+        }
+    }
+    async fn merge_insert(
+        &self,
+        params: MergeInsertBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<WriteResult>;
+    async fn commit_transaction(
+        &self,
+        operations: Vec<TransactionOperation>,
+    ) -> Result<WriteResult>;
+    async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats>;
+    async fn add_columns(
+        &self,
+        transforms: NewColumnTransform,
+        read_columns: Option<Vec<String>>,
+    ) -> Result<()>;
+    async fn alter_columns(&self, alterations: &[ColumnAlteration]) -> Result<()>;
+    async fn drop_columns(&self, columns: &[&str]) -> Result<()>;
+    /// Insert or replace key-value pairs in the schema metadata
+    async fn replace_schema_metadata(&self, values: Vec<(String, String)>) -> Result<()>;
+    async fn version(&self) -> Result<u64>;
+    async fn checkout(&self, version: u64) -> Result<()>;
+    /// When `version` was committed, in microseconds since the UNIX epoch
+    async fn version_timestamp(&self, version: u64) -> Result<i64>;
+    async fn checkout_latest(&self) -> Result<()>;
+    async fn restore(&self) -> Result<()>;
+    async fn table_definition(&self) -> Result<TableDefinition>;
+    fn dataset_uri(&self) -> &str;
+}
+
+/// A Table is a collection of strong typed Rows.
+///
+/// The type of the each row is defined in Apache Arrow [Schema].
+#[derive(Clone)]
+pub struct Table {
+    inner: Arc<dyn TableInternal>,
+    embedding_registry: Arc<dyn EmbeddingRegistry>,
+}
+
+#[cfg(all(test, feature = "remote"))]
+mod test_utils {
+    use super::*;
+
+    impl Table {
+        pub fn new_with_handler<T>(
+            name: impl Into<String>,
+            handler: impl Fn(reqwest::Request) -> http::Response<T> + Clone + Send + Sync + 'static,
+        ) -> Self
+        where
+            T: Into<reqwest::Body>,
+        {
+            let inner = Arc::new(crate::remote::table::RemoteTable::new_mock(
+                name.into(),
+                handler,
+            ));
+            Self {
+                inner,
+                // Registry is unused.
+                embedding_registry: Arc::new(MemoryRegistry::new()),
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Table {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner)
+    }
+}
+
+impl Table {
+    pub(crate) fn new(inner: Arc<dyn TableInternal>) -> Self {
+        Self {
+            inner,
+            embedding_registry: Arc::new(MemoryRegistry::new()),
+        }
+    }
+
+    pub(crate) fn new_with_embedding_registry(
+        inner: Arc<dyn TableInternal>,
+        embedding_registry: Arc<dyn EmbeddingRegistry>,
+    ) -> Self {
+        Self {
+            inner,
+            embedding_registry,
+        }
+    }
+
+    /// Cast as [`NativeTable`], or return None it if is not a [`NativeTable`].
+    ///
+    /// Warning: This function will be removed soon (features exclusive to NativeTable
+    ///          will be added to Table)
+    pub fn as_native(&self) -> Option<&NativeTable> {
+        self.inner.as_native()
+    }
+
+    /// Get the name of the table.
+    pub fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    /// Get the arrow [Schema] of the table.
+    pub async fn schema(&self) -> Result<SchemaRef> {
+        self.inner.schema().await
+    }
+
+    /// Count the number of rows in this dataset.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` if present, only count rows matching the filter
+    pub async fn count_rows(&self, filter: Option<String>) -> Result<usize> {
+        self.inner.count_rows(filter).await
     }
 
     /// Insert new records into this Table
@@ -524,10 +1489,21 @@ impl Table {
             data: batches,
             mode: AddDataMode::Append,
             write_options: WriteOptions::default(),
+            expected_version: None,
+            conflict_retry: None,
             embedding_registry: Some(self.embedding_registry.clone()),
         }
     }
 
+    /// Create a writer that buffers many small writes into a few large ones
+    ///
+    /// Each call to [`Self::add`] writes at least one new data file.  When data
+    /// arrives a few rows at a time use a [`TableWriter`] instead, which adds
+    /// the rows in large chunks once a size or time threshold is reached.
+    pub fn writer(&self) -> TableWriter {
+        TableWriter::new(self.clone())
+    }
+
     /// Update existing records in the Table
     ///
     /// An update operation can be used to adjust existing values.  Use the
@@ -543,11 +1519,16 @@ impl Table {
     /// better performance with a single [`merge_insert`] call instead of
     /// repeatedly calilng this method.
     pub fn update(&self) -> UpdateBuilder {
-        UpdateBuilder::new(self.inner.clone())
+        UpdateBuilder::new(self.inner.clone(), Some(self.embedding_registry.clone()))
     }
 
     /// Delete the rows from table that match the predicate.
     ///
+    /// Returns a [`DeleteBuilder`] that can be awaited directly, or configured
+    /// first (for example with [`DeleteBuilder::expected_version`]) and then
+    /// run with [`DeleteBuilder::execute`].  The result is a [`WriteResult`]
+    /// with the new version of the table and the number of rows deleted.
+    ///
     /// # Arguments
     /// - `predicate` - The SQL predicate string to filter the rows to be deleted.
     ///
@@ -592,11 +1573,12 @@ impl Table {
     ///     .execute()
     ///     .await
     ///     .unwrap();
-    /// tbl.delete("id > 5").await.unwrap();
+    /// let result = tbl.delete("id > 5").await.unwrap();
+    /// assert_eq!(result.num_deleted_rows, 4);
     /// # });
     /// ```
-    pub async fn delete(&self, predicate: &str) -> Result<()> {
-        self.inner.delete(predicate).await
+    pub fn delete(&self, predicate: &str) -> DeleteBuilder {
+        DeleteBuilder::new(self.inner.clone(), predicate.to_string())
     }
 
     /// Create an index on the provided column(s).
@@ -750,9 +1732,41 @@ impl Table {
         MergeInsertBuilder::new(
             self.inner.clone(),
             on.iter().map(|s| s.to_string()).collect(),
+            Some(self.embedding_registry.clone()),
         )
     }
 
+    /// Stage several write operations and commit them as a single new
+    /// version of the table
+    ///
+    /// Adds, deletes, updates and merge inserts can be staged.  They are applied
+    /// in order when [`TransactionBuilder::commit`] is called, and readers of the
+    /// table either see all of them or none of them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use arrow_array::RecordBatchReader;
+    /// # async fn replace_document(
+    /// #     tbl: lancedb::Table,
+    /// #     new_chunks: Box<dyn RecordBatchReader + Send>,
+    /// # ) -> lancedb::Result<()> {
+    /// // Replace the chunks of a document without readers ever seeing the
+    /// // document missing
+    /// tbl.transaction()
+    ///     .delete("doc_id = 7")
+    ///     .add(new_chunks)
+    ///     .commit()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    pub fn transaction(&self) -> TransactionBuilder {
+        TransactionBuilder::new(self.inner.clone(), self.embedding_registry.clone())
+    }
+
     /// Create a [`Query`] Builder.
     ///
     /// Queries allow you to search your existing data.  By default the query will
@@ -841,7 +1855,7 @@ impl Table {
     /// # });
     /// ```
     pub fn query(&self) -> Query {
-        Query::new(self.inner.clone())
+        Query::new(self.inner.clone(), self.embedding_registry.clone())
     }
 
     /// Search the table with a given query vector.
@@ -902,6 +1916,95 @@ impl Table {
         self.inner.drop_columns(columns).await
     }
 
+    /// Add an embedding column computed from an existing column
+    ///
+    /// The embedding function named by the definition is looked up in the
+    /// connection's embedding registry and applied to every existing row.
+    /// The definition is then stored in the table schema, so that rows added
+    /// later are embedded too, just as for an embedding given to
+    /// [`crate::connection::CreateTableBuilder::add_embedding`].
+    ///
+    /// Use [`AddEmbeddingColumnBuilder::checkpoint`] to make the backfill
+    /// resumable.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use lancedb::embeddings::EmbeddingDefinition;
+    /// # async fn doctest_helper(tbl: lancedb::Table) -> lancedb::Result<()> {
+    /// tbl.add_embedding_column(EmbeddingDefinition::new(
+    ///     "text",
+    ///     "my_new_model",
+    ///     Some("text_vector_v2"),
+    /// ))
+    /// .execute()
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_embedding_column(
+        &self,
+        definition: EmbeddingDefinition,
+    ) -> AddEmbeddingColumnBuilder {
+        AddEmbeddingColumnBuilder::new(
+            self.inner.clone(),
+            self.embedding_registry.clone(),
+            definition,
+        )
+    }
+
+    /// Replace an embedding column with embeddings from a new model
+    ///
+    /// This migrates to a new embedding model without downtime:
+    ///
+    /// 1. The new embeddings are computed into the new definition's
+    ///    destination column, which must not exist yet, as by
+    ///    [`Self::add_embedding_column`].
+    /// 2. The new column is recorded as a pending embedding column, so rows
+    ///    written from then on get the new embeddings as well.
+    /// 3. If `column` has an index then an index is built on the new column,
+    ///    see [`ReembedBuilder::index`].
+    /// 4. The new embeddings of rows written between steps 1 and 2 are
+    ///    computed.
+    /// 5. The table definition is switched to the new column in one commit.
+    ///    From then on text queries of the source column, or without a
+    ///    column, use the new column.
+    ///
+    /// Until then those queries keep using `column`.  Either column can
+    /// always be queried by its name.  Writers need both embedding functions
+    /// while the migration runs.  If the operation fails then running it again
+    /// continues where it stopped, and with [`ReembedBuilder::checkpoint`]
+    /// even the embeddings computed so far are kept.
+    ///
+    /// The old column is kept, and still computed for new rows, so the switch
+    /// can be undone by dropping the new column.  Drop the old column with
+    /// [`Self::drop_columns`] once it is no longer needed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use lancedb::embeddings::EmbeddingDefinition;
+    /// # async fn doctest_helper(tbl: lancedb::Table) -> lancedb::Result<()> {
+    /// tbl.reembed(
+    ///     "text_vector",
+    ///     EmbeddingDefinition::new("text", "my_new_model", Some("text_vector_v2")),
+    /// )
+    /// .execute()
+    /// .await?;
+    /// // Once nothing uses the old embeddings any more
+    /// tbl.drop_columns(&["text_vector"]).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn reembed(&self, column: &str, definition: EmbeddingDefinition) -> ReembedBuilder {
+        ReembedBuilder::new(
+            self.inner.clone(),
+            self.embedding_registry.clone(),
+            column.to_string(),
+            definition,
+        )
+    }
+
     /// Retrieve the version of the table
     ///
     /// LanceDb supports versioning.  Every operation that modifies the table increases
@@ -930,6 +2033,11 @@ impl Table {
         self.inner.checkout(version).await
     }
 
+    /// When `version` was committed, in microseconds since the UNIX epoch
+    pub(crate) async fn version_timestamp(&self, version: u64) -> Result<i64> {
+        self.inner.version_timestamp(version).await
+    }
+
     /// Ensures the table is pointing at the latest version
     ///
     /// This can be used to manually update a table when the read_consistency_interval is None
@@ -957,6 +2065,19 @@ impl Table {
         self.inner.list_indices().await
     }
 
+    /// Drop an index from the table.
+    ///
+    /// The index is removed in a new version of the table.  Older versions of
+    /// the table will still reference the index until they are pruned with
+    /// [`Self::optimize`].
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the index, as returned by [`Self::list_indices`]
+    pub async fn drop_index(&self, name: &str) -> Result<()> {
+        self.inner.drop_index(name).await
+    }
+
     /// Get the underlying dataset URI
     ///
     /// Warning: This is an internal API and the return value is subject to change.
@@ -972,6 +2093,49 @@ impl Table {
     ) -> Result<Option<IndexStatistics>> {
         self.inner.index_stats(index_name.as_ref()).await
     }
+
+    /// Get statistics about the table: its rows, fragments, size on disk,
+    /// versions and index coverage
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn doctest_helper(tbl: lancedb::Table) -> lancedb::Result<()> {
+    /// let stats = tbl.stats().await?;
+    /// println!(
+    ///     "{} rows in {} fragments, {} bytes",
+    ///     stats.num_rows, stats.fragment_stats.num_fragments, stats.total_bytes
+    /// );
+    /// for index in stats.index_stats {
+    ///     println!("{}: {} rows not indexed", index.name, index.num_unindexed_rows);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn stats(&self) -> Result<TableStatistics> {
+        self.inner.stats().await
+    }
+
+    /// Wait until an index exists and covers every row in the table.
+    ///
+    /// This is useful after starting a build with
+    /// [`IndexBuilder::execute_in_background`], or on LanceDB Cloud where indices
+    /// are built asynchronously.  Rows added after an index is created are not
+    /// covered until the index is optimized (see [`Self::optimize`]).
+    ///
+    /// Returns [`Error::Timeout`] if the index does not cover all rows within
+    /// `timeout`.
+    pub async fn wait_for_index(
+        &self,
+        index_name: impl AsRef<str>,
+        timeout: std::time::Duration,
+    ) -> Result<()> {
+        self.inner
+            .wait_for_index(index_name.as_ref(), timeout)
+            .await
+    }
 }
 
 impl From<NativeTable> for Table {
@@ -995,6 +2159,15 @@ pub struct NativeTable {
     // This comes from the connection options. We store here so we can pass down
     // to the dataset when we recreate it (for example, in checkout_latest).
     read_consistency_interval: Option<std::time::Duration>,
+
+    // Inverted indices of sparse vector columns, built on first search and
+    // updated when the version of the table changes.  Each column has its own
+    // lock so concurrent searches wait for one update.
+    sparse_indices: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<Arc<SparseIndex>>>>>>,
+
+    // The sizes of data files by path, for table statistics.  Data files are
+    // never modified once written, so their sizes can be cached.
+    file_sizes: Arc<Mutex<HashMap<String, u64>>>,
 }
 
 impl std::fmt::Display for NativeTable {
@@ -1084,6 +2257,8 @@ impl NativeTable {
             store_wrapper: write_store_wrapper,
             storage_options,
             read_consistency_interval,
+            sparse_indices: Arc::default(),
+            file_sizes: Arc::default(),
         })
     }
 
@@ -1102,6 +2277,303 @@ impl NativeTable {
         Ok(name.to_string())
     }
 
+    /// Open the table at `expected` for a write that must create the very next
+    /// version
+    ///
+    /// Fails with [`Error::CommitConflict`] if the table has already moved on.
+    /// Writes made from the returned dataset, or with the returned commit
+    /// handler, fail in the same way if another writer commits first.
+    async fn checkout_expected_version(
+        &self,
+        expected: u64,
+    ) -> Result<(Dataset, Arc<dyn CommitHandler>)> {
+        self.dataset.ensure_mutable().await?;
+        let actual = self.dataset.get().await?.latest_version_id().await?;
+        if actual != expected {
+            return Err(Error::CommitConflict { expected, actual });
+        }
+
+        let mut params = ReadParams {
+            store_options: Some(ObjectStoreParams {
+                storage_options: Some(self.storage_options.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        if let Some(wrapper) = self.store_wrapper.clone() {
+            params = params.patch_with_store_wrapper(wrapper)?;
+        }
+        let commit_handler: Arc<dyn CommitHandler> = Arc::new(
+            ExpectedVersionCommitHandler::try_new(
+                &self.uri,
+                &params.store_options.clone().unwrap_or_default(),
+                expected,
+            )
+            .await?,
+        );
+        params.commit_handler = Some(commit_handler.clone());
+
+        let dataset = DatasetBuilder::from_uri(&self.uri)
+            .with_read_params(params)
+            .with_version(expected)
+            .load()
+            .await?;
+        Ok((dataset, commit_handler))
+    }
+
+    /// Complete a write started from [`Self::checkout_expected_version`]
+    ///
+    /// A lost commit race is reported as [`Error::CommitConflict`].  On success
+    /// the table is reloaded rather than handed the written dataset, which
+    /// would otherwise keep committing through the expected version handler.
+    async fn finish_expected_version<T>(&self, expected: u64, result: Result<T>) -> Result<T> {
+        match result {
+            Ok(value) => {
+                self.dataset.reload().await?;
+                Ok(value)
+            }
+            Err(Error::Lance {
+                source: lance::Error::CommitConflict { .. },
+            }) => Err(Error::CommitConflict {
+                expected,
+                actual: self.dataset.get().await?.latest_version_id().await?,
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// The embeddings to recompute for an update of `columns`, those whose
+    /// source column is updated
+    async fn updated_embeddings(
+        &self,
+        columns: &[(String, String)],
+        registry: Option<Arc<dyn EmbeddingRegistry>>,
+    ) -> Result<Vec<(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)>> {
+        let Some(registry) = registry else {
+            return Ok(Vec::new());
+        };
+        let table_definition = self.table_definition().await?;
+        let schema = table_definition.schema.clone();
+        get_embedding_functions(&table_definition, registry.as_ref(), |ed| {
+            columns
+                .iter()
+                .any(|(column, _)| column == &ed.source_column)
+                && schema.field_with_name(&ed.dest_column_name()).is_ok()
+        })
+    }
+
+    /// Update rows, recomputing the embeddings of the updated source columns
+    ///
+    /// Lance can only update columns with SQL expressions, which cannot call
+    /// embedding functions, so the new values are computed here and merged
+    /// into the matching rows by their row id.  Only the updated columns and
+    /// their embeddings are written.
+    async fn update_with_embeddings(
+        &self,
+        update: UpdateBuilder,
+        embeddings: &[(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)],
+    ) -> Result<u64> {
+        self.dataset.ensure_mutable().await?;
+        let expected_version = update.expected_version;
+        let dataset = Arc::new(match expected_version {
+            Some(expected) => self.checkout_expected_version(expected).await?.0,
+            None => self.dataset.get().await?.clone(),
+        });
+        let source = Self::updated_rows(
+            &dataset,
+            &update.columns,
+            update.filter.as_deref(),
+            embeddings,
+        )
+        .await?;
+        self.merge_by_row_id(dataset, source, expected_version)
+            .await
+    }
+
+    /// Write the columns of the source rows into the rows with the same row id
+    async fn merge_by_row_id(
+        &self,
+        dataset: Arc<Dataset>,
+        source: SendableRecordBatchStream,
+        expected_version: Option<u64>,
+    ) -> Result<u64> {
+        let mut builder =
+            LanceMergeInsertBuilder::try_new(dataset.clone(), vec!["_rowid".to_string()])?;
+        builder.when_matched(WhenMatched::UpdateAll);
+        builder.when_not_matched(lance::dataset::WhenNotMatched::DoNothing);
+        builder.when_not_matched_by_source(WhenNotMatchedBySource::Keep);
+        let executed = builder
+            .try_build()?
+            .execute(source)
+            .await
+            .map_err(Error::from);
+        let (new_dataset, stats) = match expected_version {
+            Some(expected) => self.finish_expected_version(expected, executed).await?,
+            None => self.reload_on_conflict(executed).await?,
+        };
+        if expected_version.is_none() {
+            self.dataset.set_latest(new_dataset.as_ref().clone()).await;
+        }
+        Ok(stats.num_updated_rows)
+    }
+
+    /// The row ids of the rows matching the filter, with the new values of
+    /// the updated columns and of the recomputed embeddings
+    async fn updated_rows(
+        dataset: &Dataset,
+        columns: &[(String, String)],
+        filter: Option<&str>,
+        embeddings: &[(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)],
+    ) -> Result<SendableRecordBatchStream> {
+        let schema = Arc::new(Schema::from(dataset.schema()));
+        let mut fields = vec![Field::new("_rowid", DataType::UInt64, false)];
+        for column in columns
+            .iter()
+            .map(|(column, _)| column.clone())
+            .chain(embeddings.iter().map(|(ed, _)| ed.dest_column_name()))
+        {
+            let field = schema
+                .field_with_name(&column)
+                .map_err(|_| Error::InvalidInput {
+                    message: format!("column '{}' does not exist in the table", column),
+                })?;
+            if !fields.iter().any(|f| f.name() == &column) {
+                fields.push(field.clone());
+            }
+        }
+        let output_schema = Arc::new(Schema::new(fields));
+
+        let mut scanner = dataset.scan();
+        scanner.with_row_id();
+        if let Some(filter) = filter {
+            scanner.filter(filter)?;
+        }
+        let columns = columns.to_vec();
+        let embeddings = embeddings.to_vec();
+        let stream_schema = output_schema.clone();
+        let stream = scanner
+            .try_into_stream()
+            .await?
+            .map_err(Error::from)
+            .and_then(move |rows| {
+                let result = transaction::updated_columns(&rows, &schema, &columns, &embeddings)
+                    .and_then(|updated| {
+                        let values = stream_schema
+                            .fields()
+                            .iter()
+                            .map(|field| match field.name().as_str() {
+                                "_rowid" => rows["_rowid"].clone(),
+                                name => updated
+                                    .iter()
+                                    .find(|(column, _)| column == name)
+                                    .map(|(_, value)| value.clone())
+                                    .unwrap_or_else(|| rows[name].clone()),
+                            })
+                            .collect();
+                        Ok(RecordBatch::try_new(stream_schema.clone(), values)?)
+                    });
+                futures::future::ready(result)
+            })
+            .map_err(|e| DataFusionError::External(Box::new(e)));
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            output_schema,
+            stream,
+        )))
+    }
+
+    /// Apply the operations of a transaction, in order, to the staged table
+    async fn stage_operations(
+        &self,
+        staged: &mut StagedTable,
+        operations: Vec<TransactionOperation>,
+    ) -> Result<()> {
+        for operation in operations {
+            match operation {
+                TransactionOperation::Add {
+                    data,
+                    embedding_registry,
+                } => {
+                    let data = MaybeEmbedded::try_new(
+                        data,
+                        self.table_definition().await?,
+                        embedding_registry,
+                    )?;
+                    staged.add(data).await?;
+                }
+                TransactionOperation::Delete { predicate } => staged.delete(&predicate).await?,
+                TransactionOperation::Update {
+                    columns,
+                    only_if,
+                    embedding_registry,
+                } => {
+                    let embeddings = self
+                        .updated_embeddings(&columns, embedding_registry)
+                        .await?;
+                    staged
+                        .update(&columns, only_if.as_deref(), &embeddings)
+                        .await?
+                }
+                TransactionOperation::MergeInsert {
+                    params,
+                    data,
+                    embedding_registry,
+                } => {
+                    let data = MaybeEmbedded::try_new(
+                        data,
+                        self.table_definition().await?,
+                        embedding_registry,
+                    )?;
+                    staged.merge_insert(params, data).await?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The parameters for writing the new rows of a [`StagedTable`]
+    fn staged_write_params(&self) -> Result<WriteParams> {
+        let write_params = WriteParams {
+            store_params: Some(lance::io::ObjectStoreParams {
+                storage_options: Some(self.storage_options.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        match self.store_wrapper.clone() {
+            Some(wrapper) => Ok(write_params.patch_with_store_wrapper(wrapper)?),
+            None => Ok(write_params),
+        }
+    }
+
+    /// Bring the table up to date after losing a commit race
+    ///
+    /// The write was based on an older version, so a retry has to start from
+    /// the latest one.
+    async fn reload_on_conflict<T>(&self, result: Result<T>) -> Result<T> {
+        if let Err(Error::Lance {
+            source: lance::Error::CommitConflict { .. },
+        }) = &result
+        {
+            self.dataset.reload().await?;
+        }
+        result
+    }
+
+    async fn delete_rows(dataset: &mut Dataset, predicate: &str) -> Result<WriteResult> {
+        let before = WriteResult::live_rows(dataset);
+        dataset.delete(predicate).await?;
+        let after = WriteResult::live_rows(dataset);
+        let num_deleted_rows = before
+            .iter()
+            .map(|(id, rows)| rows.saturating_sub(after.get(id).copied().unwrap_or_default()))
+            .sum();
+        Ok(WriteResult {
+            version: dataset.version().version,
+            num_deleted_rows,
+            ..Default::default()
+        })
+    }
+
     /// Creates a new Table
     ///
     /// # Arguments
@@ -1153,6 +2625,8 @@ impl NativeTable {
             store_wrapper: write_store_wrapper,
             storage_options,
             read_consistency_interval,
+            sparse_indices: Arc::default(),
+            file_sizes: Arc::default(),
         })
     }
 
@@ -1242,7 +2716,7 @@ impl NativeTable {
         Ok(metrics)
     }
 
-    // TODO: why are these individual methods and not some single "get_stats" method?
+    // See also `Table::stats`, which collects these in one call
     pub async fn count_fragments(&self) -> Result<usize> {
         Ok(self.dataset.get().await?.count_fragments())
     }
@@ -1273,6 +2747,7 @@ impl NativeTable {
         &self,
         index: IvfPqIndexBuilder,
         field: &Field,
+        name: Option<String>,
         replace: bool,
     ) -> Result<()> {
         if !supported_vector_data_type(field.data_type()) {
@@ -1314,7 +2789,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::Vector,
-                None,
+                name,
                 &lance_idx_params,
                 replace,
             )
@@ -1326,6 +2801,7 @@ impl NativeTable {
         &self,
         index: IvfHnswPqIndexBuilder,
         field: &Field,
+        name: Option<String>,
         replace: bool,
     ) -> Result<()> {
         if !supported_vector_data_type(field.data_type()) {
@@ -1385,7 +2861,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::Vector,
-                None,
+                name,
                 &lance_idx_params,
                 replace,
             )
@@ -1397,6 +2873,7 @@ impl NativeTable {
         &self,
         index: IvfHnswSqIndexBuilder,
         field: &Field,
+        name: Option<String>,
         replace: bool,
     ) -> Result<()> {
         if !supported_vector_data_type(field.data_type()) {
@@ -1443,7 +2920,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::Vector,
-                None,
+                name,
                 &lance_idx_params,
                 replace,
             )
@@ -1453,7 +2930,7 @@ impl NativeTable {
 
     async fn create_auto_index(&self, field: &Field, opts: IndexBuilder) -> Result<()> {
         if supported_vector_data_type(field.data_type()) {
-            self.create_ivf_pq_index(IvfPqIndexBuilder::default(), field, opts.replace)
+            self.create_ivf_pq_index(IvfPqIndexBuilder::default(), field, opts.name, opts.replace)
                 .await
         } else if supported_btree_data_type(field.data_type()) {
             self.create_btree_index(field, opts).await
@@ -1487,7 +2964,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::BTree,
-                None,
+                opts.name,
                 &lance_idx_params,
                 opts.replace,
             )
@@ -1514,7 +2991,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::Bitmap,
-                None,
+                opts.name,
                 &lance_idx_params,
                 opts.replace,
             )
@@ -1541,7 +3018,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::LabelList,
-                None,
+                opts.name,
                 &lance_idx_params,
                 opts.replace,
             )
@@ -1553,6 +3030,7 @@ impl NativeTable {
         &self,
         field: &Field,
         fts_opts: FtsIndexBuilder,
+        name: Option<String>,
         replace: bool,
     ) -> Result<()> {
         if !supported_fts_data_type(field.data_type()) {
@@ -1574,7 +3052,7 @@ impl NativeTable {
             .create_index(
                 &[field.name()],
                 IndexType::Inverted,
-                None,
+                name,
                 &fts_params,
                 replace,
             )
@@ -1582,6 +3060,318 @@ impl NativeTable {
         Ok(())
     }
 
+    /// Get the inverted index of a sparse vector column.
+    ///
+    /// The index is cached and updated if the table is at a different version,
+    /// scanning only the fragments that changed.
+    async fn sparse_index(&self, dataset: &Dataset, column: &str) -> Result<Arc<SparseIndex>> {
+        let cached = self
+            .sparse_indices
+            .lock()
+            .unwrap()
+            .entry(column.to_string())
+            .or_default()
+            .clone();
+        let mut index = cached.lock().await;
+        if index.version != dataset.version().version {
+            *index = Arc::new(index.update(dataset, column).await?);
+        }
+        Ok(index.clone())
+    }
+
+    /// The first `k` of the ranked rows that match the filter
+    ///
+    /// The rows are checked in chunks, reading only the columns the filter
+    /// needs.
+    async fn filter_rows(
+        dataset: &Dataset,
+        filter: &str,
+        ranked: Vec<(u64, f32)>,
+        k: usize,
+    ) -> Result<Vec<(u64, f32)>> {
+        let planner = Planner::new(Arc::new(Schema::from(dataset.schema())));
+        let mut columns = Planner::column_names_in_expr(&planner.parse_filter(filter)?);
+        if columns.is_empty() {
+            // A filter on no columns, still read one to know the number of rows
+            columns.push(dataset.schema().fields[0].name.clone());
+        }
+        let projection = dataset.schema().project(&columns)?;
+        let mut matched = Vec::with_capacity(k);
+        for chunk in ranked.chunks(k.max(SPARSE_FILTER_CHUNK_SIZE)) {
+            let row_ids = chunk.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>();
+            let batch = dataset.take_rows(&row_ids, &projection).await?;
+            let mask = transaction::evaluate_filter(&batch, filter)?;
+            let wanted = k - matched.len();
+            matched.extend(
+                chunk
+                    .iter()
+                    .zip(mask.values().iter())
+                    .filter(|(_, matches)| *matches)
+                    .map(|(row, _)| *row)
+                    .take(wanted),
+            );
+            if matched.len() == k {
+                break;
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Find the column covered by the vector index `index_name`.
+    ///
+    /// Also returns whether the index is the one Lance searches by default,
+    /// which is the first vector index on the column.
+    async fn column_for_vector_index(
+        dataset: &Dataset,
+        index_name: &str,
+        column: Option<&String>,
+    ) -> Result<(String, bool)> {
+        let indices = dataset.load_indices().await?;
+        let index = indices
+            .iter()
+            .find(|idx| idx.name == index_name)
+            .ok_or_else(|| Error::InvalidInput {
+                message: format!("the index `{}` does not exist", index_name),
+            })?;
+        let field = index
+            .fields
+            .first()
+            .and_then(|field_id| dataset.schema().field_by_id(*field_id))
+            .ok_or_else(|| Error::Runtime {
+                message: format!(
+                    "the index `{}` does not reference a column in the schema",
+                    index_name
+                ),
+            })?;
+        if !supported_vector_data_type(&field.data_type()) {
+            return Err(Error::InvalidInput {
+                message: format!("the index `{}` is not a vector index", index_name),
+            });
+        }
+        if let Some(column) = column {
+            if column != &field.name {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "the index `{}` is on the column `{}` but the query is on the column `{}`",
+                        index_name, field.name, column
+                    ),
+                });
+            }
+        }
+
+        let searched = indices
+            .iter()
+            .find(|idx| idx.fields.contains(&field.id))
+            .map(|idx| idx.name.as_str());
+        Ok((field.name.clone(), searched == Some(index_name)))
+    }
+
+    /// Search the vector index `index_name` on `column`.
+    ///
+    /// Lance's scanner always searches the first vector index on a column, so
+    /// any other index is searched here: the index gives the nearest indexed
+    /// rows, a flat search covers the fragments it does not index yet (unless
+    /// `fast_search` is set) and the best rows are then read with
+    /// [`Dataset::take_rows`].
+    async fn named_index_search(
+        &self,
+        dataset: &Dataset,
+        index_name: &str,
+        column: &str,
+        query_vector: &Arc<dyn arrow_array::Array>,
+        query: &VectorQuery,
+    ) -> Result<RecordBatch> {
+        if query.base.full_text_search.is_some() {
+            return Err(Error::NotSupported {
+                message: format!(
+                    "full text search cannot be combined with a search of the vector index `{}`",
+                    index_name
+                ),
+            });
+        }
+        let projection = match &query.base.select {
+            Select::All => dataset.schema().clone(),
+            Select::Columns(columns) => dataset.schema().project(columns)?,
+            Select::Dynamic(_) => {
+                return Err(Error::NotSupported {
+                    message: format!(
+                    "dynamic projections are not supported when searching the vector index `{}`",
+                    index_name
+                ),
+                })
+            }
+        };
+        let distance_type = match query.distance_type {
+            Some(distance_type) => distance_type,
+            None => self
+                .index_stats(index_name)
+                .await?
+                .and_then(|stats| stats.distance_type)
+                .unwrap_or(DistanceType::L2),
+        };
+        let distance_type: LanceDistanceType = distance_type.into();
+
+        let offset = query.base.offset.unwrap_or(0);
+        let k = query.base.limit.unwrap_or(DEFAULT_TOP_K) + offset;
+        let candidates = k * query.refine_factor.unwrap_or(1).max(1) as usize;
+        let prefilter = query.base.filter.as_ref().filter(|_| query.base.prefilter);
+
+        let deltas = dataset
+            .load_indices()
+            .await?
+            .iter()
+            .filter(|idx| idx.name == index_name)
+            .cloned()
+            .collect::<Vec<_>>();
+        let prefilter_source = match prefilter {
+            Some(filter) => {
+                let mut scanner = dataset.scan();
+                scanner.filter(filter)?;
+                scanner.with_row_id();
+                scanner.project::<&str>(&[])?;
+                PreFilterSource::FilteredRowIds(scanner.create_plan().await?)
+            }
+            None => PreFilterSource::None,
+        };
+        let lance_query = LanceVectorQuery {
+            column: column.to_string(),
+            key: query_vector.clone(),
+            k: candidates,
+            lower_bound: None,
+            upper_bound: None,
+            nprobes: query.nprobes,
+            ef: None,
+            refine_factor: None,
+            metric_type: distance_type,
+            use_index: true,
+        };
+        let plan = new_knn_exec(
+            Arc::new(dataset.clone()),
+            &deltas,
+            &lance_query,
+            prefilter_source,
+        )?;
+        let mut ranked = Self::ranked_rows(DatasetRecordBatchStream::new(execute_plan(
+            plan,
+            Default::default(),
+        )?))
+        .await?;
+
+        let unindexed = dataset
+            .fragments()
+            .iter()
+            .filter(|fragment| {
+                !deltas.iter().any(|idx| {
+                    idx.fragment_bitmap
+                        .as_ref()
+                        .is_some_and(|bitmap| bitmap.contains(fragment.id as u32))
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if !query.base.fast_search && !unindexed.is_empty() {
+            let mut scanner = dataset.scan();
+            scanner.with_fragments(unindexed);
+            scanner.nearest(
+                column,
+                query_vector.as_primitive::<Float32Type>(),
+                candidates,
+            )?;
+            scanner.use_index(false);
+            scanner.distance_metric(distance_type);
+            if let Some(filter) = prefilter {
+                scanner.prefilter(true);
+                scanner.filter(filter)?;
+            }
+            scanner.with_row_id();
+            scanner.project::<&str>(&[])?;
+            ranked.extend(Self::ranked_rows(scanner.try_into_stream().await?).await?);
+        }
+        ranked.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        ranked.truncate(candidates);
+
+        if query.refine_factor.is_some() && !ranked.is_empty() {
+            // Rank the candidates again by their exact distance
+            let row_ids = ranked.iter().map(|(row_id, _)| *row_id).collect::<Vec<_>>();
+            let vectors = dataset
+                .take_rows(&row_ids, &dataset.schema().project(&[column])?)
+                .await?;
+            let distances = distance_type.arrow_batch_func()(
+                query_vector.as_ref(),
+                vectors.column(0).as_fixed_size_list(),
+            )?;
+            ranked = row_ids
+                .into_iter()
+                .zip(distances.values().iter().copied())
+                .collect();
+            ranked.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+        }
+        ranked.truncate(k);
+        if let Some(filter) = query.base.filter.as_ref().filter(|_| prefilter.is_none()) {
+            ranked = Self::filter_rows(dataset, filter, ranked, k).await?;
+        }
+        ranked.drain(..offset.min(ranked.len()));
+
+        let (row_ids, distances): (Vec<u64>, Vec<f32>) = ranked.into_iter().unzip();
+        let batch = if row_ids.is_empty() {
+            RecordBatch::new_empty(Arc::new(Schema::from(&projection)))
+        } else {
+            dataset.take_rows(&row_ids, &projection).await?
+        };
+
+        let mut fields = batch.schema().fields().to_vec();
+        let mut columns = batch.columns().to_vec();
+        fields.push(Arc::new(Field::new(
+            "_distance",
+            arrow_schema::DataType::Float32,
+            true,
+        )));
+        columns.push(Arc::new(Float32Array::from(distances)));
+        if query.base.with_row_id {
+            fields.push(Arc::new(Field::new(
+                "_rowid",
+                arrow_schema::DataType::UInt64,
+                true,
+            )));
+            columns.push(Arc::new(UInt64Array::from(row_ids)));
+        }
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+
+    /// The row ids and distances of the results of a nearest neighbor search
+    async fn ranked_rows(stream: DatasetRecordBatchStream) -> Result<Vec<(u64, f32)>> {
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        let mut ranked = Vec::new();
+        for batch in batches {
+            let (Some(row_ids), Some(distances)) = (
+                batch.column_by_name("_rowid"),
+                batch.column_by_name("_distance"),
+            ) else {
+                return Err(Error::Runtime {
+                    message: "a vector search did not return the row ids and distances".to_string(),
+                });
+            };
+            ranked.extend(
+                row_ids
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .iter()
+                    .copied()
+                    .zip(
+                        distances
+                            .as_primitive::<Float32Type>()
+                            .values()
+                            .iter()
+                            .copied(),
+                    ),
+            );
+        }
+        Ok(ranked)
+    }
+
     async fn generic_query(
         &self,
         query: &VectorQuery,
@@ -1646,6 +3436,12 @@ impl TableInternal for NativeTable {
         self.dataset.as_time_travel(version).await
     }
 
+    async fn version_timestamp(&self, version: u64) -> Result<i64> {
+        let dataset = self.dataset.get().await?;
+        let version = dataset.checkout_version(version).await?.version();
+        Ok(version.timestamp.timestamp_micros())
+    }
+
     async fn checkout_latest(&self) -> Result<()> {
         self.dataset
             .as_latest(self.read_consistency_interval)
@@ -1693,9 +3489,12 @@ impl TableInternal for NativeTable {
         &self,
         add: AddDataBuilder<NoData>,
         data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
+    ) -> Result<WriteResult> {
+        let expected_version = add.expected_version;
         let data =
             MaybeEmbedded::try_new(data, self.table_definition().await?, add.embedding_registry)?;
+        let (data, num_bad_vectors) =
+            BadVectorReader::wrap(self, &add.write_options, Box::new(data)).await?;
 
         let mut lance_params = add.write_options.lance_write_params.unwrap_or(WriteParams {
             mode: match add.mode {
@@ -1718,16 +3517,44 @@ impl TableInternal for NativeTable {
         }
 
         // patch the params if we have a write store wrapper
-        let lance_params = match self.store_wrapper.clone() {
+        let mut lance_params = match self.store_wrapper.clone() {
             Some(wrapper) => lance_params.patch_with_store_wrapper(wrapper)?,
             None => lance_params,
         };
 
         self.dataset.ensure_mutable().await?;
-        let dataset = Dataset::write(data, &self.uri, Some(lance_params)).await?;
+        let old_dataset = match expected_version {
+            Some(expected) => {
+                let (dataset, commit_handler) = self.checkout_expected_version(expected).await?;
+                lance_params.commit_handler = Some(commit_handler);
+                dataset
+            }
+            None => self.dataset.get().await?.clone(),
+        };
+        let overwrite = matches!(lance_params.mode, WriteMode::Overwrite);
+        let dataset = Dataset::write(data, &self.uri, Some(lance_params))
+            .await
+            .map_err(Error::from);
+        let dataset = match expected_version {
+            Some(expected) => self.finish_expected_version(expected, dataset).await?,
+            None => dataset?,
+        };
 
-        self.dataset.set_latest(dataset).await;
-        Ok(())
+        let mut result = if overwrite {
+            // Fragment ids restart on overwrite, so everything in the new
+            // version was written and everything in the old one is gone
+            WriteResult {
+                num_deleted_rows: WriteResult::live_rows(&old_dataset).values().sum(),
+                ..WriteResult::from_new_fragments(None, &dataset).await?
+            }
+        } else {
+            WriteResult::from_new_fragments(Some(&old_dataset), &dataset).await?
+        };
+        result.num_bad_vectors = num_bad_vectors.load(std::sync::atomic::Ordering::Relaxed);
+        if expected_version.is_none() {
+            self.dataset.set_latest(dataset).await;
+        }
+        Ok(result)
     }
 
     async fn create_index(&self, opts: IndexBuilder) -> Result<()> {
@@ -1745,21 +3572,38 @@ impl TableInternal for NativeTable {
             Index::BTree(_) => self.create_btree_index(field, opts).await,
             Index::Bitmap(_) => self.create_bitmap_index(field, opts).await,
             Index::LabelList(_) => self.create_label_list_index(field, opts).await,
-            Index::FTS(fts_opts) => self.create_fts_index(field, fts_opts, opts.replace).await,
-            Index::IvfPq(ivf_pq) => self.create_ivf_pq_index(ivf_pq, field, opts.replace).await,
+            Index::FTS(fts_opts) => {
+                self.create_fts_index(field, fts_opts, opts.name, opts.replace)
+                    .await
+            }
+            Index::IvfPq(ivf_pq) => {
+                self.create_ivf_pq_index(ivf_pq, field, opts.name, opts.replace)
+                    .await
+            }
             Index::IvfHnswPq(ivf_hnsw_pq) => {
-                self.create_ivf_hnsw_pq_index(ivf_hnsw_pq, field, opts.replace)
+                self.create_ivf_hnsw_pq_index(ivf_hnsw_pq, field, opts.name, opts.replace)
                     .await
             }
             Index::IvfHnswSq(ivf_hnsw_sq) => {
-                self.create_ivf_hnsw_sq_index(ivf_hnsw_sq, field, opts.replace)
+                self.create_ivf_hnsw_sq_index(ivf_hnsw_sq, field, opts.name, opts.replace)
                     .await
             }
         }
     }
 
     async fn update(&self, update: UpdateBuilder) -> Result<u64> {
-        let dataset = self.dataset.get().await?.clone();
+        let embeddings = self
+            .updated_embeddings(&update.columns, update.embedding_registry.clone())
+            .await?;
+        if !embeddings.is_empty() {
+            return self.update_with_embeddings(update, &embeddings).await;
+        }
+
+        let expected_version = update.expected_version;
+        let dataset = match expected_version {
+            Some(expected) => self.checkout_expected_version(expected).await?.0,
+            None => self.dataset.get().await?.clone(),
+        };
         let mut builder = LanceUpdateBuilder::new(Arc::new(dataset));
         if let Some(predicate) = update.filter {
             builder = builder.update_where(&predicate)?;
@@ -1770,11 +3614,33 @@ impl TableInternal for NativeTable {
         }
 
         let operation = builder.build()?;
-        let res = operation.execute().await?;
-        self.dataset
-            .set_latest(res.new_dataset.as_ref().clone())
-            .await;
-        Ok(res.rows_updated)
+        let res = operation.execute().await.map_err(Error::from);
+        match expected_version {
+            Some(expected) => Ok(self
+                .finish_expected_version(expected, res)
+                .await?
+                .rows_updated),
+            None => {
+                let res = res?;
+                self.dataset
+                    .set_latest(res.new_dataset.as_ref().clone())
+                    .await;
+                Ok(res.rows_updated)
+            }
+        }
+    }
+
+    async fn recompute_embedding(
+        &self,
+        filter: &str,
+        definition: EmbeddingDefinition,
+        function: Arc<dyn EmbeddingFunction>,
+    ) -> Result<u64> {
+        self.dataset.ensure_mutable().await?;
+        let dataset = Arc::new(self.dataset.get().await?.clone());
+        let source =
+            Self::updated_rows(&dataset, &[], Some(filter), &[(definition, function)]).await?;
+        self.merge_by_row_id(dataset, source, None).await
     }
 
     async fn create_plan(
@@ -1788,7 +3654,16 @@ impl TableInternal for NativeTable {
 
         if let Some(query_vector) = query.query_vector.as_ref() {
             // If there is a vector query, default to limit=10 if unspecified
-            let column = if let Some(col) = query.column.as_ref() {
+            let mut named_index = None;
+            let column = if let Some(index_name) = query.index_name.as_ref() {
+                let (column, searched_by_default) =
+                    Self::column_for_vector_index(&ds_ref, index_name, query.column.as_ref())
+                        .await?;
+                if !searched_by_default && query.use_index {
+                    named_index = Some(index_name);
+                }
+                column
+            } else if let Some(col) = query.column.as_ref() {
                 col.clone()
             } else {
                 // Infer a vector column with the same dimension of the query vector.
@@ -1822,6 +3697,14 @@ impl TableInternal for NativeTable {
                 }
             }
 
+            if let Some(index_name) = named_index {
+                let batch = self
+                    .named_index_search(&ds_ref, index_name, &column, query_vector, query)
+                    .await?;
+                let schema = batch.schema();
+                return Ok(Arc::new(MemoryExec::try_new(&[vec![batch]], schema, None)?));
+            }
+
             let query_vector = query_vector.as_primitive::<Float32Type>();
             scanner.nearest(
                 &column,
@@ -1889,29 +3772,139 @@ impl TableInternal for NativeTable {
             scanner.distance_metric(distance_type.into());
         }
 
-        Ok(scanner.create_plan().await?)
-    }
+        Ok(scanner.create_plan().await?)
+    }
+
+    async fn plain_query(
+        &self,
+        query: &Query,
+        options: QueryExecutionOptions,
+    ) -> Result<DatasetRecordBatchStream> {
+        self.generic_query(&query.clone().into_vector(), options)
+            .await
+    }
+
+    async fn sparse_search(
+        &self,
+        query: &SparseVectorQuery,
+        options: QueryExecutionOptions,
+    ) -> Result<Vec<RecordBatch>> {
+        if query.base.full_text_search.is_some() {
+            return Err(Error::NotSupported {
+                message: "full text search cannot be combined with sparse vector search"
+                    .to_string(),
+            });
+        }
+        let dataset = self.dataset.get().await?.clone();
+
+        let column = match &query.column {
+            Some(column) => column.clone(),
+            None => default_sparse_vector_column(&Schema::from(dataset.schema()))?,
+        };
+        let field = dataset.schema().field(&column).ok_or(Error::Schema {
+            message: format!("Column {} not found in dataset schema", column),
+        })?;
+        if !is_sparse_vector_type(&field.data_type()) {
+            return Err(Error::InvalidInput {
+                message: format!("The column '{}' is not a sparse vector column", column),
+            });
+        }
+
+        let offset = query.base.offset.unwrap_or(0);
+        let k = query.base.limit.unwrap_or(DEFAULT_TOP_K) + offset;
+        let index = self.sparse_index(&dataset, &column).await?;
+        let mut results = match &query.base.filter {
+            // The filter is applied before the limit, so it is checked on the
+            // candidates, best first, until there are enough
+            Some(filter) => {
+                let ranked = index.rank(&query.query_vector);
+                Self::filter_rows(&dataset, filter, ranked, k).await?
+            }
+            None => index.search(&query.query_vector, k),
+        };
+        results.drain(..offset.min(results.len()));
+
+        let projection = match &query.base.select {
+            Select::All => dataset.schema().clone(),
+            Select::Columns(columns) => dataset.schema().project(columns)?,
+            Select::Dynamic(_) => {
+                return Err(Error::NotSupported {
+                    message: "dynamic projections are not supported by sparse vector search"
+                        .to_string(),
+                })
+            }
+        };
+        let (row_ids, scores): (Vec<u64>, Vec<f32>) = results.into_iter().unzip();
+        let batch = if row_ids.is_empty() {
+            RecordBatch::new_empty(Arc::new(Schema::from(&projection)))
+        } else {
+            dataset.take_rows(&row_ids, &projection).await?
+        };
 
-    async fn plain_query(
-        &self,
-        query: &Query,
-        options: QueryExecutionOptions,
-    ) -> Result<DatasetRecordBatchStream> {
-        self.generic_query(&query.clone().into_vector(), options)
-            .await
+        let mut fields = batch.schema().fields().to_vec();
+        let mut columns = batch.columns().to_vec();
+        fields.push(Arc::new(Field::new(
+            "_score",
+            arrow_schema::DataType::Float32,
+            false,
+        )));
+        columns.push(Arc::new(Float32Array::from(scores)));
+        if query.base.with_row_id {
+            fields.push(Arc::new(Field::new(
+                "_rowid",
+                arrow_schema::DataType::UInt64,
+                false,
+            )));
+            columns.push(Arc::new(UInt64Array::from(row_ids)));
+        }
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+        let max_batch_length = (options.max_batch_length as usize).max(1);
+        if batch.num_rows() <= max_batch_length {
+            return Ok(vec![batch]);
+        }
+        Ok((0..batch.num_rows())
+            .step_by(max_batch_length)
+            .map(|start| batch.slice(start, max_batch_length.min(batch.num_rows() - start)))
+            .collect())
     }
 
     async fn merge_insert(
         &self,
         params: MergeInsertBuilder,
         new_data: Box<dyn RecordBatchReader + Send>,
-    ) -> Result<()> {
-        let dataset = Arc::new(self.dataset.get().await?.clone());
-        let mut builder = LanceMergeInsertBuilder::try_new(dataset.clone(), params.on)?;
+    ) -> Result<WriteResult> {
+        let expected_version = params.expected_version;
+        let dataset = Arc::new(match expected_version {
+            Some(expected) => self.checkout_expected_version(expected).await?.0,
+            None => self.dataset.get().await?.clone(),
+        });
+        let table_definition = self.table_definition().await?;
+        let partial_source = if params.when_matched_update.is_some() {
+            if params.when_not_matched_by_source_delete {
+                return Err(Error::NotSupported {
+                    message: "when_matched_update cannot be combined with \
+                              when_not_matched_by_source_delete on a local table, use \
+                              when_matched_update_all instead"
+                        .to_string(),
+                });
+            }
+            Some(merge::partial_update_source(
+                dataset.as_ref().clone(),
+                &params,
+                &table_definition,
+                new_data,
+            )?)
+        } else {
+            None
+        };
+        let mut builder = LanceMergeInsertBuilder::try_new(dataset.clone(), params.on.clone())?;
         match (
             params.when_matched_update_all,
             params.when_matched_update_all_filt,
         ) {
+            // The partial update source only holds the matched rows that
+            // satisfy the condition, with their new values
+            _ if partial_source.is_some() => builder.when_matched(WhenMatched::UpdateAll),
             (false, _) => builder.when_matched(WhenMatched::DoNothing),
             (true, None) => builder.when_matched(WhenMatched::UpdateAll),
             (true, Some(filt)) => builder.when_matched(WhenMatched::update_if(&dataset, &filt)?),
@@ -1932,15 +3925,75 @@ impl TableInternal for NativeTable {
             builder.when_not_matched_by_source(WhenNotMatchedBySource::Keep);
         }
         let job = builder.try_build()?;
-        let (new_dataset, _stats) = job.execute_reader(new_data).await?;
-        self.dataset.set_latest(new_dataset.as_ref().clone()).await;
-        Ok(())
+        let executed = match partial_source {
+            Some(source) => job.execute(source).await,
+            None => {
+                let new_data = MaybeEmbedded::try_new(
+                    new_data,
+                    table_definition,
+                    params.embedding_registry.clone(),
+                )?;
+                job.execute_reader(new_data).await
+            }
+        }
+        .map_err(Error::from);
+        let (new_dataset, stats) = match expected_version {
+            Some(expected) => self.finish_expected_version(expected, executed).await?,
+            None => self.reload_on_conflict(executed).await?,
+        };
+        let result = WriteResult {
+            num_inserted_rows: stats.num_inserted_rows,
+            num_updated_rows: stats.num_updated_rows,
+            num_deleted_rows: stats.num_deleted_rows,
+            ..WriteResult::from_new_fragments(Some(dataset.as_ref()), new_dataset.as_ref()).await?
+        };
+        if expected_version.is_none() {
+            self.dataset.set_latest(new_dataset.as_ref().clone()).await;
+        }
+        Ok(result)
+    }
+
+    async fn commit_transaction(
+        &self,
+        operations: Vec<TransactionOperation>,
+    ) -> Result<WriteResult> {
+        self.dataset.ensure_mutable().await?;
+        let dataset = self.dataset.get().await?.clone();
+        let version = dataset.version().version;
+        let mut staged = StagedTable::new(dataset, &self.uri, self.staged_write_params()?)?;
+        if let Err(err) = self.stage_operations(&mut staged, operations).await {
+            staged.abort().await;
+            return Err(err);
+        }
+
+        match staged.commit(None).await? {
+            Some((dataset, result)) => {
+                self.dataset.set_latest(dataset).await;
+                Ok(result)
+            }
+            None => Ok(WriteResult {
+                version,
+                ..Default::default()
+            }),
+        }
     }
 
     /// Delete rows from the table
-    async fn delete(&self, predicate: &str) -> Result<()> {
-        self.dataset.get_mut().await?.delete(predicate).await?;
-        Ok(())
+    async fn delete(&self, predicate: &str, expected_version: Option<u64>) -> Result<WriteResult> {
+        match expected_version {
+            Some(expected) => {
+                let (mut dataset, _) = self.checkout_expected_version(expected).await?;
+                let result = Self::delete_rows(&mut dataset, predicate).await;
+                self.finish_expected_version(expected, result).await
+            }
+            None => {
+                let result = {
+                    let mut dataset = self.dataset.get_mut().await?;
+                    Self::delete_rows(&mut dataset, predicate).await
+                };
+                self.reload_on_conflict(result).await
+            }
+        }
     }
 
     async fn optimize(&self, action: OptimizeAction) -> Result<OptimizeStats> {
@@ -2018,7 +4071,55 @@ impl TableInternal for NativeTable {
     }
 
     async fn drop_columns(&self, columns: &[&str]) -> Result<()> {
+        let table_definition = self.table_definition().await?;
         self.dataset.get_mut().await?.drop_columns(columns).await?;
+
+        // Column definitions are stored by position, so they have to be
+        // realigned with the remaining columns
+        if !table_definition
+            .schema
+            .metadata
+            .contains_key("lancedb::column_definitions")
+        {
+            return Ok(());
+        }
+        let column_definitions = table_definition
+            .schema
+            .fields()
+            .iter()
+            .zip(table_definition.column_definitions)
+            .filter(|(field, _)| !columns.contains(&field.name().as_str()))
+            .map(|(_, mut column_definition)| {
+                // Dropping a replacement column undoes the replacement
+                if let ColumnKind::Embedding(definition) = &mut column_definition.kind {
+                    if definition
+                        .superseded_by
+                        .as_deref()
+                        .is_some_and(|column| columns.contains(&column))
+                    {
+                        definition.superseded_by = None;
+                    }
+                }
+                column_definition
+            })
+            .collect::<Vec<_>>();
+        let column_definitions =
+            serde_json::to_string(&column_definitions).map_err(|e| Error::Runtime {
+                message: format!("Failed to serialize column definitions: {}", e),
+            })?;
+        self.replace_schema_metadata(vec![(
+            "lancedb::column_definitions".to_string(),
+            column_definitions,
+        )])
+        .await
+    }
+
+    async fn replace_schema_metadata(&self, values: Vec<(String, String)>) -> Result<()> {
+        self.dataset
+            .get_mut()
+            .await?
+            .replace_schema_metadata(values)
+            .await?;
         Ok(())
     }
 
@@ -2049,10 +4150,146 @@ impl TableInternal for NativeTable {
         }).try_collect::<Vec<_>>().await
     }
 
+    async fn drop_index(&self, index_name: &str) -> Result<()> {
+        let mut dataset = self.dataset.get_mut().await?;
+        let removed_indices = dataset
+            .load_indices()
+            .await?
+            .iter()
+            .filter(|idx| idx.name == index_name)
+            .cloned()
+            .collect::<Vec<_>>();
+        if removed_indices.is_empty() {
+            return Err(Error::InvalidInput {
+                message: format!("index {} does not exist", index_name),
+            });
+        }
+        // An index is dropped by committing an index change that only removes
+        // its segments, the same operation optimize uses to replace them
+        let operation = Operation::CreateIndex {
+            new_indices: vec![],
+            removed_indices,
+        };
+        *dataset = Dataset::commit(
+            &self.uri,
+            operation,
+            Some(dataset.version().version),
+            self.staged_write_params()?.store_params,
+            None,
+            dataset.session(),
+            dataset.manifest_naming_scheme == ManifestNamingScheme::V2,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn index_uuid(&self, index_name: &str) -> Result<Option<String>> {
+        let dataset = self.dataset.get().await?;
+        Ok(dataset
+            .load_indices()
+            .await?
+            .iter()
+            .find(|idx| idx.name == index_name)
+            .map(|idx| idx.uuid.to_string()))
+    }
+
     fn dataset_uri(&self) -> &str {
         self.uri.as_str()
     }
 
+    async fn stats(&self) -> Result<TableStatistics> {
+        let dataset = self.dataset.get().await?.clone();
+        let num_rows = dataset.count_rows(None).await? as u64;
+        let num_deleted_rows = dataset.count_deleted_rows().await? as u64;
+        let num_versions = dataset.versions().await?.len() as u64;
+        let fragments = dataset.fragments();
+        let fragment_stats = FragmentStatistics::from_row_counts(
+            fragments
+                .iter()
+                .map(|frag| frag.physical_rows.unwrap_or_default() as u64),
+        );
+
+        // File sizes are not recorded in the manifest, so they are read from
+        // the object store the first time a file is seen
+        let files = fragments
+            .iter()
+            .flat_map(|frag| frag.files.iter())
+            .collect::<Vec<_>>();
+        let missing = {
+            let file_sizes = self.file_sizes.lock().unwrap();
+            files
+                .iter()
+                .filter(|file| !file_sizes.contains_key(&file.path))
+                .map(|file| file.path.clone())
+                .collect::<Vec<_>>()
+        };
+        let object_store = dataset.object_store();
+        let data_dir = dataset.data_dir();
+        let fetched = futures::stream::iter(missing)
+            .map(|path| {
+                let data_dir = &data_dir;
+                async move {
+                    let meta = object_store
+                        .inner
+                        .head(&data_dir.child(path.as_str()))
+                        .await?;
+                    Result::Ok((path, meta.size as u64))
+                }
+            })
+            .buffered(16)
+            .try_collect::<Vec<_>>()
+            .await?;
+        let schema = dataset.schema();
+        let sizes = {
+            let mut file_sizes = self.file_sizes.lock().unwrap();
+            file_sizes.extend(fetched);
+            // Forget the files that are no longer part of the table
+            let current = files
+                .iter()
+                .map(|file| file.path.as_str())
+                .collect::<HashSet<_>>();
+            file_sizes.retain(|path, _| current.contains(path.as_str()));
+            files
+                .iter()
+                .map(|file| {
+                    let columns = schema
+                        .fields
+                        .iter()
+                        .filter(|field| file.fields.contains(&field.id))
+                        .map(|field| field.name.clone())
+                        .collect::<Vec<_>>();
+                    (columns, file_sizes[&file.path])
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut total_bytes = 0;
+        let mut column_group_stats: Vec<ColumnGroupStatistics> = Vec::new();
+        for (columns, bytes) in sizes {
+            total_bytes += bytes;
+            match column_group_stats
+                .iter_mut()
+                .find(|group| group.columns == columns)
+            {
+                Some(group) => group.bytes_on_disk += bytes,
+                None => column_group_stats.push(ColumnGroupStatistics {
+                    columns,
+                    bytes_on_disk: bytes,
+                }),
+            }
+        }
+
+        Ok(TableStatistics {
+            num_rows,
+            num_deleted_rows,
+            num_versions,
+            total_bytes,
+            fragment_stats,
+            column_group_stats,
+            index_stats: self.index_coverage().await?,
+        })
+    }
+
     async fn index_stats(&self, index_name: &str) -> Result<Option<IndexStatistics>> {
         let stats = match self
             .dataset
@@ -2194,9 +4431,438 @@ mod tests {
             schema.clone(),
         );
 
-        table.add(new_batches).execute().await.unwrap();
-        assert_eq!(table.count_rows(None).await.unwrap(), 20);
-        assert_eq!(table.name(), "test");
+        let result = table.add(new_batches).execute().await.unwrap();
+        assert_eq!(result.version, 2);
+        assert_eq!(result.num_inserted_rows, 10);
+        assert_eq!(result.num_deleted_rows, 0);
+        assert_eq!(result.num_fragments_written, 1);
+        assert!(result.bytes_written > 0);
+        assert_eq!(table.count_rows(None).await.unwrap(), 20);
+        assert_eq!(table.name(), "test");
+
+        let result = table.delete("i >= 105").await.unwrap();
+        assert_eq!(result.version, 3);
+        assert_eq!(result.num_deleted_rows, 5);
+        assert_eq!(result.num_fragments_written, 0);
+        assert_eq!(result.bytes_written, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expected_version() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        // Another writer moves the table on to version 2
+        let other = conn.open_table("test").execute().await.unwrap();
+        other.add(make_test_batches()).execute().await.unwrap();
+
+        let result = table
+            .add(make_test_batches())
+            .expected_version(1)
+            .execute()
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::CommitConflict {
+                expected: 1,
+                actual: 2
+            })
+        ));
+        assert_eq!(other.count_rows(None).await.unwrap(), 20);
+
+        let updated = table
+            .update()
+            .column("i", "i + 100")
+            .only_if("i < 5")
+            .expected_version(2)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(updated, 10);
+        assert_eq!(table.version().await.unwrap(), 3);
+
+        let result = table.delete("i >= 100").expected_version(2).await;
+        assert!(matches!(
+            result,
+            Err(Error::CommitConflict {
+                expected: 2,
+                actual: 3
+            })
+        ));
+        assert_eq!(table.count_rows(Some("i >= 100".into())).await.unwrap(), 10);
+
+        let result = table
+            .delete("i >= 100")
+            .expected_version(3)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.version, 4);
+        assert_eq!(result.num_deleted_rows, 10);
+
+        let mut merge = table.merge_insert(&["i"]);
+        merge.when_not_matched_insert_all().expected_version(3);
+        let result = merge.execute(some_sample_data()).await;
+        assert!(matches!(
+            result,
+            Err(Error::CommitConflict {
+                expected: 3,
+                actual: 4
+            })
+        ));
+
+        // Writes without an expected version are unaffected
+        let result = table.add(make_test_batches()).execute().await.unwrap();
+        assert_eq!(result.version, 5);
+    }
+
+    #[tokio::test]
+    async fn test_writer() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        let schema = table.schema().await.unwrap();
+        let rows = |start: i32| {
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(Int32Array::from_iter_values(start..start + 2))],
+            )
+            .unwrap()
+        };
+
+        let writer = table.writer().max_buffered_rows(25);
+        for i in 0..12 {
+            writer.write(rows(100 + i * 2)).await.unwrap();
+        }
+        // Nothing is written until the threshold is reached
+        assert_eq!(table.version().await.unwrap(), 1);
+        writer.write(rows(124)).await.unwrap();
+        assert_eq!(table.version().await.unwrap(), 2);
+        assert_eq!(table.count_rows(None).await.unwrap(), 36);
+
+        writer.write(rows(126)).await.unwrap();
+        let result = writer.close().await.unwrap().unwrap();
+        assert_eq!(result.version, 3);
+        assert_eq!(result.num_inserted_rows, 2);
+        assert_eq!(result.num_fragments_written, 1);
+        assert_eq!(table.count_rows(None).await.unwrap(), 38);
+
+        let other_schema = Arc::new(Schema::new(vec![Field::new("j", DataType::Int32, false)]));
+        let writer = table.writer();
+        writer.write(rows(200)).await.unwrap();
+        let result = writer
+            .write(
+                RecordBatch::try_new(
+                    other_schema.clone(),
+                    vec![Arc::new(Int32Array::from(vec![1]))],
+                )
+                .unwrap(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
+        assert!(writer.flush().await.unwrap().is_some());
+        assert!(writer.flush().await.unwrap().is_none());
+
+        // Rows are flushed in the background once they have waited long enough
+        let writer = table
+            .writer()
+            .flush_interval(std::time::Duration::from_millis(50));
+        writer.write(rows(300)).await.unwrap();
+        // Wait for the flush without depending on how long it takes
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while table.count_rows(None).await.unwrap() < 42 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(writer.close().await.unwrap().is_none());
+        assert_eq!(table.count_rows(None).await.unwrap(), 42);
+
+        // A write that cannot be flushed is not buffered
+        let writer = table.writer().max_buffered_rows(3);
+        writer
+            .write(
+                RecordBatch::try_new(
+                    other_schema.clone(),
+                    vec![Arc::new(Int32Array::from(vec![1, 2]))],
+                )
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            let result = writer
+                .write(
+                    RecordBatch::try_new(
+                        other_schema.clone(),
+                        vec![Arc::new(Int32Array::from(vec![3, 4]))],
+                    )
+                    .unwrap(),
+                )
+                .await;
+            assert!(result.is_err());
+        }
+        assert!(writer.close().await.is_err());
+        assert_eq!(table.count_rows(None).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_auto_optimize() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri)
+            .auto_optimize(auto_optimize::AutoOptimize {
+                check_interval: Duration::from_millis(50),
+                max_small_files: 2,
+                prune_older_than: None,
+                ..Default::default()
+            })
+            .execute()
+            .await
+            .unwrap();
+
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        for _ in 0..4 {
+            table.add(make_test_batches()).execute().await.unwrap();
+        }
+
+        let native = table.as_native().unwrap();
+        for _ in 0..100 {
+            if native.count_fragments().await.unwrap() == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(native.count_fragments().await.unwrap(), 1);
+        assert_eq!(table.count_rows(None).await.unwrap(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        table.add(make_test_batches()).execute().await.unwrap();
+        table.delete("i < 3").await.unwrap();
+        table
+            .create_index(&["i"], Index::BTree(BTreeIndexBuilder::default()))
+            .execute()
+            .await
+            .unwrap();
+        table.add(make_test_batches()).execute().await.unwrap();
+
+        let stats = table.stats().await.unwrap();
+        assert_eq!(stats.num_rows, 24);
+        assert_eq!(stats.num_deleted_rows, 6);
+        assert_eq!(stats.num_versions, 5);
+        assert_eq!(stats.fragment_stats.num_fragments, 3);
+        assert_eq!(stats.fragment_stats.size_histogram.len(), 5);
+        assert_eq!(stats.fragment_stats.size_histogram[0].max_rows, Some(1_000));
+        assert_eq!(stats.fragment_stats.size_histogram[0].num_fragments, 3);
+        assert_eq!(stats.fragment_stats.size_histogram[4].max_rows, None);
+        assert_eq!(stats.column_group_stats.len(), 1);
+        assert_eq!(stats.column_group_stats[0].columns, vec!["i".to_string()]);
+        assert!(stats.total_bytes > 0);
+        assert_eq!(stats.column_group_stats[0].bytes_on_disk, stats.total_bytes);
+        assert_eq!(stats.index_stats.len(), 1);
+        assert_eq!(stats.index_stats[0].name, "i_idx");
+        assert_eq!(stats.index_stats[0].columns, vec!["i".to_string()]);
+        assert_eq!(stats.index_stats[0].num_unindexed_rows, 10);
+
+        // File sizes are cached, and give the same result
+        assert_eq!(table.stats().await.unwrap(), stats);
+    }
+
+    #[tokio::test]
+    async fn test_conflict_retry() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let table = conn
+            .create_table("test", make_test_batches())
+            .execute()
+            .await
+            .unwrap();
+        let other = conn.open_table("test").execute().await.unwrap();
+
+        // Deletes from the same fragment, based on the same version, conflict
+        other.delete("i = 0").await.unwrap();
+        let result = table.delete("i = 1").await;
+        assert!(matches!(
+            result,
+            Err(Error::Lance {
+                source: lance::Error::CommitConflict { .. }
+            })
+        ));
+
+        other.delete("i = 2").await.unwrap();
+        let policy = RetryPolicy {
+            backoff_factor: Some(0.0),
+            backoff_jitter: Some(0.0),
+            ..Default::default()
+        };
+        let result = table
+            .delete("i = 3")
+            .conflict_retry(policy.clone())
+            .await
+            .unwrap();
+        assert_eq!(result.version, 4);
+        assert_eq!(result.num_deleted_rows, 1);
+        assert_eq!(table.count_rows(None).await.unwrap(), 7);
+
+        // Only appends are retried
+        let add = || {
+            table
+                .add(make_test_batches())
+                .conflict_retry(policy.clone())
+        };
+        assert!(add().is_retryable());
+        assert!(!add().mode(AddDataMode::Overwrite).is_retryable());
+        assert!(!add().expected_version(4).is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_bad_vectors() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2);
+        let table = conn
+            .create_empty_table(
+                "test",
+                Arc::new(Schema::new(vec![
+                    Field::new("id", DataType::Int32, false),
+                    Field::new("vector", vector_type, true),
+                ])),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        // One good vector, then one with a NaN, one with the wrong dimension
+        // and one that is all zeros
+        let data = || {
+            let schema = Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new(
+                    "vector",
+                    DataType::List(Arc::new(Field::new("item", DataType::Float32, true))),
+                    true,
+                ),
+            ]));
+            let vectors = arrow_array::ListArray::from_iter_primitive::<Float32Type, _, _>(vec![
+                Some(vec![Some(1.0), Some(2.0)]),
+                Some(vec![Some(f32::NAN), Some(1.0)]),
+                Some(vec![Some(1.0), Some(2.0), Some(3.0)]),
+                Some(vec![Some(0.0), Some(0.0)]),
+            ]);
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from_iter_values(0..4)),
+                    Arc::new(vectors),
+                ],
+            )
+            .unwrap();
+            RecordBatchIterator::new(vec![Ok(batch)], schema)
+        };
+
+        let options = WriteOptions::default().on_bad_vectors(BadVectorHandling::Error);
+        let result = table.add(data()).write_options(options).execute().await;
+        assert!(result.is_err());
+        assert_eq!(table.count_rows(None).await.unwrap(), 0);
+
+        // Zero vectors are only bad under cosine distance
+        let options = WriteOptions::default()
+            .on_bad_vectors(BadVectorHandling::Drop)
+            .vector_distance_type(DistanceType::Cosine);
+        let result = table
+            .add(data())
+            .write_options(options)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.num_bad_vectors, 3);
+        assert_eq!(result.num_inserted_rows, 1);
+
+        let options = WriteOptions::default().on_bad_vectors(BadVectorHandling::Fill(0.5));
+        let result = table
+            .add(data())
+            .write_options(options)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.num_bad_vectors, 2);
+        assert_eq!(result.num_inserted_rows, 4);
+        assert_eq!(
+            table
+                .count_rows(Some("vector IS NULL".to_string()))
+                .await
+                .unwrap(),
+            0
+        );
+
+        let options = WriteOptions::default().on_bad_vectors(BadVectorHandling::Null);
+        let result = table
+            .add(data())
+            .write_options(options)
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(result.num_bad_vectors, 2);
+        assert_eq!(result.num_inserted_rows, 4);
+        assert_eq!(table.count_rows(None).await.unwrap(), 9);
+        assert_eq!(
+            table
+                .count_rows(Some("vector IS NULL".to_string()))
+                .await
+                .unwrap(),
+            2
+        );
+
+        // Null is rejected up front for a column that is not nullable
+        let vector_type =
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), 2);
+        let required = conn
+            .create_empty_table(
+                "required",
+                Arc::new(Schema::new(vec![
+                    Field::new("id", DataType::Int32, false),
+                    Field::new("vector", vector_type, false),
+                ])),
+            )
+            .execute()
+            .await
+            .unwrap();
+        let options = WriteOptions::default().on_bad_vectors(BadVectorHandling::Null);
+        let result = required.add(data()).write_options(options).execute().await;
+        assert!(matches!(result, Err(Error::InvalidInput { .. })));
     }
 
     #[tokio::test]
@@ -2220,8 +4886,10 @@ mod tests {
         // Perform a "insert if not exists"
         let mut merge_insert_builder = table.merge_insert(&["i"]);
         merge_insert_builder.when_not_matched_insert_all();
-        merge_insert_builder.execute(new_batches).await.unwrap();
+        let result = merge_insert_builder.execute(new_batches).await.unwrap();
         // Only 5 rows should actually be inserted
+        assert_eq!(result.num_inserted_rows, 5);
+        assert_eq!(result.num_updated_rows, 0);
         assert_eq!(table.count_rows(None).await.unwrap(), 15);
 
         // Create new data with i=15..25 (no id matches)
@@ -2241,11 +4909,205 @@ mod tests {
         let new_batches = Box::new(merge_insert_test_batches(5, 3));
         let mut merge_insert_builder = table.merge_insert(&["i"]);
         merge_insert_builder.when_matched_update_all(Some("target.age = 0".to_string()));
-        merge_insert_builder.execute(new_batches).await.unwrap();
+        let result = merge_insert_builder.execute(new_batches).await.unwrap();
+        assert_eq!(result.num_inserted_rows, 0);
+        assert_eq!(result.num_updated_rows, 5);
+        assert_eq!(result.version, table.version().await.unwrap());
+        assert_eq!(
+            table.count_rows(Some("age = 3".to_string())).await.unwrap(),
+            5
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_insert_partial_update() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // Create a dataset with i=0..10 and a column the updates never send
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .add_columns(
+                NewColumnTransform::SqlExpressions(vec![("score".into(), "i * 10".into())]),
+                None,
+            )
+            .await
+            .unwrap();
+
+        // The new data only has the key and the updated column
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_matched_update_columns(&["age"], None);
+        let result = merge_insert_builder
+            .execute(Box::new(merge_insert_test_batches(5, 1)))
+            .await
+            .unwrap();
+        assert_eq!(result.num_updated_rows, 5);
+        assert_eq!(result.num_inserted_rows, 0);
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+        assert_eq!(
+            table.count_rows(Some("age = 1".to_string())).await.unwrap(),
+            5
+        );
+        assert_eq!(
+            table
+                .count_rows(Some("score = i * 10".to_string()))
+                .await
+                .unwrap(),
+            10
+        );
+
+        // Expressions can combine the source and target values
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_matched_update(
+            [("age", "target.age + source.age * 2")],
+            Some("target.i < 7".to_string()),
+        );
+        merge_insert_builder
+            .execute(Box::new(merge_insert_test_batches(0, 1)))
+            .await
+            .unwrap();
+        assert_eq!(
+            table.count_rows(Some("age = 2".to_string())).await.unwrap(),
+            5
+        );
         assert_eq!(
             table.count_rows(Some("age = 3".to_string())).await.unwrap(),
+            2
+        );
+
+        // Inserted rows get nulls for the columns the new data doesn't have
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder
+            .when_matched_update_columns(&["age"], None)
+            .when_not_matched_insert_all();
+        let result = merge_insert_builder
+            .execute(Box::new(merge_insert_test_batches(5, 4)))
+            .await
+            .unwrap();
+        assert_eq!(result.num_updated_rows, 5);
+        assert_eq!(result.num_inserted_rows, 5);
+        assert_eq!(table.count_rows(None).await.unwrap(), 15);
+        assert_eq!(
+            table.count_rows(Some("age = 4".to_string())).await.unwrap(),
+            10
+        );
+        assert_eq!(
+            table
+                .count_rows(Some("score IS NULL".to_string()))
+                .await
+                .unwrap(),
             5
         );
+        assert_eq!(
+            table
+                .count_rows(Some("score = i * 10".to_string()))
+                .await
+                .unwrap(),
+            10
+        );
+
+        // The new data is processed a batch at a time
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int32, false),
+            Field::new("age", DataType::Int32, false),
+        ]));
+        let batches = (0..3)
+            .map(|batch| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![
+                        Arc::new(Int32Array::from_iter_values(batch * 5..(batch + 1) * 5)),
+                        Arc::new(Int32Array::from_iter_values(iter::repeat(5).take(5))),
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut merge_insert_builder = table.merge_insert(&["i"]);
+        merge_insert_builder.when_matched_update([("age", "source.age + target.i")], None);
+        let result = merge_insert_builder
+            .execute(Box::new(RecordBatchIterator::new(batches, schema)))
+            .await
+            .unwrap();
+        assert_eq!(result.num_updated_rows, 15);
+        assert_eq!(
+            table
+                .count_rows(Some("age = i + 5".to_string()))
+                .await
+                .unwrap(),
+            15
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        // Create a dataset with i=0..10
+        let table = conn
+            .create_table("my_table", merge_insert_test_batches(0, 0))
+            .execute()
+            .await
+            .unwrap();
+        let version = table.version().await.unwrap();
+
+        // Nothing is applied on rollback
+        table.transaction().delete("true").rollback();
+        assert_eq!(table.count_rows(None).await.unwrap(), 10);
+
+        let mut upsert = table.merge_insert(&["i"]);
+        upsert
+            .when_matched_update_all(None)
+            .when_not_matched_insert_all();
+        let result = table
+            .transaction()
+            .delete("i < 3")
+            .add(merge_insert_test_batches(100, 5))
+            .merge_insert(upsert, Box::new(merge_insert_test_batches(5, 1)))
+            .update([("age", "age + 10")], Some("i = 100".to_string()))
+            .commit()
+            .await
+            .unwrap();
+
+        // All of the operations are committed as one version
+        assert_eq!(result.version, version + 1);
+        assert_eq!(table.version().await.unwrap(), version + 1);
+        assert_eq!(result.num_deleted_rows, 3);
+        assert_eq!(result.num_inserted_rows, 15);
+        assert_eq!(result.num_updated_rows, 6);
+        assert_eq!(result.num_fragments_written, 1);
+
+        assert_eq!(table.count_rows(None).await.unwrap(), 22);
+        assert_eq!(
+            table.count_rows(Some("age = 0".to_string())).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            table.count_rows(Some("age = 1".to_string())).await.unwrap(),
+            10
+        );
+        assert_eq!(
+            table.count_rows(Some("age = 5".to_string())).await.unwrap(),
+            9
+        );
+        assert_eq!(
+            table
+                .count_rows(Some("i = 100 AND age = 15".to_string()))
+                .await
+                .unwrap(),
+            1
+        );
+
+        // An empty transaction does not create a version
+        let result = table.transaction().commit().await.unwrap();
+        assert_eq!(result.version, version + 1);
+        assert_eq!(table.version().await.unwrap(), version + 1);
     }
 
     #[tokio::test]
@@ -2270,12 +5132,14 @@ mod tests {
         let new_batches = RecordBatchIterator::new(batches.clone(), schema.clone());
 
         // Can overwrite using AddDataOptions::mode
-        table
+        let result = table
             .add(new_batches)
             .mode(AddDataMode::Overwrite)
             .execute()
             .await
             .unwrap();
+        assert_eq!(result.num_inserted_rows, 10);
+        assert_eq!(result.num_deleted_rows, 10);
         assert_eq!(table.count_rows(None).await.unwrap(), 10);
         assert_eq!(table.name(), "test");
 
@@ -2292,6 +5156,7 @@ mod tests {
             .add(new_batches)
             .write_options(WriteOptions {
                 lance_write_params: Some(param),
+                ..Default::default()
             })
             .mode(AddDataMode::Append)
             .execute()
@@ -2880,6 +5745,137 @@ mod tests {
         assert_eq!(stats.num_unindexed_rows, 0);
     }
 
+    #[tokio::test]
+    async fn test_create_named_vector_index() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+
+        let dimension = 16;
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "embeddings",
+            DataType::FixedSizeList(
+                Arc::new(Field::new("item", DataType::Float32, true)),
+                dimension,
+            ),
+            false,
+        )]));
+        let mut rng = rand::thread_rng();
+        let float_arr = Float32Array::from(
+            iter::repeat_with(|| rng.gen::<f32>())
+                .take(512 * dimension as usize)
+                .collect::<Vec<f32>>(),
+        );
+        let vectors = Arc::new(create_fixed_size_list(float_arr, dimension).unwrap());
+        let batches = RecordBatchIterator::new(
+            vec![RecordBatch::try_new(schema.clone(), vec![vectors]).unwrap()]
+                .into_iter()
+                .map(Ok),
+            schema,
+        );
+        let table = conn.create_table("test", batches).execute().await.unwrap();
+
+        table
+            .create_index(&["embeddings"], Index::IvfPq(Default::default()))
+            .name("pq_idx")
+            .execute()
+            .await
+            .unwrap();
+        table
+            .create_index(&["embeddings"], Index::IvfHnswSq(Default::default()))
+            .name("hnsw_idx")
+            .execute()
+            .await
+            .unwrap();
+
+        // Re-using a name without replace is an error
+        assert!(table
+            .create_index(&["embeddings"], Index::IvfPq(Default::default()))
+            .name("pq_idx")
+            .replace(false)
+            .execute()
+            .await
+            .is_err());
+
+        let mut index_configs = table.list_indices().await.unwrap();
+        index_configs.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(index_configs.len(), 2);
+        assert_eq!(index_configs[0].name, "hnsw_idx");
+        assert_eq!(
+            index_configs[0].index_type,
+            crate::index::IndexType::IvfHnswSq
+        );
+        assert_eq!(index_configs[1].name, "pq_idx");
+        assert_eq!(index_configs[1].index_type, crate::index::IndexType::IvfPq);
+
+        // Rows added after the indices are built are found by a flat search
+        let new_vectors = Arc::new(
+            create_fixed_size_list(Float32Array::from(vec![0.5; dimension as usize]), dimension)
+                .unwrap(),
+        );
+        let schema = table.schema().await.unwrap();
+        table
+            .add(RecordBatchIterator::new(
+                vec![Ok(
+                    RecordBatch::try_new(schema.clone(), vec![new_vectors]).unwrap()
+                )],
+                schema,
+            ))
+            .execute()
+            .await
+            .unwrap();
+
+        let query = vec![0.5; dimension as usize];
+        for index_name in ["pq_idx", "hnsw_idx"] {
+            let results = table
+                .query()
+                .nearest_to(query.as_slice())
+                .unwrap()
+                .use_index(index_name)
+                .with_row_id()
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(results.iter().map(|b| b.num_rows()).sum::<usize>(), 10);
+            let batch = &results[0];
+            assert!(batch.column_by_name("_distance").is_some());
+            let row_ids = batch["_rowid"].as_primitive::<UInt64Type>();
+            assert_eq!(row_ids.value(0), 512, "index {}", index_name);
+
+            // Only the indexed rows are searched with fast search
+            let results = table
+                .query()
+                .nearest_to(query.as_slice())
+                .unwrap()
+                .use_index(index_name)
+                .fast_search()
+                .with_row_id()
+                .execute()
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert!(results.iter().all(|batch| batch["_rowid"]
+                .as_primitive::<UInt64Type>()
+                .values()
+                .iter()
+                .all(|row_id| *row_id < 512)));
+        }
+
+        let res = table
+            .query()
+            .nearest_to(query.as_slice())
+            .unwrap()
+            .use_index("no_such_idx")
+            .execute()
+            .await;
+        assert!(matches!(res, Err(Error::InvalidInput { .. })));
+    }
+
     fn create_fixed_size_list<T: Array>(values: T, list_size: i32) -> Result<FixedSizeListArray> {
         let list_type = DataType::FixedSizeList(
             Arc::new(Field::new("item", values.data_type().clone(), true)),
@@ -3131,6 +6127,146 @@ mod tests {
         assert_eq!(stats.distance_type, None);
     }
 
+    #[tokio::test]
+    async fn test_drop_index() {
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+
+        let conn = ConnectBuilder::new(uri).execute().await.unwrap();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("text", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..100)),
+                Arc::new(StringArray::from_iter_values(
+                    (0..100).map(|i| format!("text_{}", i)),
+                )),
+            ],
+        )
+        .unwrap();
+
+        let table = conn
+            .create_table(
+                "test_drop_index",
+                RecordBatchIterator::new(vec![Ok(batch.clone())], batch.schema()),
+            )
+            .execute()
+            .await
+            .unwrap();
+
+        table
+            .create_index(&["id"], Index::BTree(Default::default()))
+            .execute()
+            .await
+            .unwrap();
+        table
+            .create_index(&["text"], Index::FTS(Default::default()))
+            .execute()
+            .await
+            .unwrap();
+        assert_eq!(table.list_indices().await.unwrap().len(), 2);
+
+        let version = table.version().await.unwrap();
+        table.drop_index("text_idx").await.unwrap();
+        assert_eq!(table.version().await.unwrap(), version + 1);
+
+        let index_configs = table.list_indices().await.unwrap();
+        assert_eq!(index_configs.len(), 1);
+        assert_eq!(index_configs[0].name, "id_idx");
+        assert_eq!(table.index_stats("text_idx").await.unwrap(), None);
+
+        // Dropping an index that does not exist is an error
+        assert!(table.drop_index("text_idx").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_index_in_background() {
+        use crate::index::IndexBuildStatus;
+
+        let tmp_dir = tempdir().unwrap();
+        let uri = tmp_dir.path().to_str().unwrap();
+        let conn = connect(uri).execute().await.unwrap();
+        let table = conn
+            .create_table("my_table", some_sample_data())
+            .execute()
+            .await
+            .unwrap();
+
+        // Cancelling before the task gets a chance to run leaves the table unchanged
+        let handle = table
+            .create_index(&["i"], Index::BTree(Default::default()))
+            .execute_in_background();
+        assert!(matches!(
+            handle.cancel().await.unwrap(),
+            IndexBuildStatus::Cancelled
+        ));
+        assert!(table.list_indices().await.unwrap().is_empty());
+
+        // Cancelling a build that has already committed the index reports it as done
+        let handle = table
+            .create_index(&["i"], Index::BTree(Default::default()))
+            .execute_in_background();
+        while !handle.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(matches!(
+            handle.cancel().await.unwrap(),
+            IndexBuildStatus::Done
+        ));
+        assert_eq!(table.list_indices().await.unwrap().len(), 1);
+        table.drop_index("i_idx").await.unwrap();
+
+        let handle = table
+            .create_index(&["i"], Index::BTree(Default::default()))
+            .execute_in_background();
+        assert!(matches!(
+            handle.status(),
+            IndexBuildStatus::Running | IndexBuildStatus::Done
+        ));
+        while !handle.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(matches!(handle.status(), IndexBuildStatus::Done));
+        handle.wait().await.unwrap();
+
+        // A failed build keeps the error it failed with
+        let handle = table
+            .create_index(&["no_such_column"], Index::BTree(Default::default()))
+            .execute_in_background();
+        while !handle.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        match handle.status() {
+            IndexBuildStatus::Failed(e) => assert!(matches!(*e, Error::Arrow { .. }), "{}", e),
+            status => panic!("unexpected status {:?}", status),
+        }
+        let res = handle.wait().await;
+        assert!(matches!(res, Err(Error::Arrow { .. })));
+
+        table
+            .wait_for_index("i_idx", Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        // New rows are not covered by the index until it is optimized
+        table.add(some_sample_data()).execute().await.unwrap();
+        let res = table
+            .wait_for_index("i_idx", Duration::from_millis(10))
+            .await;
+        assert!(matches!(res, Err(Error::Timeout { .. })));
+        table
+            .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+            .await
+            .unwrap();
+        table
+            .wait_for_index("i_idx", Duration::from_secs(1))
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     async fn test_read_consistency_interval() {
         let intervals = vec![
@@ -0,0 +1,166 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Background maintenance of tables
+
+use std::sync::{Arc, Weak};
+
+use lance_index::optimize::OptimizeOptions;
+use log::{debug, warn};
+
+use crate::error::Result;
+
+use super::{CompactionOptions, Duration, NativeTable, OptimizeAction, TableInternal};
+
+/// A policy for optimizing a table in the background when it needs it
+///
+/// Instead of calling [`super::Table::optimize`] on a fixed schedule, the
+/// table is checked every [`Self::check_interval`] and each optimization only
+/// runs when its threshold is crossed:
+///
+/// * files are compacted when there are at least [`Self::max_small_files`]
+///   small files or [`Self::max_deleted_rows`] deleted rows, and old versions
+///   are then pruned if [`Self::prune_older_than`] is set
+/// * indices are optimized when any index has at least
+///   [`Self::max_unindexed_rows`] unindexed rows
+///
+/// The checks run in a background task, which stops once every handle to the
+/// table has been dropped.  Failures are logged and retried at the next check.
+/// Remote tables are maintained by the server, so the policy is ignored for
+/// them.
+///
+/// Set it for one table with
+/// [`crate::connection::OpenTableBuilder::auto_optimize`] or for every table
+/// of a connection with [`crate::connection::ConnectBuilder::auto_optimize`].
+#[derive(Debug, Clone)]
+pub struct AutoOptimize {
+    /// How often to check the thresholds.
+    ///
+    /// The default is 5 minutes.
+    pub check_interval: std::time::Duration,
+    /// Fragments with fewer rows than this are counted as small files.
+    ///
+    /// The default is 1024 * 1024 rows, the target size of a compacted file.
+    pub small_file_rows: usize,
+    /// Compact once the table has this many small files.
+    ///
+    /// The default is 16.
+    pub max_small_files: usize,
+    /// Compact once the table has this many deleted rows.
+    ///
+    /// The default is 100,000.
+    pub max_deleted_rows: usize,
+    /// Optimize the indices once any of them has this many unindexed rows.
+    ///
+    /// The default is 100,000.
+    pub max_unindexed_rows: usize,
+    /// Prune versions older than this after compacting.  If `None` then
+    /// versions are never pruned.
+    ///
+    /// The default is 7 days.
+    pub prune_older_than: Option<Duration>,
+}
+
+impl Default for AutoOptimize {
+    fn default() -> Self {
+        Self {
+            check_interval: std::time::Duration::from_secs(5 * 60),
+            small_file_rows: 1024 * 1024,
+            max_small_files: 16,
+            max_deleted_rows: 100_000,
+            max_unindexed_rows: 100_000,
+            prune_older_than: Some(Duration::try_days(7).expect("valid delta")),
+        }
+    }
+}
+
+impl AutoOptimize {
+    /// Start maintaining `table` in the background
+    pub(crate) fn spawn(self, table: &Arc<dyn TableInternal>) {
+        if table.as_native().is_none() {
+            debug!(
+                "Ignoring auto optimize for {}, remote tables are optimized by the server",
+                table.name()
+            );
+            return;
+        }
+        let table = Arc::downgrade(table);
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(self.check_interval).await;
+                let Some(table) = Weak::upgrade(&table) else {
+                    break;
+                };
+                let native = table.as_native().expect("checked before spawning");
+                if let Err(err) = self.run(native).await {
+                    warn!("Failed to optimize table {}: {}", native.name(), err);
+                }
+            }
+        });
+    }
+
+    /// Check the thresholds once and run the optimizations that are due
+    async fn run(&self, table: &NativeTable) -> Result<()> {
+        let num_small_files = table.num_small_files(self.small_file_rows).await?;
+        let num_deleted_rows = table.count_deleted_rows().await?;
+        if table.count_fragments().await? > 1
+            && (num_small_files >= self.max_small_files
+                || num_deleted_rows >= self.max_deleted_rows)
+        {
+            debug!(
+                "Compacting {} ({} small files, {} deleted rows)",
+                table.name(),
+                num_small_files,
+                num_deleted_rows
+            );
+            table
+                .optimize(OptimizeAction::Compact {
+                    options: CompactionOptions {
+                        target_rows_per_fragment: self.small_file_rows,
+                        ..Default::default()
+                    },
+                    remap_options: None,
+                })
+                .await?;
+            if let Some(older_than) = self.prune_older_than {
+                table
+                    .optimize(OptimizeAction::Prune {
+                        older_than: Some(older_than),
+                        delete_unverified: None,
+                        error_if_tagged_old_versions: None,
+                    })
+                    .await?;
+            }
+        }
+
+        for index in table.list_indices().await? {
+            let Some(stats) = table.index_stats(&index.name).await? else {
+                continue;
+            };
+            if stats.num_unindexed_rows >= self.max_unindexed_rows {
+                debug!(
+                    "Optimizing the indices of {} ({} unindexed rows in {})",
+                    table.name(),
+                    stats.num_unindexed_rows,
+                    index.name
+                );
+                table
+                    .optimize(OptimizeAction::Index(OptimizeOptions::default()))
+                    .await?;
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection and handling of bad vectors in data written to a table
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use arrow::buffer::NullBuffer;
+use arrow::compute::{concat, filter_record_batch};
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_array::{
+    new_null_array, Array, ArrayRef, BooleanArray, FixedSizeListArray, Float64Array, RecordBatch,
+    RecordBatchReader,
+};
+use arrow_cast::cast;
+use arrow_schema::{ArrowError, DataType, Field, FieldRef, Schema, SchemaRef};
+
+use crate::error::{Error, Result};
+use crate::index::IndexType;
+use crate::DistanceType;
+
+use super::{BadVectorHandling, TableInternal, WriteOptions};
+
+/// A vector column of the table that the data is checked against
+struct VectorColumn {
+    /// The index of the column in the data
+    index: usize,
+    /// The field of the column in the table
+    field: FieldRef,
+    /// Whether all-zero vectors are bad, because they are searched by cosine
+    /// distance
+    reject_zeros: bool,
+}
+
+/// Wraps the data of a write, checking each vector column for vectors that are
+/// not finite, have the wrong dimension, or (for cosine distance) are all zero
+pub(crate) struct BadVectorReader {
+    inner: Box<dyn RecordBatchReader + Send>,
+    schema: SchemaRef,
+    columns: Vec<VectorColumn>,
+    handling: BadVectorHandling,
+    num_bad_vectors: Arc<AtomicU64>,
+}
+
+impl BadVectorReader {
+    /// Wrap `data` if `options` asks for bad vectors to be handled
+    ///
+    /// Returns the data to write and a counter of the bad vectors found, which
+    /// is complete once the data has been consumed.
+    pub(crate) async fn wrap(
+        table: &dyn TableInternal,
+        options: &WriteOptions,
+        data: Box<dyn RecordBatchReader + Send>,
+    ) -> Result<(Box<dyn RecordBatchReader + Send>, Arc<AtomicU64>)> {
+        let num_bad_vectors = Arc::new(AtomicU64::new(0));
+        let Some(handling) = options.on_bad_vectors.clone() else {
+            return Ok((data, num_bad_vectors));
+        };
+
+        let table_schema = table.schema().await?;
+        let data_schema = data.schema();
+        let vector_fields = data_schema
+            .fields()
+            .iter()
+            .enumerate()
+            .filter_map(|(index, data_field)| {
+                let field = table_schema.field_with_name(data_field.name()).ok()?;
+                match field.data_type() {
+                    DataType::FixedSizeList(child, _) if child.data_type().is_floating() => {
+                        Some((index, data_field, field))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if vector_fields.is_empty() {
+            return Ok((data, num_bad_vectors));
+        }
+        if matches!(handling, BadVectorHandling::Null) {
+            if let Some((_, _, field)) = vector_fields.iter().find(|(_, _, f)| !f.is_nullable()) {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "bad vectors cannot be replaced with null, the column '{}' is not \
+                         nullable",
+                        field.name()
+                    ),
+                });
+            }
+        }
+
+        // The distance type of a column is that of its vector index, if any
+        let mut cosine_columns = Vec::new();
+        if options.vector_distance_type.is_none() {
+            for index in table.list_indices().await? {
+                let is_vector_index = matches!(
+                    index.index_type,
+                    IndexType::IvfPq | IndexType::IvfHnswPq | IndexType::IvfHnswSq
+                );
+                let on_vector_field = index.columns.iter().any(|column| {
+                    vector_fields
+                        .iter()
+                        .any(|(_, _, field)| field.name() == column)
+                });
+                if !is_vector_index || !on_vector_field {
+                    continue;
+                }
+                if let Some(stats) = table.index_stats(&index.name).await? {
+                    if stats.distance_type == Some(DistanceType::Cosine) {
+                        cosine_columns.extend(index.columns);
+                    }
+                }
+            }
+        }
+
+        let mut columns = Vec::new();
+        let mut fields = data_schema.fields().iter().cloned().collect::<Vec<_>>();
+        for (index, data_field, field) in vector_fields {
+            let reject_zeros = match options.vector_distance_type {
+                Some(distance_type) => distance_type == DistanceType::Cosine,
+                None => cosine_columns.contains(field.name()),
+            };
+            // Lists are converted to the fixed size lists of the table
+            fields[index] = Arc::new(
+                Field::new(
+                    data_field.name(),
+                    field.data_type().clone(),
+                    data_field.is_nullable() || matches!(handling, BadVectorHandling::Null),
+                )
+                .with_metadata(data_field.metadata().clone()),
+            );
+            columns.push(VectorColumn {
+                index,
+                field: Arc::new(field.clone()),
+                reject_zeros,
+            });
+        }
+
+        let schema = Arc::new(Schema::new_with_metadata(
+            fields,
+            data_schema.metadata().clone(),
+        ));
+        let reader = Self {
+            inner: data,
+            schema,
+            columns,
+            handling,
+            num_bad_vectors: num_bad_vectors.clone(),
+        };
+        Ok((Box::new(reader), num_bad_vectors))
+    }
+
+    fn process(&self, batch: RecordBatch) -> std::result::Result<RecordBatch, ArrowError> {
+        let mut arrays = batch.columns().to_vec();
+        let mut keep = vec![true; batch.num_rows()];
+        for column in &self.columns {
+            let (array, bad_rows) = self.process_column(column, &arrays[column.index])?;
+            for row in bad_rows {
+                keep[row] = false;
+            }
+            arrays[column.index] = array;
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), arrays)?;
+        if keep.iter().all(|keep| *keep) {
+            Ok(batch)
+        } else {
+            filter_record_batch(&batch, &BooleanArray::from(keep))
+        }
+    }
+
+    /// Check and convert one vector column, returning the rows to drop
+    fn process_column(
+        &self,
+        column: &VectorColumn,
+        array: &ArrayRef,
+    ) -> std::result::Result<(ArrayRef, Vec<usize>), ArrowError> {
+        let DataType::FixedSizeList(child_field, dim) = column.field.data_type() else {
+            unreachable!("vector columns are fixed size lists");
+        };
+        let dim = *dim as usize;
+        let name = column.field.name();
+
+        // The offsets and lengths of each row's values, and the values as f64
+        let (ranges, values, nulls): (Vec<(usize, usize)>, ArrayRef, Option<NullBuffer>) =
+            match array.data_type() {
+                DataType::FixedSizeList(_, size) => {
+                    let list = array.as_fixed_size_list();
+                    let size = *size as usize;
+                    (
+                        (0..list.len())
+                            .map(|row| (list.value_offset(row) as usize, size))
+                            .collect(),
+                        list.values().clone(),
+                        list.nulls().cloned(),
+                    )
+                }
+                DataType::List(_) => {
+                    let list = array.as_list::<i32>();
+                    (
+                        list.offsets()
+                            .windows(2)
+                            .map(|w| (w[0] as usize, (w[1] - w[0]) as usize))
+                            .collect(),
+                        list.values().clone(),
+                        list.nulls().cloned(),
+                    )
+                }
+                DataType::LargeList(_) => {
+                    let list = array.as_list::<i64>();
+                    (
+                        list.offsets()
+                            .windows(2)
+                            .map(|w| (w[0] as usize, (w[1] - w[0]) as usize))
+                            .collect(),
+                        list.values().clone(),
+                        list.nulls().cloned(),
+                    )
+                }
+                other => {
+                    return Err(ArrowError::InvalidArgumentError(format!(
+                        "vector column '{}' must be a list of floats, got {}",
+                        name, other
+                    )))
+                }
+            };
+        let checked = cast(&values, &DataType::Float64)?;
+        let checked = checked.as_primitive::<Float64Type>();
+
+        let mut bad_rows = Vec::new();
+        for (row, (offset, len)) in ranges.iter().enumerate() {
+            if nulls.as_ref().map(|n| n.is_null(row)).unwrap_or(false) {
+                continue;
+            }
+            let reason = if *len != dim {
+                Some(format!("has dimension {} instead of {}", len, dim))
+            } else {
+                let vector = &checked.values()[*offset..*offset + *len];
+                if vector.iter().any(|v| !v.is_finite()) {
+                    Some("contains NaN or infinite values".to_string())
+                } else if column.reject_zeros && vector.iter().all(|v| *v == 0.0) {
+                    Some("is all zeros, which has no cosine distance".to_string())
+                } else {
+                    None
+                }
+            };
+            if let Some(reason) = reason {
+                if matches!(self.handling, BadVectorHandling::Error) {
+                    return Err(ArrowError::InvalidArgumentError(format!(
+                        "the vector in row {} of column '{}' {}",
+                        row, name, reason
+                    )));
+                }
+                bad_rows.push(row);
+            }
+        }
+        self.num_bad_vectors
+            .fetch_add(bad_rows.len() as u64, Ordering::Relaxed);
+
+        let is_fixed_size = matches!(
+            array.data_type(),
+            DataType::FixedSizeList(_, size) if *size as usize == dim
+        );
+        if bad_rows.is_empty() && is_fixed_size {
+            return Ok((cast(array, column.field.data_type())?, Vec::new()));
+        }
+
+        // Rebuild the column, replacing bad vectors as requested
+        let filler = match self.handling {
+            BadVectorHandling::Fill(value) => {
+                Arc::new(Float64Array::from(vec![value as f64; dim])) as ArrayRef
+            }
+            _ => Arc::new(Float64Array::from(vec![0.0; dim])) as ArrayRef,
+        };
+        let filler = cast(&filler, child_field.data_type())?;
+        let values = cast(&values, child_field.data_type())?;
+        let mut pieces = Vec::with_capacity(ranges.len());
+        let mut validity = Vec::with_capacity(ranges.len());
+        let mut bad = bad_rows.iter().peekable();
+        for (row, (offset, len)) in ranges.iter().enumerate() {
+            let is_null = nulls.as_ref().map(|n| n.is_null(row)).unwrap_or(false);
+            if bad.peek() == Some(&&row) {
+                bad.next();
+                pieces.push(filler.clone());
+                validity.push(!matches!(self.handling, BadVectorHandling::Null));
+            } else if is_null || *len != dim {
+                pieces.push(new_null_array(child_field.data_type(), dim));
+                validity.push(false);
+            } else {
+                pieces.push(values.slice(*offset, *len));
+                validity.push(true);
+            }
+        }
+        let pieces = pieces.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
+        let values = if pieces.is_empty() {
+            new_null_array(child_field.data_type(), 0)
+        } else {
+            concat(&pieces)?
+        };
+        let nulls = NullBuffer::from(validity);
+        let nulls = (nulls.null_count() > 0).then_some(nulls);
+        let array = FixedSizeListArray::try_new(child_field.clone(), dim as i32, values, nulls)?;
+
+        let drop_rows = match self.handling {
+            BadVectorHandling::Drop => bad_rows,
+            _ => Vec::new(),
+        };
+        Ok((Arc::new(array), drop_rows))
+    }
+}
+
+impl Iterator for BadVectorReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|batch| batch.and_then(|batch| self.process(batch)))
+    }
+}
+
+impl RecordBatchReader for BadVectorReader {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
@@ -18,7 +18,14 @@ use std::{
     time::{self, Duration, Instant},
 };
 
+use async_trait::async_trait;
+use lance::io::{ObjectStore, ObjectStoreParams};
 use lance::Dataset;
+use lance_table::format::{Index, Manifest};
+use lance_table::io::commit::{
+    commit_handler_from_url, CommitError, CommitHandler, ManifestNamingScheme, ManifestWriter,
+};
+use object_store::path::Path;
 use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::error::Result;
@@ -301,3 +308,53 @@ impl DerefMut for DatasetWriteGuard<'_> {
         }
     }
 }
+
+/// A commit handler that only lets a write create the version after `expected`
+///
+/// Lance rebases a write onto compatible versions that were committed after the
+/// write started.  For writes with an expected version that would silently
+/// break a read-modify-write cycle, so this handler rejects every other target
+/// version and Lance reports a commit conflict instead.
+#[derive(Debug)]
+pub(crate) struct ExpectedVersionCommitHandler {
+    inner: Arc<dyn CommitHandler>,
+    expected: u64,
+}
+
+impl ExpectedVersionCommitHandler {
+    pub(crate) async fn try_new(
+        uri: &str,
+        store_params: &ObjectStoreParams,
+        expected: u64,
+    ) -> Result<Self> {
+        let inner = commit_handler_from_url(uri, &Some(store_params.clone())).await?;
+        Ok(Self { inner, expected })
+    }
+}
+
+#[async_trait]
+impl CommitHandler for ExpectedVersionCommitHandler {
+    async fn commit(
+        &self,
+        manifest: &mut Manifest,
+        indices: Option<Vec<Index>>,
+        base_path: &Path,
+        object_store: &ObjectStore,
+        manifest_writer: ManifestWriter,
+        naming_scheme: ManifestNamingScheme,
+    ) -> std::result::Result<Path, CommitError> {
+        if manifest.version != self.expected + 1 {
+            return Err(CommitError::CommitConflict);
+        }
+        self.inner
+            .commit(
+                manifest,
+                indices,
+                base_path,
+                object_store,
+                manifest_writer,
+                naming_scheme,
+            )
+            .await
+    }
+}
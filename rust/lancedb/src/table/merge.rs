@@ -12,13 +12,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use arrow_array::RecordBatchReader;
+use arrow::compute::{concat_batches, filter_record_batch, take_record_batch};
+use arrow::row::{RowConverter, SortField};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type, UInt64Type, UInt8Type,
+};
+use arrow_array::{
+    new_null_array, Array, ArrayRef, RecordBatch, RecordBatchIterator, RecordBatchReader,
+    StructArray, UInt32Array,
+};
+use arrow_cast::cast;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use datafusion_common::DataFusionError;
+use datafusion_physical_plan::stream::RecordBatchStreamAdapter;
+use datafusion_physical_plan::SendableRecordBatchStream;
+use futures::TryStreamExt;
+use lance::dataset::Dataset;
+use lance_datafusion::planner::Planner;
 
-use crate::Result;
+use crate::embeddings::{
+    get_embedding_functions, EmbeddingDefinition, EmbeddingFunction, EmbeddingRegistry,
+    WithEmbeddings,
+};
+use crate::{Error, Result};
 
-use super::TableInternal;
+use super::{RetryPolicy, TableDefinition, TableInternal, WriteResult};
 
 /// A builder used to create and run a merge insert operation
 ///
@@ -29,21 +51,35 @@ pub struct MergeInsertBuilder {
     pub(crate) on: Vec<String>,
     pub(crate) when_matched_update_all: bool,
     pub(crate) when_matched_update_all_filt: Option<String>,
+    pub(crate) when_matched_update: Option<Vec<(String, String)>>,
+    pub(crate) when_matched_update_filt: Option<String>,
     pub(crate) when_not_matched_insert_all: bool,
     pub(crate) when_not_matched_by_source_delete: bool,
     pub(crate) when_not_matched_by_source_delete_filt: Option<String>,
+    pub(crate) expected_version: Option<u64>,
+    pub(crate) conflict_retry: Option<RetryPolicy>,
+    pub(crate) embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
 }
 
 impl MergeInsertBuilder {
-    pub(super) fn new(table: Arc<dyn TableInternal>, on: Vec<String>) -> Self {
+    pub(super) fn new(
+        table: Arc<dyn TableInternal>,
+        on: Vec<String>,
+        embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    ) -> Self {
         Self {
             table,
             on,
             when_matched_update_all: false,
             when_matched_update_all_filt: None,
+            when_matched_update: None,
+            when_matched_update_filt: None,
             when_not_matched_insert_all: false,
             when_not_matched_by_source_delete: false,
             when_not_matched_by_source_delete_filt: None,
+            expected_version: None,
+            conflict_retry: None,
+            embedding_registry,
         }
     }
 
@@ -70,9 +106,77 @@ impl MergeInsertBuilder {
     pub fn when_matched_update_all(&mut self, condition: Option<String>) -> &mut Self {
         self.when_matched_update_all = true;
         self.when_matched_update_all_filt = condition;
+        self.when_matched_update = None;
+        self.when_matched_update_filt = None;
         self
     }
 
+    /// Rows that exist in both the source table (new data) and
+    /// the target table (old data) will be updated, but only the
+    /// listed columns are changed.
+    ///
+    /// Each update is a column name and an SQL expression giving the
+    /// new value, like the SET clause of an SQL UPDATE.  Use the prefix
+    /// source. to refer to values in the source table (new data) and
+    /// the prefix target. to refer to values in the target table (old
+    /// data).  For example, `("count", "target.count + source.count")`.
+    ///
+    /// The source data only needs to contain the `on` columns and the
+    /// columns referenced by the expressions, so large columns such as
+    /// vectors do not need to be sent when they have not changed.
+    ///
+    /// An optional condition may be given, with the same meaning as in
+    /// [`Self::when_matched_update_all`].
+    ///
+    /// When combined with [`Self::when_not_matched_insert_all`] the new rows
+    /// take the values of the source data, and columns that are missing from
+    /// the source data are null (it is an error if they are not nullable).
+    /// Local tables then rewrite the matched rows in full, so all of their
+    /// columns are read.
+    ///
+    /// Local tables find the matched rows with a filter on the `on` columns,
+    /// so a scalar index on those columns makes this faster.
+    ///
+    /// This replaces any previous call to [`Self::when_matched_update_all`].
+    /// Local tables cannot combine a partial update with
+    /// [`Self::when_not_matched_by_source_delete`].
+    pub fn when_matched_update(
+        &mut self,
+        updates: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        condition: Option<String>,
+    ) -> &mut Self {
+        self.when_matched_update = Some(
+            updates
+                .into_iter()
+                .map(|(column, expr)| (column.into(), expr.into()))
+                .collect(),
+        );
+        self.when_matched_update_filt = condition;
+        self.when_matched_update_all = false;
+        self.when_matched_update_all_filt = None;
+        self
+    }
+
+    /// Rows that exist in both the source table (new data) and the
+    /// target table (old data) will have the listed columns replaced
+    /// by the values from the source table.
+    ///
+    /// This is shorthand for [`Self::when_matched_update`] with an
+    /// expression of `source.<column>` for each column.
+    pub fn when_matched_update_columns(
+        &mut self,
+        columns: &[impl AsRef<str>],
+        condition: Option<String>,
+    ) -> &mut Self {
+        self.when_matched_update(
+            columns.iter().map(|column| {
+                let column = column.as_ref();
+                (column.to_string(), format!("source.{}", column))
+            }),
+            condition,
+        )
+    }
+
     /// Rows that exist only in the source table (new data) should
     /// be inserted into the target table.
     pub fn when_not_matched_insert_all(&mut self) -> &mut Self {
@@ -95,10 +199,598 @@ impl MergeInsertBuilder {
         self
     }
 
+    /// Only run the merge if the table is still at the given version
+    ///
+    /// If another writer has committed a new version in the meantime the
+    /// merge fails with [`crate::Error::CommitConflict`] and nothing is
+    /// written.
+    ///
+    /// This is not yet supported on LanceDB Cloud.
+    pub fn expected_version(&mut self, version: u64) -> &mut Self {
+        self.expected_version = Some(version);
+        self
+    }
+
+    /// Retry the merge according to `policy` if it loses a commit race
+    ///
+    /// A merge with an expected version is never retried.
+    pub fn conflict_retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.conflict_retry = Some(policy);
+        self
+    }
+
     /// Executes the merge insert operation
     ///
-    /// Nothing is returned but the [`super::Table`] is updated
-    pub async fn execute(self, new_data: Box<dyn RecordBatchReader + Send>) -> Result<()> {
-        self.table.clone().merge_insert(self, new_data).await
+    /// The [`super::Table`] is updated and the number of inserted, updated
+    /// and deleted rows is returned
+    pub async fn execute(self, new_data: Box<dyn RecordBatchReader + Send>) -> Result<WriteResult> {
+        match self.conflict_retry.clone() {
+            Some(policy) if self.expected_version.is_none() => {
+                // Each attempt consumes the data so it is buffered up front
+                let schema = new_data.schema();
+                let batches = new_data.collect::<std::result::Result<Vec<_>, _>>()?;
+                policy
+                    .run(|| {
+                        let new_data = Box::new(RecordBatchIterator::new(
+                            batches.clone().into_iter().map(Ok),
+                            schema.clone(),
+                        ));
+                        self.table.merge_insert(self.clone(), new_data)
+                    })
+                    .await
+            }
+            _ => self.table.clone().merge_insert(self, new_data).await,
+        }
+    }
+}
+
+/// The most source rows whose matching target rows are looked up in one scan
+const MAX_KEYS_PER_SCAN: usize = 4096;
+
+/// Build the source for a partial-column update of a local table
+///
+/// Lance can update a subset of the columns when the source only contains
+/// those columns (and the `on` columns).  The update expressions are
+/// evaluated here, against the source row and (if referenced) the matching
+/// target row, and the result is the source that is handed to Lance.
+///
+/// When unmatched rows are also inserted Lance needs every column, so matched
+/// rows are completed with their target values and inserted rows with the
+/// source values, or nulls for the columns the source doesn't have.
+///
+/// The source is processed a batch at a time.  The matching target rows are
+/// found with a filter on the `on` columns, which uses a scalar index on them
+/// if there is one.
+pub(crate) fn partial_update_source(
+    dataset: Dataset,
+    params: &MergeInsertBuilder,
+    table_definition: &TableDefinition,
+    new_data: Box<dyn RecordBatchReader + Send>,
+) -> Result<SendableRecordBatchStream> {
+    let updates = params.when_matched_update.clone().unwrap_or_default();
+    let condition = params.when_matched_update_filt.clone();
+    let insert_all = params.when_not_matched_insert_all;
+    let on = params.on.clone();
+    let target_schema = Arc::new(Schema::from(dataset.schema()));
+    let source_schema = new_data.schema();
+
+    for column in on.iter().chain(updates.iter().map(|(column, _)| column)) {
+        if target_schema.field_with_name(column).is_err() {
+            return Err(Error::InvalidInput {
+                message: format!("column '{}' does not exist in the table", column),
+            });
+        }
+    }
+    let mut updated = on.clone();
+    for (column, _) in &updates {
+        if updated.contains(column) {
+            return Err(Error::InvalidInput {
+                message: format!(
+                    "column '{}' cannot be updated more than once or be both updated and matched on",
+                    column
+                ),
+            });
+        }
+        updated.push(column.clone());
+    }
+    for column in &on {
+        if source_schema.field_with_name(column).is_err() {
+            return Err(Error::InvalidInput {
+                message: format!("the new data is missing the 'on' column '{}'", column),
+            });
+        }
+    }
+
+    // Embeddings are computed from the values that are written, so those of
+    // updated or inserted source columns are recomputed
+    let embeddings = match &params.embedding_registry {
+        Some(registry) => get_embedding_functions(table_definition, registry.as_ref(), |ed| {
+            let dest = ed.dest_column_name();
+            if updated.contains(&dest) || target_schema.field_with_name(&dest).is_err() {
+                return false;
+            }
+            updated.contains(&ed.source_column)
+                || (insert_all
+                    && source_schema.field_with_name(&ed.source_column).is_ok()
+                    && source_schema.field_with_name(&dest).is_err())
+        })?,
+        None => vec![],
+    };
+    let embedded = embeddings
+        .iter()
+        .map(|(ed, _)| ed.dest_column_name())
+        .collect::<Vec<_>>();
+
+    let output_fields = if insert_all {
+        target_schema
+            .fields()
+            .iter()
+            .filter(|field| !embedded.contains(field.name()))
+            .cloned()
+            .collect::<Vec<_>>()
+    } else {
+        updated
+            .iter()
+            .map(|column| Arc::new(target_schema.field_with_name(column).unwrap().clone()))
+            .collect()
+    };
+    let output_schema = Arc::new(Schema::new(output_fields));
+    let final_schema = if insert_all {
+        target_schema.clone()
+    } else if embeddings.is_empty() {
+        output_schema.clone()
+    } else {
+        WithEmbeddings::new(
+            RecordBatchIterator::new(vec![], output_schema.clone()),
+            embeddings.clone(),
+        )
+        .schema()
+    };
+
+    // Only read the target columns that are needed, which keeps large
+    // columns such as vectors out of memory unless the rows are rewritten
+    let target_columns = if insert_all {
+        target_schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect()
+    } else {
+        let referenced = target_schema
+            .fields()
+            .iter()
+            .map(|field| field.name())
+            .filter(|name| {
+                updates
+                    .iter()
+                    .map(|(_, expr)| expr.as_str())
+                    .chain(condition.as_deref())
+                    .any(|expr| references_column(expr, "target", name))
+            })
+            .collect::<Vec<_>>();
+        if referenced.is_empty() {
+            vec![]
+        } else {
+            let mut columns = on.clone();
+            columns.extend(
+                referenced
+                    .into_iter()
+                    .filter(|name| !on.contains(name))
+                    .cloned(),
+            );
+            columns
+        }
+    };
+
+    let update = Arc::new(PartialUpdate {
+        dataset,
+        on,
+        updates,
+        condition,
+        insert_all,
+        target_columns,
+        output_schema,
+        embeddings,
+        final_schema: final_schema.clone(),
+    });
+    let stream = futures::stream::iter(new_data)
+        .map_err(Error::from)
+        .and_then(move |batch| {
+            let update = update.clone();
+            async move { update.process(batch).await }
+        })
+        .map_err(|e| DataFusionError::External(Box::new(e)));
+    Ok(Box::pin(RecordBatchStreamAdapter::new(
+        final_schema,
+        stream,
+    )))
+}
+
+/// The state of a partial-column update, see [`partial_update_source`]
+struct PartialUpdate {
+    dataset: Dataset,
+    on: Vec<String>,
+    updates: Vec<(String, String)>,
+    condition: Option<String>,
+    insert_all: bool,
+    /// The target columns to read for matched rows, empty if the target is
+    /// not needed
+    target_columns: Vec<String>,
+    /// The schema of the values built here, before embeddings are added
+    output_schema: SchemaRef,
+    embeddings: Vec<(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)>,
+    /// The schema of the source handed to Lance
+    final_schema: SchemaRef,
+}
+
+impl PartialUpdate {
+    async fn process(&self, batch: RecordBatch) -> Result<RecordBatch> {
+        let mut outputs = Vec::new();
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let len = MAX_KEYS_PER_SCAN.min(batch.num_rows() - offset);
+            outputs.push(self.process_chunk(batch.slice(offset, len)).await?);
+            offset += len;
+        }
+        let output = concat_batches(&self.output_schema, &outputs)?;
+        if self.embeddings.is_empty() {
+            return Ok(output.with_schema(self.final_schema.clone())?);
+        }
+
+        // Embedding functions may block on a model or a remote service
+        let embeddings = self.embeddings.clone();
+        let schema = self.output_schema.clone();
+        let embedded = tokio::task::spawn_blocking(move || {
+            WithEmbeddings::new(
+                RecordBatchIterator::new(vec![Ok(output)], schema),
+                embeddings,
+            )
+            .next()
+            .unwrap()
+        })
+        .await
+        .map_err(|e| Error::Runtime {
+            message: format!("computing embeddings failed: {}", e),
+        })??;
+        let indices = self
+            .final_schema
+            .fields()
+            .iter()
+            .map(|field| embedded.schema().index_of(field.name()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(embedded
+            .project(&indices)?
+            .with_schema(self.final_schema.clone())?)
+    }
+
+    async fn process_chunk(&self, source: RecordBatch) -> Result<RecordBatch> {
+        if self.target_columns.is_empty() {
+            // Unmatched rows are ignored by Lance, so every row is passed on
+            let combined = combine(&source, None)?;
+            return self.matched_output(&source, None, &combined);
+        }
+
+        let target = self.scan_matching(&source).await?;
+        let (source_indices, target_indices): (Vec<u32>, Vec<u32>) =
+            match_rows(&self.on, &source, &target)?.into_iter().unzip();
+        let matched_source =
+            take_record_batch(&source, &UInt32Array::from(source_indices.clone()))?;
+        let matched_target = take_record_batch(&target, &UInt32Array::from(target_indices))?;
+        let combined = combine(&matched_source, Some(&matched_target))?;
+        let matched = self.matched_output(&matched_source, Some(&matched_target), &combined)?;
+        if !self.insert_all {
+            return Ok(matched);
+        }
+
+        let matched_rows = source_indices.into_iter().collect::<HashSet<_>>();
+        let unmatched = UInt32Array::from_iter_values(
+            (0..source.num_rows() as u32).filter(|row| !matched_rows.contains(row)),
+        );
+        let unmatched = self.inserted_output(&take_record_batch(&source, &unmatched)?)?;
+        Ok(concat_batches(&self.output_schema, &[matched, unmatched])?)
+    }
+
+    /// Read the target rows whose keys appear in `source`
+    async fn scan_matching(&self, source: &RecordBatch) -> Result<RecordBatch> {
+        let mut scanner = self.dataset.scan();
+        scanner.project(&self.target_columns)?;
+        if let Some(filter) = key_filter(&self.on, source) {
+            scanner.filter(&filter)?;
+        }
+        Ok(scanner.try_into_batch().await?)
+    }
+
+    /// The new values of the matched rows that satisfy the condition
+    fn matched_output(
+        &self,
+        source: &RecordBatch,
+        target: Option<&RecordBatch>,
+        combined: &RecordBatch,
+    ) -> Result<RecordBatch> {
+        let planner = Planner::new(combined.schema());
+        let evaluate = |expr| -> Result<ArrayRef> {
+            let expr = planner.optimize_expr(expr)?;
+            let expr = planner.create_physical_expr(&expr)?;
+            Ok(expr
+                .evaluate(combined)
+                .and_then(|value| value.into_array(combined.num_rows()))
+                .map_err(lance::Error::from)?)
+        };
+
+        let mut columns = Vec::with_capacity(self.output_schema.fields().len());
+        for field in self.output_schema.fields() {
+            let values = match self
+                .updates
+                .iter()
+                .find(|(column, _)| column == field.name())
+            {
+                Some((_, expr)) => evaluate(planner.parse_expr(expr)?)?,
+                None if self.on.contains(field.name()) => {
+                    source.column_by_name(field.name()).unwrap().clone()
+                }
+                // Only full rows have other columns, and then the target is read
+                None => target
+                    .unwrap()
+                    .column_by_name(field.name())
+                    .unwrap()
+                    .clone(),
+            };
+            columns.push(cast(&values, field.data_type())?);
+        }
+        let mut output = RecordBatch::try_new(self.output_schema.clone(), columns)?;
+
+        if let Some(condition) = &self.condition {
+            let mask = evaluate(planner.parse_filter(condition)?)?;
+            let mask = mask.as_boolean_opt().ok_or_else(|| Error::InvalidInput {
+                message: format!("condition '{}' is not a boolean expression", condition),
+            })?;
+            output = filter_record_batch(&output, mask)?;
+        }
+        Ok(output)
+    }
+
+    /// The rows inserted for unmatched source rows, with nulls for the columns
+    /// the source doesn't have
+    fn inserted_output(&self, source: &RecordBatch) -> Result<RecordBatch> {
+        let columns = self
+            .output_schema
+            .fields()
+            .iter()
+            .map(|field| match source.column_by_name(field.name()) {
+                Some(values) => Ok(cast(values, field.data_type())?),
+                None if field.is_nullable() || source.num_rows() == 0 => {
+                    Ok(new_null_array(field.data_type(), source.num_rows()))
+                }
+                None => Err(Error::InvalidInput {
+                    message: format!(
+                        "the new data is missing the column '{}', which is needed to insert rows because it is not nullable",
+                        field.name()
+                    ),
+                }),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+/// A batch with a `source` struct column, and a `target` struct column if
+/// there is a target, that update expressions are evaluated against
+fn combine(source: &RecordBatch, target: Option<&RecordBatch>) -> Result<RecordBatch> {
+    let mut fields = vec![Field::new(
+        "source",
+        DataType::Struct(source.schema().fields().clone()),
+        false,
+    )];
+    let mut columns = vec![Arc::new(StructArray::from(source.clone())) as ArrayRef];
+    if let Some(target) = target {
+        fields.push(Field::new(
+            "target",
+            DataType::Struct(target.schema().fields().clone()),
+            false,
+        ));
+        columns.push(Arc::new(StructArray::from(target.clone())) as ArrayRef);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Whether `expr` refers to `column` of `prefix` (e.g. `target.price`) as a
+/// whole name, and not just as the start of a longer one
+fn references_column(expr: &str, prefix: &str, column: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    [
+        format!("{}.{}", prefix, column),
+        format!("{}.`{}`", prefix, column),
+    ]
+    .iter()
+    .any(|qualified| {
+        expr.match_indices(qualified.as_str()).any(|(start, _)| {
+            let before = expr[..start].chars().next_back();
+            let after = expr[start + qualified.len()..].chars().next();
+            !before.is_some_and(|c| is_ident(c) || c == '.' || c == '`')
+                && !after.is_some_and(is_ident)
+        })
+    })
+}
+
+/// An SQL filter that selects the rows with the same `on` values as a row
+/// of `source`
+///
+/// Returns `None` if a key has a type that can't be written as a literal,
+/// in which case every row has to be read.
+fn key_filter(on: &[String], source: &RecordBatch) -> Option<String> {
+    let keys = on
+        .iter()
+        .map(|column| source.column_by_name(column).cloned())
+        .collect::<Option<Vec<_>>>()?;
+    let column_names = on
+        .iter()
+        .map(|column| {
+            if column.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                column.clone()
+            } else {
+                format!("`{}`", column)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut rows = Vec::with_capacity(source.num_rows());
+    for row in 0..source.num_rows() {
+        // Null keys never match
+        if keys.iter().any(|key| key.is_null(row)) {
+            continue;
+        }
+        let values = keys
+            .iter()
+            .map(|key| sql_literal(key.as_ref(), row))
+            .collect::<Option<Vec<_>>>()?;
+        rows.push(values);
+    }
+    if rows.is_empty() {
+        return Some("false".to_string());
+    }
+    if let [column] = column_names.as_slice() {
+        let values = rows.into_iter().map(|mut row| row.remove(0));
+        return Some(format!(
+            "{} IN ({})",
+            column,
+            values.collect::<Vec<_>>().join(", ")
+        ));
+    }
+    Some(
+        rows.into_iter()
+            .map(|row| {
+                let terms = column_names
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| format!("{} = {}", column, value));
+                format!("({})", terms.collect::<Vec<_>>().join(" AND "))
+            })
+            .collect::<Vec<_>>()
+            .join(" OR "),
+    )
+}
+
+/// `array[row]` as an SQL literal, for the integer, string and boolean types
+fn sql_literal(array: &dyn Array, row: usize) -> Option<String> {
+    macro_rules! integer {
+        ($t:ty) => {
+            Some(array.as_primitive::<$t>().value(row).to_string())
+        };
+    }
+    match array.data_type() {
+        DataType::Int8 => integer!(Int8Type),
+        DataType::Int16 => integer!(Int16Type),
+        DataType::Int32 => integer!(Int32Type),
+        DataType::Int64 => integer!(Int64Type),
+        DataType::UInt8 => integer!(UInt8Type),
+        DataType::UInt16 => integer!(UInt16Type),
+        DataType::UInt32 => integer!(UInt32Type),
+        DataType::UInt64 => integer!(UInt64Type),
+        DataType::Boolean => Some(array.as_boolean().value(row).to_string()),
+        DataType::Utf8 => Some(quote_string(array.as_string::<i32>().value(row))),
+        DataType::LargeUtf8 => Some(quote_string(array.as_string::<i64>().value(row))),
+        _ => None,
+    }
+}
+
+fn quote_string(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Find the rows of `source` whose `on` columns match a row of `target`
+///
+/// Returns `(source row, target row)` pairs.  If several target rows share a
+/// key the last of them is matched.
+pub(crate) fn match_rows(
+    on: &[String],
+    source: &RecordBatch,
+    target: &RecordBatch,
+) -> Result<Vec<(u32, u32)>> {
+    let target_schema = target.schema();
+    let key_types = on
+        .iter()
+        .map(|column| Ok(target_schema.field_with_name(column)?.data_type().clone()))
+        .collect::<Result<Vec<_>>>()?;
+    let key_columns = |batch: &RecordBatch| -> Result<Vec<ArrayRef>> {
+        on.iter()
+            .zip(key_types.iter())
+            .map(|(column, data_type)| {
+                let array = batch
+                    .column_by_name(column)
+                    .ok_or_else(|| Error::InvalidInput {
+                        message: format!("the data is missing the 'on' column '{}'", column),
+                    })?;
+                Ok(cast(array, data_type)?)
+            })
+            .collect()
+    };
+
+    let converter = RowConverter::new(
+        key_types
+            .iter()
+            .map(|data_type| SortField::new(data_type.clone()))
+            .collect(),
+    )?;
+    let target_keys = converter.convert_columns(&key_columns(target)?)?;
+    let source_keys = converter.convert_columns(&key_columns(source)?)?;
+    let target_index = target_keys
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| (row, idx as u32))
+        .collect::<HashMap<_, _>>();
+    Ok(source_keys
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, row)| target_index.get(&row).map(|t| (idx as u32, *t)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow_array::{Int32Array, StringArray};
+
+    use super::*;
+
+    #[test]
+    fn test_references_column() {
+        assert!(references_column("target.age + 1", "target", "age"));
+        assert!(references_column(
+            "source.x * target.`age`",
+            "target",
+            "age"
+        ));
+        assert!(!references_column("target.age2 + 1", "target", "age"));
+        assert!(!references_column("source.age + 1", "target", "age"));
+        assert!(!references_column("my_target.age", "target", "age"));
+        assert!(!references_column("target.info.age", "target", "age"));
+    }
+
+    #[test]
+    fn test_key_filter() {
+        let batch = RecordBatch::try_from_iter(vec![
+            (
+                "id",
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef,
+            ),
+            (
+                "name",
+                Arc::new(StringArray::from(vec!["a", "b", "it's"])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        assert_eq!(
+            key_filter(&["id".to_string()], &batch).unwrap(),
+            "id IN (1, 3)"
+        );
+        assert_eq!(
+            key_filter(&["id".to_string(), "name".to_string()], &batch).unwrap(),
+            "(id = 1 AND name = 'a') OR (id = 3 AND name = 'it''s')"
+        );
+        assert_eq!(
+            key_filter(&["id".to_string()], &batch.slice(1, 1)).unwrap(),
+            "false"
+        );
     }
 }
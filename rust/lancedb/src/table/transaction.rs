@@ -0,0 +1,835 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Transactions that commit several write operations as one table version
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use arrow::compute::{concat_batches, filter_record_batch};
+use arrow_array::cast::AsArray;
+use arrow_array::types::UInt64Type;
+use arrow_array::{ArrayRef, BooleanArray, RecordBatch, RecordBatchIterator, RecordBatchReader};
+use arrow_cast::cast;
+use arrow_schema::{Schema, SchemaRef};
+use futures::{Stream, StreamExt, TryStreamExt};
+use lance::dataset::fragment::FileFragment;
+use lance::dataset::transaction::Operation;
+use lance::dataset::{Dataset, WriteParams};
+use lance_datafusion::planner::Planner;
+use lance_table::format::Fragment;
+use lance_table::io::commit::{CommitHandler, ManifestNamingScheme};
+use lance_table::io::deletion::{deletion_file_path, read_deletion_file, write_deletion_file};
+use object_store::path::Path;
+
+use crate::arrow::IntoArrow;
+use crate::embeddings::{EmbeddingDefinition, EmbeddingFunction, EmbeddingRegistry};
+use crate::{Error, Result};
+
+use super::merge::{match_rows, MergeInsertBuilder};
+use super::{TableInternal, WriteResult};
+
+/// A write operation staged in a [`TransactionBuilder`]
+pub(crate) enum TransactionOperation {
+    Add {
+        data: Box<dyn RecordBatchReader + Send>,
+        embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    },
+    Delete {
+        predicate: String,
+    },
+    Update {
+        columns: Vec<(String, String)>,
+        only_if: Option<String>,
+        embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    },
+    MergeInsert {
+        params: MergeInsertBuilder,
+        data: Box<dyn RecordBatchReader + Send>,
+        embedding_registry: Option<Arc<dyn EmbeddingRegistry>>,
+    },
+}
+
+/// A builder that stages several write operations and commits them as a
+/// single new version of a table
+///
+/// Nothing is written until [`Self::commit`] is called, and readers never see
+/// the state between two of the staged operations.
+///
+/// See [`super::Table::transaction`] for more context
+pub struct TransactionBuilder {
+    table: Arc<dyn TableInternal>,
+    embedding_registry: Arc<dyn EmbeddingRegistry>,
+    operations: Vec<TransactionOperation>,
+    // The first error raised while staging an operation, returned by commit
+    error: Option<Error>,
+}
+
+impl std::fmt::Debug for TransactionBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransactionBuilder")
+            .field("table", &self.table)
+            .field("num_operations", &self.operations.len())
+            .finish()
+    }
+}
+
+impl TransactionBuilder {
+    pub(super) fn new(
+        table: Arc<dyn TableInternal>,
+        embedding_registry: Arc<dyn EmbeddingRegistry>,
+    ) -> Self {
+        Self {
+            table,
+            embedding_registry,
+            operations: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub(crate) fn table(&self) -> &Arc<dyn TableInternal> {
+        &self.table
+    }
+
+    /// Stage adding rows to the table
+    ///
+    /// See [`super::Table::add`]
+    pub fn add<T: IntoArrow>(mut self, data: T) -> Self {
+        match data.into_arrow() {
+            Ok(data) => self.operations.push(TransactionOperation::Add {
+                data,
+                embedding_registry: Some(self.embedding_registry.clone()),
+            }),
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    /// Stage deleting the rows that match the predicate
+    ///
+    /// Rows added earlier in the same transaction are deleted too if they
+    /// match.  See [`super::Table::delete`]
+    pub fn delete(mut self, predicate: impl Into<String>) -> Self {
+        self.operations.push(TransactionOperation::Delete {
+            predicate: predicate.into(),
+        });
+        self
+    }
+
+    /// Stage updating rows of the table
+    ///
+    /// Each update is a column name and an SQL expression giving its new value.
+    /// If `only_if` is given then only the rows that match it are updated.
+    /// See [`super::Table::update`]
+    pub fn update(
+        mut self,
+        columns: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+        only_if: Option<String>,
+    ) -> Self {
+        self.operations.push(TransactionOperation::Update {
+            columns: columns
+                .into_iter()
+                .map(|(column, expr)| (column.into(), expr.into()))
+                .collect(),
+            only_if,
+            embedding_registry: Some(self.embedding_registry.clone()),
+        });
+        self
+    }
+
+    /// Stage a merge insert, configured with [`super::Table::merge_insert`]
+    ///
+    /// Conditional and partial-column updates are not supported inside a
+    /// transaction.
+    pub fn merge_insert(
+        mut self,
+        params: MergeInsertBuilder,
+        new_data: Box<dyn RecordBatchReader + Send>,
+    ) -> Self {
+        self.operations.push(TransactionOperation::MergeInsert {
+            params,
+            data: new_data,
+            embedding_registry: Some(self.embedding_registry.clone()),
+        });
+        self
+    }
+
+    /// Apply the staged operations, in order, and commit the result as one
+    /// new version of the table
+    ///
+    /// If the table was changed by someone else since the transaction started
+    /// and the changes conflict then an error is returned and nothing is
+    /// committed.
+    pub async fn commit(self) -> Result<WriteResult> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.table.commit_transaction(self.operations).await
+    }
+
+    /// Discard the staged operations
+    ///
+    /// Staged operations are not applied until [`Self::commit`], so nothing
+    /// has been written yet and this only drops them.  A commit that fails
+    /// removes the data files it wrote before failing.
+    pub fn rollback(self) {}
+}
+
+/// The number of rows of a fragment that have not been deleted
+pub(crate) fn fragment_live_rows(fragment: &Fragment) -> u64 {
+    let physical = fragment.physical_rows.unwrap_or_default() as u64;
+    let deleted = fragment
+        .deletion_file
+        .as_ref()
+        .and_then(|file| file.num_deleted_rows)
+        .unwrap_or_default() as u64;
+    physical.saturating_sub(deleted)
+}
+
+fn evaluate(batch: &RecordBatch, expr: &str) -> Result<ArrayRef> {
+    let planner = Planner::new(batch.schema());
+    let expr = planner.optimize_expr(planner.parse_expr(expr)?)?;
+    let expr = planner.create_physical_expr(&expr)?;
+    Ok(expr
+        .evaluate(batch)
+        .and_then(|value| value.into_array(batch.num_rows()))
+        .map_err(lance::Error::from)?)
+}
+
+/// The new values of the updated columns of some rows, followed by the
+/// embeddings
+///
+/// Every expression sees the values from before the update, and the values
+/// are cast to the types of the columns in `schema`.  Embeddings are computed
+/// from the new values of their source column, or from the current ones if it
+/// is not updated.
+pub(crate) fn updated_columns(
+    rows: &RecordBatch,
+    schema: &Schema,
+    columns: &[(String, String)],
+    embeddings: &[(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)],
+) -> Result<Vec<(String, ArrayRef)>> {
+    let mut updated = Vec::with_capacity(columns.len() + embeddings.len());
+    for (column, expr) in columns {
+        let value = evaluate(rows, expr)?;
+        let value = cast(&value, schema.field_with_name(column)?.data_type())?;
+        updated.push((column.clone(), value));
+    }
+    for (definition, function) in embeddings {
+        let source = match updated
+            .iter()
+            .find(|(column, _)| column == &definition.source_column)
+        {
+            Some((_, value)) => value.clone(),
+            None => rows
+                .column_by_name(&definition.source_column)
+                .cloned()
+                .ok_or_else(|| Error::InvalidInput {
+                    message: format!(
+                        "the source column '{}' does not exist",
+                        definition.source_column
+                    ),
+                })?,
+        };
+        let dest_column = definition.dest_column_name();
+        let embeddings = function.compute_source_embeddings(source)?;
+        let embeddings = cast(
+            &embeddings,
+            schema.field_with_name(&dest_column)?.data_type(),
+        )?;
+        updated.retain(|(column, _)| column != &dest_column);
+        updated.push((dest_column, embeddings));
+    }
+    Ok(updated)
+}
+
+/// Which rows of the batch match the predicate (nulls do not match)
+pub(crate) fn evaluate_filter(batch: &RecordBatch, predicate: &str) -> Result<BooleanArray> {
+    let planner = Planner::new(batch.schema());
+    let expr = planner.optimize_expr(planner.parse_filter(predicate)?)?;
+    let expr = planner.create_physical_expr(&expr)?;
+    let mask = expr
+        .evaluate(batch)
+        .and_then(|value| value.into_array(batch.num_rows()))
+        .map_err(lance::Error::from)?;
+    let mask = mask.as_boolean_opt().ok_or_else(|| Error::InvalidInput {
+        message: format!("'{}' is not a boolean expression", predicate),
+    })?;
+    Ok(mask
+        .iter()
+        .map(|matched| Some(matched == Some(true)))
+        .collect())
+}
+
+fn negate(mask: &BooleanArray) -> BooleanArray {
+    mask.iter().map(|value| value.map(|value| !value)).collect()
+}
+
+/// The most bytes of new rows held in memory before they are written to a
+/// data file
+const MAX_PENDING_BYTES: usize = 64 * 1024 * 1024;
+
+/// A fragment as seen from inside a transaction
+struct StagedFragment {
+    fragment: FileFragment,
+    /// Whether the fragment was written by the transaction, in which case it
+    /// is not part of any version yet
+    new: bool,
+    /// The offsets of the rows deleted by the transaction
+    deleted: HashSet<u32>,
+}
+
+impl StagedFragment {
+    /// Scan the rows of the fragment that the transaction has not deleted,
+    /// along with their offsets in the fragment
+    async fn scan(
+        &self,
+        columns: Option<&[String]>,
+        filter: Option<&str>,
+    ) -> Result<impl Stream<Item = Result<(RecordBatch, Vec<u32>)>> + Unpin + '_> {
+        let mut scanner = self.fragment.scan();
+        if let Some(columns) = columns {
+            scanner.project(columns)?;
+        }
+        if let Some(filter) = filter {
+            scanner.filter(filter)?;
+        }
+        scanner.with_row_address();
+        let stream = scanner.try_into_stream().await?;
+        Ok(stream.map(move |batch| self.live_rows(batch?)))
+    }
+
+    fn live_rows(&self, mut batch: RecordBatch) -> Result<(RecordBatch, Vec<u32>)> {
+        let addresses = batch.remove_column(batch.schema().index_of("_rowaddr")?);
+        // The low 32 bits of a row address are the offset in the fragment
+        let offsets = addresses
+            .as_primitive::<UInt64Type>()
+            .values()
+            .iter()
+            .map(|address| *address as u32)
+            .collect::<Vec<_>>();
+        if self.deleted.is_empty() {
+            return Ok((batch, offsets));
+        }
+        let keep = offsets
+            .iter()
+            .map(|offset| Some(!self.deleted.contains(offset)))
+            .collect::<BooleanArray>();
+        let offsets = offsets
+            .into_iter()
+            .filter(|offset| !self.deleted.contains(offset))
+            .collect();
+        Ok((filter_record_batch(&batch, &keep)?, offsets))
+    }
+
+    /// The offsets of the live rows that match the filter
+    async fn matching(&self, filter: Option<&str>) -> Result<Vec<u32>> {
+        let mut stream = self.scan(Some(&[]), filter).await?;
+        let mut offsets = Vec::new();
+        while let Some((_, matched)) = stream.try_next().await? {
+            offsets.extend(matched);
+        }
+        Ok(offsets)
+    }
+
+    /// Delete rows, returning how many of them were not already deleted
+    fn delete(&mut self, offsets: impl IntoIterator<Item = u32>) -> u64 {
+        offsets
+            .into_iter()
+            .filter(|offset| self.deleted.insert(*offset))
+            .count() as u64
+    }
+}
+
+/// A local table as seen from inside a transaction
+///
+/// New rows are written to new data files as they are staged, and deletions
+/// are kept per fragment and only written as deletion files on commit, so
+/// readers of the table see none of the changes until then.  Data files of a
+/// transaction that is not committed are removed again.
+pub(crate) struct StagedTable {
+    dataset: Arc<Dataset>,
+    uri: String,
+    write_params: WriteParams,
+    schema: SchemaRef,
+    fragments: Vec<StagedFragment>,
+    /// New rows that are not written yet
+    pending: Vec<RecordBatch>,
+    pending_bytes: usize,
+    /// The files written by the transaction
+    written: Vec<Path>,
+    result: WriteResult,
+}
+
+impl StagedTable {
+    pub(crate) fn new(dataset: Dataset, uri: &str, mut write_params: WriteParams) -> Result<Self> {
+        // New data files must use the same format as the rest of the table
+        write_params.data_storage_version = Some(
+            dataset
+                .manifest()
+                .data_storage_format
+                .lance_file_version()?,
+        );
+        let dataset = Arc::new(dataset);
+        let fragments = dataset
+            .get_fragments()
+            .into_iter()
+            .map(|fragment| StagedFragment {
+                fragment,
+                new: false,
+                deleted: HashSet::new(),
+            })
+            .collect();
+        Ok(Self {
+            schema: Arc::new(Schema::from(dataset.schema())),
+            dataset,
+            uri: uri.to_string(),
+            write_params,
+            fragments,
+            pending: Vec::new(),
+            pending_bytes: 0,
+            written: Vec::new(),
+            result: WriteResult::default(),
+        })
+    }
+
+    /// Cast a batch to the schema of the table
+    fn conform(&self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let columns = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| {
+                let column =
+                    batch
+                        .column_by_name(field.name())
+                        .ok_or_else(|| Error::InvalidInput {
+                            message: format!("the data is missing the column '{}'", field.name()),
+                        })?;
+                Ok(cast(column, field.data_type())?)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+
+    /// Stage new rows, writing them out once enough have been buffered
+    async fn stage(&mut self, rows: RecordBatch) -> Result<()> {
+        if rows.num_rows() == 0 {
+            return Ok(());
+        }
+        self.pending_bytes += rows.get_array_memory_size();
+        self.pending.push(rows);
+        let num_rows = self.pending.iter().map(|b| b.num_rows()).sum::<usize>();
+        if num_rows >= self.write_params.max_rows_per_file
+            || self.pending_bytes >= MAX_PENDING_BYTES
+        {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Take the new rows that are not written yet
+    fn take_pending(&mut self) -> Result<RecordBatch> {
+        self.pending_bytes = 0;
+        Ok(concat_batches(
+            &self.schema,
+            &std::mem::take(&mut self.pending),
+        )?)
+    }
+
+    /// Write the buffered new rows to new fragments
+    async fn flush(&mut self) -> Result<()> {
+        let rows = self.take_pending()?;
+        let max_rows = self.write_params.max_rows_per_file.max(1);
+        let data_dir = self.dataset.data_dir();
+        let mut offset = 0;
+        while offset < rows.num_rows() {
+            let chunk = rows.slice(offset, max_rows.min(rows.num_rows() - offset));
+            offset += chunk.num_rows();
+            let reader = RecordBatchIterator::new(vec![Ok(chunk)], self.schema.clone());
+            let fragment =
+                FileFragment::create(&self.uri, 0, reader, Some(self.write_params.clone())).await?;
+            self.written.extend(
+                fragment
+                    .files
+                    .iter()
+                    .map(|file| data_dir.child(file.path.as_str())),
+            );
+            self.fragments.push(StagedFragment {
+                fragment: FileFragment::new(self.dataset.clone(), fragment),
+                new: true,
+                deleted: HashSet::new(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove the data files of a fragment written by the transaction
+    async fn remove_files(&mut self, fragment: &Fragment) -> Result<()> {
+        let data_dir = self.dataset.data_dir();
+        for file in &fragment.files {
+            let path = data_dir.child(file.path.as_str());
+            self.dataset.object_store().inner.delete(&path).await?;
+            self.written.retain(|written| written != &path);
+        }
+        Ok(())
+    }
+
+    /// Record rows deleted from a fragment
+    ///
+    /// Rows of new fragments were never visible, so deleting them means they
+    /// are no longer inserted.
+    fn count_deleted(&mut self, fragment: &StagedFragment, deleted: u64) {
+        if fragment.new {
+            self.result.num_inserted_rows = self.result.num_inserted_rows.saturating_sub(deleted);
+        } else {
+            self.result.num_deleted_rows += deleted;
+        }
+    }
+
+    pub(crate) async fn add(&mut self, data: impl RecordBatchReader) -> Result<()> {
+        for batch in data {
+            let batch = self.conform(&batch?)?;
+            self.result.num_inserted_rows += batch.num_rows() as u64;
+            self.stage(batch).await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn delete(&mut self, predicate: &str) -> Result<()> {
+        let pending = self.take_pending()?;
+        let matched = evaluate_filter(&pending, predicate)?;
+        self.result.num_inserted_rows = self
+            .result
+            .num_inserted_rows
+            .saturating_sub(matched.true_count() as u64);
+        self.stage(filter_record_batch(&pending, &negate(&matched))?)
+            .await?;
+
+        for mut fragment in std::mem::take(&mut self.fragments) {
+            let offsets = fragment.matching(Some(predicate)).await?;
+            let deleted = fragment.delete(offsets);
+            self.count_deleted(&fragment, deleted);
+            self.fragments.push(fragment);
+        }
+        Ok(())
+    }
+
+    /// Apply the update expressions to the rows, then recompute the
+    /// embeddings whose source column was updated
+    fn apply_updates(
+        &self,
+        rows: &RecordBatch,
+        columns: &[(String, String)],
+        embeddings: &[(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)],
+    ) -> Result<RecordBatch> {
+        let rows = self.conform(rows)?;
+        let mut values = rows.columns().to_vec();
+        for (column, value) in updated_columns(&rows, &self.schema, columns, embeddings)? {
+            values[self.schema.index_of(&column)?] = value;
+        }
+        Ok(RecordBatch::try_new(self.schema.clone(), values)?)
+    }
+
+    pub(crate) async fn update(
+        &mut self,
+        columns: &[(String, String)],
+        only_if: Option<&str>,
+        embeddings: &[(EmbeddingDefinition, Arc<dyn EmbeddingFunction>)],
+    ) -> Result<()> {
+        for (column, _) in columns {
+            if self.schema.field_with_name(column).is_err() {
+                return Err(Error::InvalidInput {
+                    message: format!("column '{}' does not exist in the table", column),
+                });
+            }
+        }
+
+        let pending = self.take_pending()?;
+        let matched = evaluate_filter(&pending, only_if.unwrap_or("true"))?;
+        let updated = self.apply_updates(
+            &filter_record_batch(&pending, &matched)?,
+            columns,
+            embeddings,
+        )?;
+        self.result.num_updated_rows += updated.num_rows() as u64;
+        self.stage(filter_record_batch(&pending, &negate(&matched))?)
+            .await?;
+        self.stage(updated).await?;
+
+        // Updated rows are deleted from their fragment and staged as new rows,
+        // one batch at a time
+        for mut fragment in std::mem::take(&mut self.fragments) {
+            let mut updated = Vec::new();
+            let mut stream = fragment.scan(None, only_if).await?;
+            while let Some((rows, offsets)) = stream.try_next().await? {
+                self.result.num_updated_rows += rows.num_rows() as u64;
+                self.stage(self.apply_updates(&rows, columns, embeddings)?)
+                    .await?;
+                updated.extend(offsets);
+            }
+            drop(stream);
+            fragment.delete(updated);
+            self.fragments.push(fragment);
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn merge_insert(
+        &mut self,
+        params: MergeInsertBuilder,
+        data: impl RecordBatchReader,
+    ) -> Result<()> {
+        if params.when_matched_update_all_filt.is_some()
+            || params.when_matched_update.is_some()
+            || params.when_not_matched_by_source_delete_filt.is_some()
+        {
+            return Err(Error::NotSupported {
+                message: "conditional and partial-column merge inserts are not supported in a \
+                          transaction"
+                    .to_string(),
+            });
+        }
+        if params.expected_version.is_some() {
+            return Err(Error::InvalidInput {
+                message: "expected_version cannot be set on a merge insert in a transaction"
+                    .to_string(),
+            });
+        }
+        let on = &params.on;
+        let source_schema = data.schema();
+        let batches = data.collect::<std::result::Result<Vec<_>, _>>()?;
+        let source = self.conform(&concat_batches(&source_schema, &batches)?)?;
+
+        let remove_target = |matched: bool| {
+            (matched && params.when_matched_update_all)
+                || (!matched && params.when_not_matched_by_source_delete)
+        };
+        // Which source rows have a match in the table, and which rows of a
+        // batch of the table have a match in the source
+        let mut source_matched = vec![false; source.num_rows()];
+        let mut match_batch = |target: &RecordBatch| -> Result<Vec<bool>> {
+            for (idx, _) in match_rows(on, &source, target)? {
+                source_matched[idx as usize] = true;
+            }
+            let mut matched = vec![false; target.num_rows()];
+            for (idx, _) in match_rows(on, target, &source)? {
+                matched[idx as usize] = true;
+            }
+            Ok(matched)
+        };
+
+        // Remove the replaced and the deleted rows
+        let pending = self.take_pending()?;
+        let keep = match_batch(&pending)?
+            .into_iter()
+            .map(|matched| Some(!remove_target(matched)))
+            .collect::<BooleanArray>();
+        let kept = filter_record_batch(&pending, &keep)?;
+
+        let mut fragments = Vec::new();
+        for mut fragment in std::mem::take(&mut self.fragments) {
+            let mut removed = Vec::new();
+            let mut num_deleted = 0;
+            let mut stream = fragment.scan(Some(on.as_slice()), None).await?;
+            while let Some((keys, offsets)) = stream.try_next().await? {
+                for (matched, offset) in match_batch(&keys)?.into_iter().zip(offsets) {
+                    if remove_target(matched) {
+                        removed.push(offset);
+                        // Rows of new fragments were never visible, and
+                        // matched rows are updated rather than deleted
+                        if fragment.new || !matched {
+                            num_deleted += 1;
+                        }
+                    }
+                }
+            }
+            drop(stream);
+            fragment.delete(removed);
+            fragments.push((fragment, num_deleted));
+        }
+        for (fragment, num_deleted) in fragments {
+            self.count_deleted(&fragment, num_deleted);
+            self.fragments.push(fragment);
+        }
+
+        // Add the updated and inserted rows
+        let mut num_updated = 0;
+        let mut num_inserted = 0;
+        let add = source_matched
+            .iter()
+            .map(|matched| {
+                let add = if *matched {
+                    params.when_matched_update_all
+                } else {
+                    params.when_not_matched_insert_all
+                };
+                match (add, *matched) {
+                    (true, true) => num_updated += 1,
+                    (true, false) => num_inserted += 1,
+                    _ => {}
+                }
+                Some(add)
+            })
+            .collect::<BooleanArray>();
+        self.result.num_inserted_rows = self
+            .result
+            .num_inserted_rows
+            .saturating_sub((pending.num_rows() - kept.num_rows()) as u64);
+        self.result.num_updated_rows += num_updated;
+        self.result.num_inserted_rows += num_inserted;
+        self.stage(kept).await?;
+        self.stage(filter_record_batch(&source, &add)?).await?;
+        Ok(())
+    }
+
+    /// The root of the dataset in its object store (data files are in
+    /// `<root>/data`)
+    fn base_path(&self) -> Path {
+        let data_dir = self.dataset.data_dir();
+        let parts = data_dir.parts().collect::<Vec<_>>();
+        parts[..parts.len().saturating_sub(1)]
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Write the staged rows and deletions and commit everything as one new
+    /// version
+    ///
+    /// Returns `None` if the transaction did not change the table.  If the
+    /// commit fails the files written by the transaction are removed.
+    pub(crate) async fn commit(
+        mut self,
+        commit_handler: Option<Arc<dyn CommitHandler>>,
+    ) -> Result<Option<(Dataset, WriteResult)>> {
+        let committed = self.try_commit(commit_handler).await;
+        if committed.is_err() {
+            self.remove_written().await;
+        }
+        committed
+    }
+
+    /// Discard the transaction, removing the files it has written
+    pub(crate) async fn abort(mut self) {
+        self.remove_written().await;
+    }
+
+    async fn remove_written(&mut self) {
+        for path in std::mem::take(&mut self.written) {
+            if let Err(err) = self.dataset.object_store().inner.delete(&path).await {
+                log::warn!("failed to remove uncommitted file {}: {}", path, err);
+            }
+        }
+    }
+
+    async fn try_commit(
+        &mut self,
+        commit_handler: Option<Arc<dyn CommitHandler>>,
+    ) -> Result<Option<(Dataset, WriteResult)>> {
+        // Deletion files are named after the fragment id, which new fragments
+        // only get on commit, so new fragments that lost rows are rewritten
+        let mut fragments = Vec::new();
+        for fragment in std::mem::take(&mut self.fragments) {
+            if !fragment.new || fragment.deleted.is_empty() {
+                fragments.push(fragment);
+                continue;
+            }
+            let mut stream = fragment.scan(None, None).await?;
+            while let Some((rows, _)) = stream.try_next().await? {
+                let rows = self.conform(&rows)?;
+                self.stage(rows).await?;
+            }
+            drop(stream);
+            self.remove_files(fragment.fragment.metadata()).await?;
+        }
+        self.fragments.extend(fragments);
+        self.flush().await?;
+
+        let base = self.base_path();
+        let object_store = self.dataset.object_store();
+        let read_version = self.dataset.version().version;
+        let mut new_fragments = Vec::new();
+        let mut updated_fragments = Vec::new();
+        let mut removed_fragment_ids = Vec::new();
+        let mut deletion_files = Vec::new();
+        for fragment in &self.fragments {
+            let metadata = fragment.fragment.metadata();
+            if fragment.new {
+                new_fragments.push(metadata.clone());
+                continue;
+            }
+            if fragment.deleted.is_empty() {
+                continue;
+            }
+            let mut deletion_vector = read_deletion_file(&base, metadata, object_store)
+                .await?
+                .unwrap_or_default();
+            deletion_vector.extend(fragment.deleted.iter().copied());
+            if Some(deletion_vector.len()) == metadata.physical_rows {
+                removed_fragment_ids.push(metadata.id);
+                continue;
+            }
+            let mut metadata = metadata.clone();
+            metadata.deletion_file = write_deletion_file(
+                &base,
+                metadata.id,
+                read_version,
+                &deletion_vector,
+                object_store,
+            )
+            .await?;
+            if let Some(file) = &metadata.deletion_file {
+                deletion_files.push(deletion_file_path(&base, metadata.id, file));
+            }
+            updated_fragments.push(metadata);
+        }
+        self.written.extend(deletion_files);
+        if new_fragments.is_empty()
+            && updated_fragments.is_empty()
+            && removed_fragment_ids.is_empty()
+        {
+            return Ok(None);
+        }
+
+        let num_fragments_written = new_fragments.len() as u64;
+        let bytes_written = super::data_files_bytes(&self.dataset, &new_fragments).await?;
+        let operation = Operation::Update {
+            removed_fragment_ids,
+            updated_fragments,
+            new_fragments,
+            fields_modified: vec![],
+        };
+        let dataset = Dataset::commit(
+            &self.uri,
+            operation,
+            Some(read_version),
+            self.write_params.store_params.clone(),
+            commit_handler,
+            self.dataset.session(),
+            self.dataset.manifest_naming_scheme == ManifestNamingScheme::V2,
+        )
+        .await?;
+
+        let result = WriteResult {
+            version: dataset.version().version,
+            num_fragments_written,
+            bytes_written,
+            ..std::mem::take(&mut self.result)
+        };
+        Ok(Some((dataset, result)))
+    }
+}
@@ -0,0 +1,344 @@
+// Copyright 2024 Lance Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Buffered writer for ingesting many small batches into a table
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow_array::{RecordBatch, RecordBatchIterator};
+use arrow_schema::SchemaRef;
+use log::warn;
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+
+use super::{Table, WriteOptions, WriteResult};
+
+/// The default number of buffered rows that triggers a flush
+pub const DEFAULT_MAX_BUFFERED_ROWS: usize = 1024 * 1024;
+/// The default number of buffered bytes that triggers a flush
+pub const DEFAULT_MAX_BUFFERED_BYTES: usize = 256 * 1024 * 1024;
+
+/// A writer that buffers small writes and adds them to a table in large chunks
+///
+/// Every [`Table::add`] writes at least one new data file, so adding many small
+/// batches leaves the table with many small files.  A `TableWriter` collects
+/// the batches in memory instead and adds them all at once when one of these
+/// thresholds is reached:
+///
+/// * the number of buffered rows ([`Self::max_buffered_rows`])
+/// * the in-memory size of the buffered batches ([`Self::max_buffered_bytes`])
+/// * the time since the oldest buffered batch was written
+///   ([`Self::flush_interval`])
+///
+/// Buffered data is not visible to readers until it is flushed.  Call
+/// [`Self::close`] when done so that the remaining rows are written; rows still
+/// buffered when the writer is dropped are lost.
+///
+/// If a flush fails the buffered rows are kept so that a later flush can try
+/// again, but the buffer does not grow past the thresholds: a write that would
+/// need a flush fails, without buffering its batch, until a flush succeeds.
+///
+/// Create one with [`Table::writer`].
+///
+/// # Example
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use arrow_array::RecordBatch;
+/// # async fn doctest_helper(tbl: lancedb::Table, events: Vec<RecordBatch>) -> lancedb::Result<()> {
+/// let writer = tbl
+///     .writer()
+///     .max_buffered_rows(100_000)
+///     .flush_interval(Duration::from_secs(30));
+/// for event in events {
+///     writer.write(event).await?;
+/// }
+/// writer.close().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TableWriter {
+    table: Table,
+    max_rows: usize,
+    max_bytes: usize,
+    flush_interval: Option<Duration>,
+    write_options: WriteOptions,
+    state: Arc<Mutex<WriterState>>,
+    /// Flushes rows that have waited too long, started by the first write
+    flusher: std::sync::Mutex<Option<Flusher>>,
+}
+
+/// The background task of a [`TableWriter`] with a flush interval
+struct Flusher {
+    task: JoinHandle<()>,
+    /// Tells the task to stop once it is not in the middle of a flush
+    stop: watch::Sender<bool>,
+}
+
+impl std::fmt::Debug for TableWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TableWriter")
+            .field("table", &self.table)
+            .field("max_rows", &self.max_rows)
+            .field("max_bytes", &self.max_bytes)
+            .field("flush_interval", &self.flush_interval)
+            .field("write_options", &self.write_options)
+            .finish()
+    }
+}
+
+#[derive(Default)]
+struct WriterState {
+    schema: Option<SchemaRef>,
+    batches: Vec<RecordBatch>,
+    num_rows: usize,
+    num_bytes: usize,
+    /// When the oldest of the buffered batches was written
+    oldest: Option<Instant>,
+    /// An error from a background flush, reported by the next call
+    error: Option<Error>,
+}
+
+impl WriterState {
+    fn push(&mut self, batch: RecordBatch) {
+        self.num_rows += batch.num_rows();
+        self.num_bytes += batch.get_array_memory_size();
+        self.oldest.get_or_insert_with(Instant::now);
+        self.batches.push(batch);
+    }
+
+    /// Remove the batch that was pushed last
+    fn pop(&mut self) {
+        if let Some(batch) = self.batches.pop() {
+            self.num_rows -= batch.num_rows();
+            self.num_bytes -= batch.get_array_memory_size();
+        }
+        if self.batches.is_empty() {
+            self.oldest = None;
+        }
+    }
+
+    /// Add the buffered batches to the table
+    ///
+    /// If this fails then the batches stay buffered, so that a later flush can
+    /// try again.
+    async fn flush(
+        &mut self,
+        table: &Table,
+        write_options: &WriteOptions,
+    ) -> Result<Option<WriteResult>> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        let Some(schema) = self.schema.clone() else {
+            return Ok(None);
+        };
+        if self.batches.is_empty() {
+            return Ok(None);
+        }
+        let data = RecordBatchIterator::new(self.batches.clone().into_iter().map(Ok), schema);
+        let result = table
+            .add(data)
+            .write_options(write_options.clone())
+            .execute()
+            .await?;
+        self.batches.clear();
+        self.num_rows = 0;
+        self.num_bytes = 0;
+        self.oldest = None;
+        Ok(Some(result))
+    }
+}
+
+impl TableWriter {
+    pub(crate) fn new(table: Table) -> Self {
+        Self {
+            table,
+            max_rows: DEFAULT_MAX_BUFFERED_ROWS,
+            max_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            flush_interval: None,
+            write_options: WriteOptions::default(),
+            state: Arc::new(Mutex::new(WriterState::default())),
+            flusher: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Flush once this many rows are buffered
+    ///
+    /// The default is [`DEFAULT_MAX_BUFFERED_ROWS`].
+    pub fn max_buffered_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    /// Flush once the buffered batches take up this many bytes of memory
+    ///
+    /// The default is [`DEFAULT_MAX_BUFFERED_BYTES`].
+    pub fn max_buffered_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Flush rows that have been buffered for this long, even if no other
+    /// threshold has been reached
+    ///
+    /// This runs in a background task, so rows are written in time even when
+    /// no more rows arrive.  Errors from a background flush are returned by the
+    /// next call to [`Self::write`], [`Self::flush`] or [`Self::close`].
+    ///
+    /// By default rows are only flushed on size.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self.stop_flusher();
+        self
+    }
+
+    /// The options to use when adding the buffered rows to the table
+    pub fn write_options(mut self, options: WriteOptions) -> Self {
+        self.write_options = options;
+        self.stop_flusher();
+        self
+    }
+
+    /// Tell the background flusher to stop, returning its task
+    ///
+    /// The task is not aborted, since aborting it in the middle of a flush
+    /// could leave rows that were already added to the table in the buffer.
+    fn stop_flusher(&self) -> Option<JoinHandle<()>> {
+        let flusher = self.flusher.lock().unwrap().take()?;
+        // The task may already have stopped, after which there is no receiver
+        let _ = flusher.stop.send(true);
+        Some(flusher.task)
+    }
+
+    fn start_flusher(&self, interval: Duration) {
+        let mut flusher = self.flusher.lock().unwrap();
+        if flusher.is_some() {
+            return;
+        }
+        let table = self.table.clone();
+        let write_options = self.write_options.clone();
+        let state = self.state.clone();
+        let (stop, mut stopped) = watch::channel(false);
+        let task = tokio::spawn(async move {
+            loop {
+                let oldest = state.lock().await.oldest;
+                let deadline = oldest.unwrap_or_else(Instant::now) + interval;
+                if !wait_until(&mut stopped, deadline).await {
+                    return;
+                }
+                let mut state = state.lock().await;
+                let due = state
+                    .oldest
+                    .map(|oldest| oldest.elapsed() >= interval)
+                    .unwrap_or(false);
+                if due {
+                    if let Err(err) = state.flush(&table, &write_options).await {
+                        warn!("Failed to flush buffered rows: {}", err);
+                        state.error = Some(err);
+                        // Leave the rows for the caller to retry instead of
+                        // failing again straight away
+                        drop(state);
+                        if !wait_until(&mut stopped, Instant::now() + interval).await {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+        *flusher = Some(Flusher { task, stop });
+    }
+
+    /// Buffer a batch of rows, flushing if a threshold is reached
+    ///
+    /// A single row can be written as a batch with one row.  All batches must
+    /// have the same schema.
+    ///
+    /// If this returns an error the batch was not buffered.
+    pub async fn write(&self, batch: RecordBatch) -> Result<()> {
+        if let Some(interval) = self.flush_interval {
+            self.start_flusher(interval);
+        }
+        let mut state = self.state.lock().await;
+        if let Some(err) = state.error.take() {
+            return Err(err);
+        }
+        match &state.schema {
+            Some(schema) if schema != &batch.schema() => {
+                return Err(Error::InvalidInput {
+                    message: format!(
+                        "all batches written to a TableWriter must have the same schema, \
+                         expected {:?} but got {:?}",
+                        schema,
+                        batch.schema()
+                    ),
+                });
+            }
+            Some(_) => {}
+            None => state.schema = Some(batch.schema()),
+        }
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        state.push(batch);
+        if state.num_rows >= self.max_rows || state.num_bytes >= self.max_bytes {
+            if let Err(err) = state.flush(&self.table, &self.write_options).await {
+                // Keep the buffer bounded while the table cannot be written
+                state.pop();
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add all of the buffered rows to the table now
+    ///
+    /// Returns `None` if there was nothing to write.
+    pub async fn flush(&self) -> Result<Option<WriteResult>> {
+        self.state
+            .lock()
+            .await
+            .flush(&self.table, &self.write_options)
+            .await
+    }
+
+    /// Flush the remaining rows and stop the writer
+    ///
+    /// Waits for a background flush that is in progress to finish first.
+    pub async fn close(self) -> Result<Option<WriteResult>> {
+        if let Some(task) = self.stop_flusher() {
+            if let Err(err) = task.await {
+                warn!("The background flush of a TableWriter failed: {}", err);
+            }
+        }
+        self.flush().await
+    }
+}
+
+/// Wait until the deadline, returning false if the writer was stopped first
+async fn wait_until(stopped: &mut watch::Receiver<bool>, deadline: Instant) -> bool {
+    tokio::time::timeout_at(deadline, stopped.changed())
+        .await
+        .is_err()
+}
+
+impl Drop for TableWriter {
+    fn drop(&mut self) {
+        self.stop_flusher();
+    }
+}
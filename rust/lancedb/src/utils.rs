@@ -20,6 +20,7 @@ use lance::io::{ObjectStoreParams, WrappingObjectStore};
 use lazy_static::lazy_static;
 
 use crate::error::{Error, Result};
+use crate::sparse::is_sparse_vector_type;
 
 lazy_static! {
     static ref TABLE_NAME_REGEX: regex::Regex = regex::Regex::new(r"^[a-zA-Z0-9_\-\.]+$").unwrap();
@@ -137,6 +138,31 @@ pub(crate) fn default_vector_column(schema: &Schema, dim: Option<i32>) -> Result
     }
 }
 
+/// Find the default sparse vector column to query.
+pub(crate) fn default_sparse_vector_column(schema: &Schema) -> Result<String> {
+    let candidates = schema
+        .fields()
+        .iter()
+        .filter(|field| is_sparse_vector_type(field.data_type()))
+        .map(|field| field.name())
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        Err(Error::InvalidInput {
+            message: "No sparse vector column found to match with the query vector".to_string(),
+        })
+    } else if candidates.len() != 1 {
+        Err(Error::Schema {
+            message: format!(
+                "More than one sparse vector columns found, \
+                    please specify which column to query: {:?}",
+                candidates
+            ),
+        })
+    } else {
+        Ok(candidates[0].to_string())
+    }
+}
+
 pub fn supported_btree_data_type(dtype: &DataType) -> bool {
     dtype.is_integer()
         || dtype.is_floating()
@@ -175,6 +201,40 @@ pub fn supported_vector_data_type(dtype: &DataType) -> bool {
     }
 }
 
+/// Run a future to completion from synchronous code, such as an
+/// [`crate::embeddings::EmbeddingFunction`] called while writing to a table
+///
+/// On a multi-threaded tokio runtime the current worker hands its other tasks
+/// over with `block_in_place`.  That is not possible on a current_thread
+/// runtime, so there the future runs on a thread of its own with a temporary
+/// runtime; it must not wait for tasks of the blocked runtime.  Outside of any
+/// runtime a temporary runtime is used as well.
+pub(crate) fn block_on<T: Send>(
+    future: impl std::future::Future<Output = Result<T>> + Send,
+) -> Result<T> {
+    let run = |future| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Runtime {
+                message: format!("failed to start a tokio runtime: {}", e),
+            })?
+            .block_on(future)
+    };
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::CurrentThread => {
+            std::thread::scope(|scope| {
+                scope
+                    .spawn(|| run(future))
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+        }
+        Ok(handle) => tokio::task::block_in_place(move || handle.block_on(future)),
+        Err(_) => run(future),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
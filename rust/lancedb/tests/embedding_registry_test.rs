@@ -2,21 +2,34 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     iter::repeat,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use arrow::buffer::NullBuffer;
 use arrow_array::{
-    Array, FixedSizeListArray, Float32Array, Int32Array, RecordBatch, RecordBatchIterator,
-    StringArray,
+    cast::AsArray, new_null_array, types::Float32Type, Array, FixedSizeListArray, Float32Array,
+    Int32Array, RecordBatch, RecordBatchIterator, StringArray,
 };
 use arrow_schema::{DataType, Field, Schema};
-use futures::StreamExt;
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
 use lancedb::{
     arrow::IntoArrow,
     connect,
-    embeddings::{EmbeddingDefinition, EmbeddingFunction, EmbeddingRegistry},
-    query::ExecutableQuery,
+    embeddings::{
+        batched::BatchedEmbeddingFunction,
+        cache::{CachedEmbeddingFunction, MemoryEmbeddingCache, TableEmbeddingCache},
+        matryoshka::MatryoshkaEmbeddingFunction,
+        AsyncEmbeddingFunction, EmbeddingDefinition, EmbeddingFunction, EmbeddingFunctionConfig,
+        EmbeddingFunctionFactory, EmbeddingRegistry,
+    },
+    index::{scalar::BTreeIndexBuilder, Index},
+    query::{ExecutableQuery, QueryBase},
+    sparse::{sparse_vector_type, sparse_vectors_to_array, SparseVector},
+    table::{BatchUDF, ColumnKind, NewColumnTransform, TableDefinition},
     Error, Result,
 };
 
@@ -209,6 +222,778 @@ async fn test_no_func_in_registry_on_add() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_embedding_config_in_metadata() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry().register(
+        "embed_fun",
+        Arc::new(MockEmbed::new("embed_fun".to_string(), 3)),
+    )?;
+    db.create_table("test", create_some_records()?)
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "embed_fun",
+            Some("embeddings"),
+        ))?
+        .execute()
+        .await?;
+
+    // The function's configuration is stored with the table
+    let tbl = db.open_table("test").execute().await?;
+    let definition = TableDefinition::try_from_rich_schema(tbl.schema().await?)?;
+    let config = definition
+        .column_definitions
+        .iter()
+        .find_map(|cd| match &cd.kind {
+            ColumnKind::Embedding(ed) => ed.config.clone(),
+            ColumnKind::Physical => None,
+        })
+        .unwrap();
+    assert_eq!(
+        config,
+        EmbeddingFunctionConfig::new("mock")
+            .model("embed_fun")
+            .dimensions(3)
+    );
+
+    // Without the function or a factory the table can't be written to
+    let db = connect(tempdir).execute().await?;
+    let tbl = db.open_table("test").execute().await?;
+    let res = tbl.add(create_some_records()?).execute().await;
+    assert!(matches!(res, Err(Error::EmbeddingFunctionNotFound { .. })));
+
+    // A factory recreates the function from its configuration
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register_factory("mock", Arc::new(MockFactory))?;
+    let tbl = db.open_table("test").execute().await?;
+    tbl.add(create_some_records()?).execute().await?;
+    assert_eq!(tbl.count_rows(None).await?, 4);
+    assert_eq!(
+        tbl.count_rows(Some("embeddings IS NULL".to_string()))
+            .await?,
+        0
+    );
+    assert!(db.embedding_registry().functions().contains("embed_fun"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_add_embedding_column() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry().register(
+        "embed_fun",
+        Arc::new(MockEmbed::new("embed_fun".to_string(), 4)),
+    )?;
+
+    let tbl = db
+        .create_table("test", create_some_records()?)
+        .execute()
+        .await?;
+
+    // The function must be registered
+    let res = tbl
+        .add_embedding_column(EmbeddingDefinition::new("text", "missing", None))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(Error::EmbeddingFunctionNotFound { .. })));
+
+    let definition = EmbeddingDefinition::new("text", "embed_fun", Some("embeddings"));
+    tbl.add_embedding_column(definition.clone())
+        .execute()
+        .await?;
+    let res = tbl.add_embedding_column(definition).execute().await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    // An existing column is not overwritten
+    let res = tbl
+        .add_embedding_column(EmbeddingDefinition::new("text", "embed_fun", Some("id")))
+        .execute()
+        .await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    // Existing rows are backfilled and new rows are embedded on add
+    tbl.add(create_some_records()?).execute().await?;
+    assert_eq!(tbl.count_rows(None).await?, 4);
+    assert_eq!(
+        tbl.count_rows(Some("embeddings IS NULL".to_string()))
+            .await?,
+        0
+    );
+    let definition = TableDefinition::try_from_rich_schema(tbl.schema().await?)?;
+    assert!(definition.column_definitions.iter().any(|cd| matches!(
+        &cd.kind,
+        ColumnKind::Embedding(ed) if ed.dest_column.as_deref() == Some("embeddings")
+    )));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_embeddings_on_update_and_merge_insert() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("text_len", Arc::new(TextLengthEmbed::default()))?;
+
+    let tbl = db
+        .create_table("test", create_some_records()?)
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "text_len",
+            Some("embeddings"),
+        ))?
+        .execute()
+        .await?;
+
+    // Updating the source column recomputes the embedding
+    let updated = tbl
+        .update()
+        .only_if("id = 0")
+        .column("text", "'hi'")
+        .execute()
+        .await?;
+    assert_eq!(updated, 1);
+    assert_eq!(embedding_of(&tbl, 0).await?, 2.0);
+    assert_eq!(embedding_of(&tbl, 1).await?, 11.0);
+
+    // Upserted rows are embedded, whether they are updated or inserted
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let new_data = RecordBatchIterator::new(
+        vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 5])),
+                Arc::new(StringArray::from(vec!["abc", "abcd"])),
+            ],
+        )
+        .unwrap()]
+        .into_iter()
+        .map(Ok),
+        schema,
+    );
+    let mut merge_insert = tbl.merge_insert(&["id"]);
+    merge_insert
+        .when_matched_update_all(None)
+        .when_not_matched_insert_all();
+    merge_insert.execute(Box::new(new_data)).await?;
+    assert_eq!(tbl.count_rows(None).await?, 3);
+    assert_eq!(embedding_of(&tbl, 1).await?, 3.0);
+    assert_eq!(embedding_of(&tbl, 5).await?, 4.0);
+
+    Ok(())
+}
+
+async fn embedding_of(tbl: &lancedb::Table, id: i32) -> Result<f32> {
+    let batches = tbl
+        .query()
+        .only_if(format!("id = {}", id))
+        .execute()
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    let embeddings = batches[0]
+        .column_by_name("embeddings")
+        .unwrap()
+        .as_fixed_size_list();
+    Ok(embeddings.value(0).as_primitive::<Float32Type>().value(0))
+}
+
+#[tokio::test]
+async fn test_nearest_to_text() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("text_len", Arc::new(TextLengthEmbed::default()))?;
+    db.embedding_registry().register(
+        "embed_fun",
+        Arc::new(MockEmbed::new("embed_fun".to_string(), 2)),
+    )?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![0, 1, 2])),
+            Arc::new(StringArray::from(vec!["a", "abcd", "abcdefgh"])),
+        ],
+    )?;
+    let tbl = db
+        .create_table(
+            "test",
+            RecordBatchIterator::new(vec![Ok(batch)], schema.clone()),
+        )
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "text_len",
+            Some("len_embedding"),
+        ))?
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "embed_fun",
+            Some("mock_embedding"),
+        ))?
+        .execute()
+        .await?;
+
+    // With more than one embedding column the column must be chosen
+    let res = tbl.query().nearest_to_text("abc").execute().await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+    let res = tbl
+        .query()
+        .nearest_to_text("abc")
+        .column("id")
+        .execute()
+        .await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    let nearest_id = |query: lancedb::query::VectorQuery| async move {
+        let batches = query
+            .limit(1)
+            .execute()
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        Result::Ok(
+            batches[0]
+                .column_by_name("id")
+                .unwrap()
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .value(0),
+        )
+    };
+    let query = tbl.query().nearest_to_text("abc").column("len_embedding");
+    assert_eq!(nearest_id(query).await?, 1);
+    // Strings are also accepted as query vectors
+    let query = tbl.vector_search("abcdefg")?.column("len_embedding");
+    assert_eq!(nearest_id(query).await?, 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_nearest_to_text_sparse() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("letters", Arc::new(LetterCountEmbed))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![0, 1, 2])),
+            Arc::new(StringArray::from(vec!["xyz", "aab", "bbbc"])),
+        ],
+    )?;
+    let tbl = db
+        .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+        .add_embedding(EmbeddingDefinition::new("text", "letters", Some("sparse")))?
+        .execute()
+        .await?;
+
+    // The text is embedded as a sparse vector and searched as one
+    let batches = tbl
+        .query()
+        .nearest_to_text("b")
+        .execute()
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    let ids = batches
+        .iter()
+        .flat_map(|batch| {
+            batch["id"]
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .values()
+                .to_vec()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(ids, vec![2, 1]);
+    assert!(batches[0].column_by_name("_score").is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_truncated_normalized_embeddings() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("text_stats", Arc::new(TextStatsEmbed))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![0, 1, 2])),
+            Arc::new(StringArray::from(vec!["a", "bbbb", "aab"])),
+        ],
+    )?;
+    let tbl = db
+        .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+        .add_embedding(
+            EmbeddingDefinition::new("text", "text_stats", Some("embeddings"))
+                .truncate_dim(2)
+                .normalize(true),
+        )?
+        .execute()
+        .await?;
+
+    // The embeddings are stored truncated and normalized
+    let batches = tbl
+        .query()
+        .only_if("id = 2")
+        .execute()
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    let embeddings = batches[0]["embeddings"].as_fixed_size_list();
+    assert_eq!(embeddings.value_length(), 2);
+    let embedding = embeddings.value(0);
+    let embedding = embedding.as_primitive::<Float32Type>().values();
+    let norm = 13.0_f32.sqrt();
+    assert!((embedding[0] - 3.0 / norm).abs() < 1e-6);
+    assert!((embedding[1] - 2.0 / norm).abs() < 1e-6);
+
+    // The options are stored with the definition
+    let definition = TableDefinition::try_from_rich_schema(tbl.schema().await?)?;
+    let embedding_definition = definition
+        .column_definitions
+        .iter()
+        .find_map(|cd| match &cd.kind {
+            ColumnKind::Embedding(ed) => Some(ed.clone()),
+            ColumnKind::Physical => None,
+        })
+        .unwrap();
+    assert_eq!(embedding_definition.truncate_dim, Some(2));
+    assert!(embedding_definition.normalize);
+
+    // Queries are embedded the same way, or their dimensions would not match
+    let batches = tbl
+        .query()
+        .nearest_to_text("bb")
+        .limit(1)
+        .execute()
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(
+        batches[0]["id"]
+            .as_primitive::<arrow_array::types::Int32Type>()
+            .value(0),
+        1
+    );
+
+    // Query embeddings of several rows returned as one array of values are
+    // processed one row at a time
+    let func = MatryoshkaEmbeddingFunction::new(Arc::new(TextStatsEmbed))
+        .truncate_dim(2)
+        .normalize(true);
+    let embeddings =
+        func.compute_query_embeddings(Arc::new(StringArray::from(vec!["aab", "bbbb"])))?;
+    let embeddings = embeddings.as_primitive::<Float32Type>().values();
+    assert_eq!(embeddings.len(), 4);
+    assert!((embeddings[0] - 3.0 / norm).abs() < 1e-6);
+    assert!((embeddings[1] - 2.0 / norm).abs() < 1e-6);
+    assert_eq!(&embeddings[2..], &[1.0, 0.0]);
+
+    // Truncating to more dimensions than the function has is an error
+    let res = tbl
+        .add_embedding_column(
+            EmbeddingDefinition::new("text", "text_stats", Some("too_long")).truncate_dim(4),
+        )
+        .execute()
+        .await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    // Embeddings without dimensions can not be processed
+    let func =
+        MatryoshkaEmbeddingFunction::new(Arc::new(FlatEmbed { dim: 0, len: 0 })).normalize(true);
+    let source: Arc<dyn Array> = Arc::new(StringArray::from(vec!["a"]));
+    let res = func.compute_source_embeddings(source.clone());
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+    let res = func.compute_query_embeddings(source.clone());
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    // Nor can query embeddings that are not a whole number of vectors
+    let func =
+        MatryoshkaEmbeddingFunction::new(Arc::new(FlatEmbed { dim: 2, len: 3 })).normalize(true);
+    let res = func.compute_query_embeddings(source);
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reembed() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("text_len", Arc::new(TextLengthEmbed::default()))?;
+    db.embedding_registry()
+        .register("text_stats", Arc::new(TextStatsEmbed))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let make_batch = |ids: Vec<i32>, texts: Vec<&str>| {
+        RecordBatchIterator::new(
+            vec![RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int32Array::from(ids)),
+                    Arc::new(StringArray::from(texts)),
+                ],
+            )],
+            schema.clone(),
+        )
+    };
+    let tbl = db
+        .create_table("test", make_batch(vec![0, 1, 2], vec!["a", "bbbb", "aab"]))
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "text_len",
+            Some("len_embedding"),
+        ))?
+        .execute()
+        .await?;
+
+    let nearest_id = |column: Option<&'static str>| {
+        let tbl = tbl.clone();
+        async move {
+            let mut query = tbl.query().nearest_to_text("bbb");
+            if let Some(column) = column {
+                query = query.column(column);
+            }
+            let batches = query
+                .limit(1)
+                .execute()
+                .await?
+                .try_collect::<Vec<_>>()
+                .await?;
+            Result::Ok(
+                batches[0]["id"]
+                    .as_primitive::<arrow_array::types::Int32Type>()
+                    .value(0),
+            )
+        }
+    };
+    // By length "bbb" is closest to "aab"
+    assert_eq!(nearest_id(None).await?, 2);
+
+    let res = tbl
+        .reembed(
+            "text",
+            EmbeddingDefinition::new("text", "text_stats", Some("stats_embedding")),
+        )
+        .execute()
+        .await;
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    let reembed = || {
+        tbl.reembed(
+            "len_embedding",
+            EmbeddingDefinition::new("text", "text_stats", Some("stats_embedding")),
+        )
+    };
+    // Fail after the new column was added, while building the index
+    let res = reembed()
+        .index(Index::BTree(BTreeIndexBuilder::default()))
+        .execute()
+        .await;
+    assert!(res.is_err());
+
+    // Rows written mid-migration get the new embeddings, but text queries
+    // without a column keep using the old ones
+    tbl.add(make_batch(vec![10], vec!["c"])).execute().await?;
+    assert_eq!(
+        tbl.count_rows(Some("stats_embedding IS NULL".to_string()))
+            .await?,
+        0
+    );
+    assert_eq!(nearest_id(None).await?, 2);
+    assert_eq!(nearest_id(Some("text")).await?, 2);
+    assert_eq!(nearest_id(Some("stats_embedding")).await?, 1);
+
+    // The old column has no index, so by default the new one gets none
+    reembed().execute().await?;
+    // Running it again does nothing
+    reembed().execute().await?;
+    assert!(tbl.list_indices().await?.is_empty());
+
+    // Queries of the source or no column use the new column, where "bbb" is
+    // closest to "bbbb", and the old column can still be queried by name
+    assert_eq!(nearest_id(None).await?, 1);
+    assert_eq!(nearest_id(Some("text")).await?, 1);
+    assert_eq!(nearest_id(Some("stats_embedding")).await?, 1);
+    assert_eq!(nearest_id(Some("len_embedding")).await?, 2);
+
+    // Both columns are computed for new rows until the old one is dropped
+    tbl.add(make_batch(vec![3], vec!["ccc"])).execute().await?;
+    for column in ["len_embedding", "stats_embedding"] {
+        assert_eq!(
+            tbl.count_rows(Some(format!("{} IS NULL", column))).await?,
+            0
+        );
+    }
+    tbl.drop_columns(&["len_embedding"]).await?;
+    tbl.add(make_batch(vec![4], vec!["dddd"])).execute().await?;
+    assert_eq!(tbl.count_rows(None).await?, 6);
+    assert_eq!(
+        tbl.count_rows(Some("stats_embedding IS NULL".to_string()))
+            .await?,
+        0
+    );
+    assert_eq!(nearest_id(None).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reembed_fills_missing_embeddings() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    db.embedding_registry()
+        .register("text_len", Arc::new(TextLengthEmbed::default()))?;
+    db.embedding_registry()
+        .register("text_stats", Arc::new(TextStatsEmbed))?;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![0, 1, 2])),
+            Arc::new(StringArray::from(vec![Some("a"), Some("bbbb"), None])),
+        ],
+    )?;
+    let tbl = db
+        .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "text_len",
+            Some("len_embedding"),
+        ))?
+        .execute()
+        .await?;
+
+    // The new column exists but the rows have no embeddings yet, as for rows
+    // written while the new column was added
+    let stats_type = DataType::new_fixed_size_list(DataType::Float32, 3, true);
+    let output_schema = Arc::new(Schema::new(vec![Field::new(
+        "stats_embedding",
+        stats_type.clone(),
+        true,
+    )]));
+    let mapper_schema = output_schema.clone();
+    let transform = NewColumnTransform::BatchUDF(BatchUDF {
+        mapper: Box::new(move |batch: &RecordBatch| -> lance::Result<RecordBatch> {
+            Ok(RecordBatch::try_new(
+                mapper_schema.clone(),
+                vec![new_null_array(&stats_type, batch.num_rows())],
+            )?)
+        }),
+        output_schema,
+        result_checkpoint: None,
+    });
+    tbl.add_columns(transform, Some(vec!["text".to_string()]))
+        .await?;
+
+    tbl.reembed(
+        "len_embedding",
+        EmbeddingDefinition::new("text", "text_stats", Some("stats_embedding")),
+    )
+    .execute()
+    .await?;
+    // Only the row without text has no embedding
+    assert_eq!(tbl.count_rows(None).await?, 3);
+    assert_eq!(
+        tbl.count_rows(Some("stats_embedding IS NULL".to_string()))
+            .await?,
+        1
+    );
+    assert_eq!(
+        tbl.count_rows(Some("stats_embedding IS NULL AND id = 2".to_string()))
+            .await?,
+        1
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_batched_embeddings() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    let slow_embed = Arc::new(SlowEmbed::default());
+    let func = BatchedEmbeddingFunction::new(slow_embed.clone())
+        .max_batch_size(3)
+        .max_concurrent_requests(2);
+    db.embedding_registry()
+        .register("slow_embed", Arc::new(func))?;
+
+    let schema = Arc::new(Schema::new(vec![Field::new("text", DataType::Utf8, true)]));
+    let data = RecordBatchIterator::new(
+        vec![RecordBatch::try_new(
+            schema.clone(),
+            vec![Arc::new(StringArray::from_iter_values(
+                (0..10).map(|i| i.to_string()),
+            ))],
+        )
+        .unwrap()]
+        .into_iter()
+        .map(Ok),
+        schema,
+    );
+    let tbl = db
+        .create_table("test", Box::new(data))
+        .add_embedding(EmbeddingDefinition::new("text", "slow_embed", None))?
+        .execute()
+        .await?;
+    assert_eq!(tbl.count_rows(None).await?, 10);
+
+    let mut batch_sizes = slow_embed.batch_sizes.lock().unwrap().clone();
+    batch_sizes.sort();
+    assert_eq!(batch_sizes, vec![1, 3, 3, 3]);
+    assert_eq!(slow_embed.max_in_flight.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batched_embeddings_current_thread() -> Result<()> {
+    // The sync entry points must not need a multi-threaded runtime
+    let slow_embed = Arc::new(SlowEmbed::default());
+    let func = BatchedEmbeddingFunction::new(slow_embed.clone())
+        .max_batch_size(3)
+        .max_concurrent_requests(2);
+    let source: Arc<dyn Array> = Arc::new(StringArray::from_iter_values(
+        (0..10).map(|i| i.to_string()),
+    ));
+    let embeddings = func.compute_source_embeddings(source)?;
+    assert_eq!(embeddings.len(), 10);
+    assert_eq!(slow_embed.batch_sizes.lock().unwrap().len(), 4);
+
+    let cache = Arc::new(MemoryEmbeddingCache::default());
+    let counting = Arc::new(CountingEmbed::default());
+    let func = CachedEmbeddingFunction::new(counting.clone(), cache);
+    let source: Arc<dyn Array> = Arc::new(StringArray::from(vec!["a", "b"]));
+    func.compute_source_embeddings(source.clone())?;
+    func.compute_source_embeddings(source)?;
+    assert_eq!(counting.num_embedded.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_cached_embeddings() -> Result<()> {
+    let tempdir = tempfile::tempdir().unwrap();
+    let tempdir = tempdir.path().to_str().unwrap();
+    let db = connect(tempdir).execute().await?;
+    let counting = Arc::new(CountingEmbed::default());
+    let cache = Arc::new(TableEmbeddingCache::new(db.clone(), "cache"));
+    db.embedding_registry().register(
+        "cached",
+        Arc::new(CachedEmbeddingFunction::new(counting.clone(), cache)),
+    )?;
+
+    let tbl = db
+        .create_table("test", create_some_records()?)
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "cached",
+            Some("embeddings"),
+        ))?
+        .execute()
+        .await?;
+    assert_eq!(counting.num_embedded.load(Ordering::SeqCst), 2);
+
+    // Unchanged documents are not embedded again, even with a new cache
+    // that shares the table
+    let cache = Arc::new(TableEmbeddingCache::new(db.clone(), "cache"));
+    db.embedding_registry().register(
+        "cached",
+        Arc::new(CachedEmbeddingFunction::new(counting.clone(), cache)),
+    )?;
+    tbl.add(create_some_records()?).execute().await?;
+    assert_eq!(counting.num_embedded.load(Ordering::SeqCst), 2);
+    assert_eq!(tbl.count_rows(None).await?, 4);
+    assert_eq!(
+        tbl.count_rows(Some("embeddings IS NULL".to_string()))
+            .await?,
+        0
+    );
+
+    // A different namespace, for example another model, misses the cache
+    let cache = Arc::new(MemoryEmbeddingCache::default());
+    let func = CachedEmbeddingFunction::new(counting.clone(), cache).namespace("other");
+    let source: Arc<dyn Array> = Arc::new(StringArray::from(vec!["a", "b", "a"]));
+    func.compute_source_embeddings(source.clone())?;
+    assert_eq!(counting.num_embedded.load(Ordering::SeqCst), 5);
+    let embeddings = func.compute_source_embeddings(source)?;
+    assert_eq!(counting.num_embedded.load(Ordering::SeqCst), 5);
+    assert_eq!(embeddings.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_cache_namespace_defaults_to_config() -> Result<()> {
+    let cache = Arc::new(MemoryEmbeddingCache::default());
+    let source: Arc<dyn Array> = Arc::new(StringArray::from(vec!["a", "b"]));
+
+    // Two models with the same dimensions do not share embeddings
+    let v1 = Arc::new(CountingEmbed::new("counting-v1"));
+    let v2 = Arc::new(CountingEmbed::new("counting-v2"));
+    CachedEmbeddingFunction::new(v1.clone(), cache.clone())
+        .compute_source_embeddings(source.clone())?;
+    CachedEmbeddingFunction::new(v2.clone(), cache.clone())
+        .compute_source_embeddings(source.clone())?;
+    assert_eq!(v1.num_embedded.load(Ordering::SeqCst), 2);
+    assert_eq!(v2.num_embedded.load(Ordering::SeqCst), 2);
+
+    // The same model does
+    let v1_again = Arc::new(CountingEmbed::new("counting-v1"));
+    CachedEmbeddingFunction::new(v1_again.clone(), cache.clone())
+        .compute_source_embeddings(source.clone())?;
+    assert_eq!(v1_again.num_embedded.load(Ordering::SeqCst), 0);
+
+    // A function without a configuration needs a namespace
+    let func = CachedEmbeddingFunction::new(Arc::new(TextLengthEmbed::default()), cache);
+    let res = func.compute_source_embeddings(source);
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    Ok(())
+}
+
 fn create_some_records() -> Result<impl IntoArrow> {
     const TOTAL: usize = 2;
 
@@ -322,4 +1107,280 @@ impl EmbeddingFunction for MockEmbed {
     fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
         unimplemented!()
     }
+
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        Some(
+            EmbeddingFunctionConfig::new("mock")
+                .model(&self.name)
+                .dimensions(self.dim),
+        )
+    }
+}
+
+/// Recreates [`MockEmbed`]s from their configuration
+#[derive(Debug)]
+struct MockFactory;
+
+impl EmbeddingFunctionFactory for MockFactory {
+    fn create(&self, config: &EmbeddingFunctionConfig) -> Result<Arc<dyn EmbeddingFunction>> {
+        Ok(Arc::new(MockEmbed::new(
+            config.model.clone().unwrap(),
+            config.dimensions.unwrap(),
+        )))
+    }
+}
+
+/// Embeds text as sparse vectors of the number of times each byte occurs
+#[derive(Debug)]
+struct LetterCountEmbed;
+
+impl LetterCountEmbed {
+    fn embed(input: &dyn Array) -> Result<Arc<dyn Array>> {
+        let vectors = input.as_string::<i32>().iter().map(|text| {
+            text.map(|text| {
+                let mut counts = HashMap::<u32, f32>::new();
+                for byte in text.bytes() {
+                    *counts.entry(byte as u32).or_default() += 1.0;
+                }
+                SparseVector::from(counts)
+            })
+        });
+        sparse_vectors_to_array(vectors)
+    }
+}
+
+impl EmbeddingFunction for LetterCountEmbed {
+    fn name(&self) -> &str {
+        "letters"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(sparse_vector_type()))
+    }
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Self::embed(source.as_ref())
+    }
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Self::embed(input.as_ref())
+    }
+}
+
+/// Embeds text as its length, so that stale embeddings can be detected
+#[derive(Debug)]
+struct TextLengthEmbed {
+    dest_type: DataType,
+}
+
+impl Default for TextLengthEmbed {
+    fn default() -> Self {
+        Self {
+            dest_type: DataType::new_fixed_size_list(DataType::Float32, 1, true),
+        }
+    }
+}
+
+impl EmbeddingFunction for TextLengthEmbed {
+    fn name(&self) -> &str {
+        "text_len"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Borrowed(&self.dest_type))
+    }
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        let lengths = source
+            .as_string::<i32>()
+            .iter()
+            .map(|text| text.map(|text| text.len() as f32).unwrap_or_default());
+        let values = Arc::new(Float32Array::from_iter_values(lengths));
+        Ok(Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            1,
+            values,
+            None,
+        )))
+    }
+
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        let lengths = input
+            .as_string::<i32>()
+            .iter()
+            .map(|text| text.map(|text| text.len() as f32).unwrap_or_default());
+        Ok(Arc::new(Float32Array::from_iter_values(lengths)))
+    }
+}
+
+/// Embeds text as its length, its number of `a`s and one
+#[derive(Debug)]
+struct TextStatsEmbed;
+
+impl TextStatsEmbed {
+    fn embed(input: &dyn Array) -> Float32Array {
+        input
+            .as_string::<i32>()
+            .iter()
+            .flat_map(|text| {
+                let text = text.unwrap_or_default();
+                let a_count = text.chars().filter(|c| *c == 'a').count();
+                [text.len() as f32, a_count as f32, 1.0]
+            })
+            .collect()
+    }
+}
+
+impl EmbeddingFunction for TextStatsEmbed {
+    fn name(&self) -> &str {
+        "text_stats"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::new_fixed_size_list(
+            DataType::Float32,
+            3,
+            true,
+        )))
+    }
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Ok(Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            3,
+            Arc::new(Self::embed(source.as_ref())),
+            None,
+        )))
+    }
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Ok(Arc::new(Self::embed(input.as_ref())))
+    }
+}
+
+/// Declares `dim` dimensions, but embeds any input as `len` ones
+#[derive(Debug)]
+struct FlatEmbed {
+    dim: i32,
+    len: usize,
+}
+
+impl EmbeddingFunction for FlatEmbed {
+    fn name(&self) -> &str {
+        "flat"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::new_fixed_size_list(
+            DataType::Float32,
+            self.dim,
+            true,
+        )))
+    }
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Ok(Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            self.dim,
+            Arc::new(Float32Array::from(vec![
+                1.0;
+                source.len() * self.dim as usize
+            ])),
+            None,
+        )))
+    }
+    fn compute_query_embeddings(&self, _input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        Ok(Arc::new(Float32Array::from(vec![1.0; self.len])))
+    }
+}
+
+/// Records how it is called, taking a while for each call
+#[derive(Debug, Default)]
+struct SlowEmbed {
+    batch_sizes: std::sync::Mutex<Vec<usize>>,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl AsyncEmbeddingFunction for SlowEmbed {
+    fn name(&self) -> &str {
+        "slow_embed"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::Utf8))
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        Ok(Cow::Owned(DataType::new_fixed_size_list(
+            DataType::Float32,
+            1,
+            true,
+        )))
+    }
+    async fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        self.batch_sizes.lock().unwrap().push(source.len());
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+        let values = Arc::new(Float32Array::from(vec![1.0; source.len()]));
+        Ok(Arc::new(FixedSizeListArray::new(
+            Arc::new(Field::new("item", DataType::Float32, true)),
+            1,
+            values,
+            None,
+        )))
+    }
+
+    #[allow(unused_variables)]
+    async fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        unimplemented!()
+    }
+}
+
+/// Counts the values it embeds
+#[derive(Debug)]
+struct CountingEmbed {
+    inner: MockEmbed,
+    num_embedded: AtomicUsize,
+}
+
+impl Default for CountingEmbed {
+    fn default() -> Self {
+        Self::new("counting-v1")
+    }
+}
+
+impl CountingEmbed {
+    fn new(model: &str) -> Self {
+        Self {
+            inner: MockEmbed::new(model.to_string(), 2),
+            num_embedded: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl EmbeddingFunction for CountingEmbed {
+    fn name(&self) -> &str {
+        "counting"
+    }
+    fn source_type(&self) -> Result<Cow<DataType>> {
+        self.inner.source_type()
+    }
+    fn dest_type(&self) -> Result<Cow<DataType>> {
+        self.inner.dest_type()
+    }
+    fn compute_source_embeddings(&self, source: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        self.num_embedded.fetch_add(source.len(), Ordering::SeqCst);
+        self.inner.compute_source_embeddings(source)
+    }
+    fn compute_query_embeddings(&self, input: Arc<dyn Array>) -> Result<Arc<dyn Array>> {
+        self.inner.compute_query_embeddings(input)
+    }
+    fn config(&self) -> Option<EmbeddingFunctionConfig> {
+        self.inner.config()
+    }
 }
@@ -0,0 +1,279 @@
+// Copyright 2024 LanceDB Developers.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#![cfg(feature = "http-embeddings")]
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+};
+
+use arrow_array::{
+    cast::AsArray, types::Int32Type, Array, Int32Array, RecordBatch, RecordBatchIterator,
+    StringArray,
+};
+use arrow_schema::{DataType, Field, Schema};
+use futures::TryStreamExt;
+use lancedb::{
+    connect,
+    embeddings::{
+        http::{HttpEmbeddingFunction, HttpEmbeddingFunctionFactory},
+        EmbeddingDefinition, EmbeddingFunction, EmbeddingFunctionFactory,
+    },
+    query::{ExecutableQuery, QueryBase},
+    Error, Result,
+};
+use serde_json::{json, Value};
+
+/// The requests received by a [`StandInServer`]
+#[derive(Debug, Default)]
+struct Requests {
+    paths: Vec<String>,
+    authorization: Vec<Option<String>>,
+    tenants: Vec<Option<String>>,
+    batch_sizes: Vec<usize>,
+}
+
+/// A minimal embedding server that embeds text as `[length, 1.0]`
+///
+/// It speaks both the OpenAI and the Ollama API.
+struct StandInServer {
+    url: String,
+    requests: Arc<Mutex<Requests>>,
+}
+
+impl StandInServer {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Requests::default()));
+        let server_requests = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let requests = server_requests.clone();
+                std::thread::spawn(move || Self::serve(stream.unwrap(), requests));
+            }
+        });
+        Self { url, requests }
+    }
+
+    fn serve(mut stream: TcpStream, requests: Arc<Mutex<Requests>>) {
+        let mut buffer = Vec::new();
+        loop {
+            // Read the head of the request
+            let head_end = loop {
+                if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break pos + 4;
+                }
+                let mut chunk = [0; 4096];
+                let n = stream.read(&mut chunk).unwrap_or(0);
+                if n == 0 {
+                    return;
+                }
+                buffer.extend_from_slice(&chunk[..n]);
+            };
+            let head = String::from_utf8(buffer[..head_end].to_vec()).unwrap();
+            let mut lines = head.lines();
+            let path = lines.next().unwrap().split(' ').nth(1).unwrap().to_string();
+            let mut content_length = 0;
+            let mut authorization = None;
+            let mut tenant = None;
+            for line in lines {
+                if let Some((name, value)) = line.split_once(':') {
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => content_length = value.trim().parse().unwrap(),
+                        "authorization" => authorization = Some(value.trim().to_string()),
+                        "x-tenant" => tenant = Some(value.trim().to_string()),
+                        _ => {}
+                    }
+                }
+            }
+            while buffer.len() < head_end + content_length {
+                let mut chunk = [0; 4096];
+                let n = stream.read(&mut chunk).unwrap();
+                buffer.extend_from_slice(&chunk[..n]);
+            }
+            let body: Value =
+                serde_json::from_slice(&buffer[head_end..head_end + content_length]).unwrap();
+            buffer.drain(..head_end + content_length);
+
+            let embeddings = body["input"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|text| json!([text.as_str().unwrap().len() as f32, 1.0]))
+                .collect::<Vec<_>>();
+            {
+                let mut requests = requests.lock().unwrap();
+                requests.paths.push(path.clone());
+                requests.authorization.push(authorization);
+                requests.tenants.push(tenant);
+                requests.batch_sizes.push(embeddings.len());
+            }
+            let response = if path.ends_with("/api/embed") {
+                json!({ "embeddings": embeddings })
+            } else {
+                // Out of order, as servers may return them
+                let data = embeddings
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .map(|(index, embedding)| json!({ "index": index, "embedding": embedding }))
+                    .collect::<Vec<_>>();
+                json!({ "object": "list", "data": data })
+            };
+            let response = response.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                response.len(),
+                response
+            );
+            if stream.write_all(response.as_bytes()).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_openai_compatible() -> Result<()> {
+    let server = StandInServer::start();
+    let func = HttpEmbeddingFunction::openai_compatible(format!("{}/v1", server.url), "stand-in")
+        .api_key("secret-key")
+        .max_batch_size(2)
+        .build()?;
+
+    // The dimensions are probed on first use
+    assert_eq!(
+        func.dest_type()?.as_ref(),
+        &DataType::new_fixed_size_list(DataType::Float32, 2, true)
+    );
+    // The API key is never part of the configuration
+    let config = serde_json::to_string(&func.config()).unwrap();
+    assert!(!config.contains("secret-key"));
+
+    let tempdir = tempfile::tempdir().unwrap();
+    let db = connect(tempdir.path().to_str().unwrap()).execute().await?;
+    db.embedding_registry()
+        .register("stand_in", Arc::new(func))?;
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("text", DataType::Utf8, true),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![0, 1, 2, 3, 4])),
+            Arc::new(StringArray::from(vec![
+                Some("a"),
+                Some("abc"),
+                None,
+                Some("abcdef"),
+                Some("abcdefghi"),
+            ])),
+        ],
+    )?;
+    let tbl = db
+        .create_table("test", RecordBatchIterator::new(vec![Ok(batch)], schema))
+        .add_embedding(EmbeddingDefinition::new(
+            "text",
+            "stand_in",
+            Some("embeddings"),
+        ))?
+        .execute()
+        .await?;
+    assert_eq!(
+        tbl.count_rows(Some("embeddings IS NULL".to_string()))
+            .await?,
+        1
+    );
+    {
+        let requests = server.requests.lock().unwrap();
+        assert!(requests.paths.iter().all(|path| path == "/v1/embeddings"));
+        assert!(requests
+            .authorization
+            .iter()
+            .all(|auth| auth.as_deref() == Some("Bearer secret-key")));
+        // The probe, then the four values in batches of two
+        assert_eq!(requests.batch_sizes, vec![1, 2, 2]);
+    }
+
+    let batches = tbl
+        .query()
+        .nearest_to_text("abcde")
+        .only_if("text IS NOT NULL")
+        .limit(1)
+        .execute()
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(
+        batches[0]
+            .column_by_name("id")
+            .unwrap()
+            .as_primitive::<Int32Type>()
+            .value(0),
+        3
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_ollama() -> Result<()> {
+    let server = StandInServer::start();
+    let func = HttpEmbeddingFunction::ollama(&server.url, "stand-in")
+        .dimensions(2)
+        .header("x-tenant", "test")
+        .build()?;
+
+    let source = Arc::new(StringArray::from(vec!["a", "abcd"]));
+    let embeddings = func.compute_source_embeddings(source)?;
+    let embeddings = embeddings.as_fixed_size_list();
+    assert_eq!(embeddings.len(), 2);
+    assert_eq!(
+        embeddings
+            .value(1)
+            .as_primitive::<arrow_array::types::Float32Type>()
+            .values()
+            .to_vec(),
+        vec![4.0, 1.0]
+    );
+    {
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests.paths, vec!["/api/embed".to_string()]);
+        assert_eq!(requests.authorization, vec![None]);
+        assert_eq!(requests.tenants, vec![Some("test".to_string())]);
+    }
+
+    // Header values are not stored, so the function cannot be recreated
+    let res = HttpEmbeddingFunctionFactory.create(&func.config().unwrap());
+    assert!(matches!(res, Err(Error::InvalidInput { .. })));
+
+    // Unless they are read from the environment
+    std::env::set_var("LANCEDB_TEST_HTTP_TENANT", "from-env");
+    let func = HttpEmbeddingFunction::ollama(&server.url, "stand-in")
+        .dimensions(2)
+        .header_env("x-tenant", "LANCEDB_TEST_HTTP_TENANT")
+        .build()?;
+    let config = func.config().unwrap();
+    assert!(!serde_json::to_string(&config).unwrap().contains("from-env"));
+    let func = HttpEmbeddingFunctionFactory.create(&config)?;
+    func.compute_source_embeddings(Arc::new(StringArray::from(vec!["a"])))?;
+    assert_eq!(
+        server.requests.lock().unwrap().tenants.last(),
+        Some(&Some("from-env".to_string()))
+    );
+
+    Ok(())
+}